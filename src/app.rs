@@ -1,43 +1,212 @@
-use std::{fs, marker::PhantomData, path::Path};
+use std::{
+    collections::HashMap,
+    env, fs,
+    marker::PhantomData,
+    mem,
+    path::Path,
+    sync::mpsc::{self, Receiver, TryRecvError},
+    time::{Duration, Instant},
+};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use vello::{
     kurbo::Affine,
-    peniko::{Brush, Color, Fill},
+    peniko::{Brush, Fill},
 };
 use winit::{
-    event::ElementState,
-    keyboard::{KeyCode, PhysicalKey},
+    event::{ElementState, MouseButton, MouseScrollDelta},
+    keyboard::{Key, KeyCode, ModifiersState, NamedKey, PhysicalKey},
 };
 
-use crate::base::{
-    AppContext, AppEvent, AppFont, AppHandler, AppRenderer, DrawFillRectangleOptions,
-    DrawTextOptions, Position, Size,
+use crate::{
+    base::{
+        discover_emoji_font, discover_monospace_font, AppContext, AppEvent, AppEventSender,
+        AppFont, AppHandler, AppKeyEvent, AppRenderer, CursorShape, DrawCursorOptions,
+        DrawFillRectangleOptions, DrawLineOptions, DrawTextOptions, Position, Size,
+    },
+    editor::{
+        char_width, execute_command, layout_tabs, Buffer, BufferList, CharFind, Confirm, DiskState,
+        FileLocation, FileType, FileWatcher, FocusPath, Jump, JumpEntry, JumpList, Layout, Mode,
+        MotionKind, Notification, NotificationLevel, Operator, PaneArea, RegisterText, Registers,
+        SelectionKind, SplitDirection, Step, Tab, TextObject, Theme, View, CLIPBOARD_REGISTER,
+        UNNAMED_REGISTER,
+    },
 };
 
 const APP_NAME: &str = env!("CARGO_PKG_NAME");
 const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+// moving the cursor further than this many lines is remembered as a jump
+const JUMP_LINES: usize = 5;
+
+// guards against macros that (indirectly) play themselves forever
+const MAX_MACRO_DEPTH: usize = 100;
+
+// a frame after being idle for a while only animates as far as this many seconds
+const MAX_FRAME_TIME: f64 = 0.05;
+
+// how far one notch of the mouse wheel scrolls, in lines or cells
+const WHEEL_SCROLL_LINES: f32 = 3.0;
+
+// how long notifications are shown when nothing else is asked for
+const NOTIFICATION_DURATION: Duration = Duration::from_secs(3);
+
+// the pending key for the window commands after CTRL-W
+const WINDOW_KEY: char = '\u{17}';
+
+// the command line flag for rendering on the CPU, for when the GPU renderer
+// is broken rather than missing
+const CPU_FLAG: &str = "--cpu";
+
 fn load_monospace_font() -> AppFont {
-    let monospace_font_path = if cfg!(windows) {
-        Path::new(r"C:\Windows\Fonts\consola.ttf")
-    } else {
-        panic!("don't know where to find monospace font");
-    };
+    let monospace_font_path =
+        discover_monospace_font().expect("cannot find a monospace font on this system");
 
     let monospace_font_bytes = fs::read(monospace_font_path).expect("fail to load monospace font");
 
-    monospace_font_bytes.into()
+    let mut monospace_font =
+        AppFont::try_from(monospace_font_bytes).expect("fail to load monospace font");
+    // emoji are drawn as missing glyphs without one, as they are with an
+    // emoji font that can't be read
+    if let Some(emoji_font_bytes) = discover_emoji_font().and_then(|path| fs::read(path).ok()) {
+        let _ = monospace_font.set_emoji_fallback(emoji_font_bytes);
+    }
+    monospace_font
+}
+
+// the file given on the command line, if any
+fn location_from_args() -> Option<FileLocation> {
+    env::args()
+        .skip(1)
+        .find(|arg| arg != CPU_FLAG)
+        .map(|arg| FileLocation::parse(&arg))
+}
+
+fn cpu_rendering_from_args() -> bool {
+    env::args().skip(1).any(|arg| arg == CPU_FLAG)
+}
+
+// starts loading the file at `path` in the background
+fn load_buffer(path: String) -> Receiver<Result<Buffer>> {
+    let (sender, receiver) = mpsc::channel();
+    Buffer::load_async(path, sender);
+    receiver
 }
 
 pub struct App {
     monospace_font: AppFont,
-    text: String,
-    cursor_pos: Position<u32>,
+    // the view of the focused pane
+    view: View,
+    // the panes the window is split into, holding the views of the other panes
+    layout: Layout,
+    focus: FocusPath,
+    // where the panes are drawn, in cells
+    layout_area: PaneArea,
+    // the open buffers that aren't shown in any pane
+    buffers: BufferList,
+    jump_list: JumpList,
+    // where the cursor was before the last jump, for '' and ``
+    last_jump: Option<JumpEntry>,
+    // uppercase marks, which can be jumped to from any buffer
+    global_marks: HashMap<char, JumpEntry>,
+    // whether the last key moved through the jump list, so isn't a jump itself
+    following_jump: bool,
+    // the buffer being loaded into the view, if it hasn't arrived yet
+    pending_load: Option<Receiver<Result<Buffer>>>,
+    // where to move the cursor once the buffer has loaded
+    pending_jump: Option<Jump>,
+    event_sender: AppEventSender,
+    // watches the file of the buffer for changes by other programs
+    watcher: Option<FileWatcher>,
+    // a question that takes every key until it is answered
+    confirm: Option<Confirm<App>>,
+    command_line: String,
+    // shown in the last row when not typing a command, e.g. command errors
+    message: Option<String>,
+    // shown above the last row until they expire, the latest one on top
+    notifications: Vec<Notification>,
+    // the first key of a normal mode command that takes two keys, like g-
+    pending_normal_key: Option<char>,
+    // the last f, F, t or T, for ; and , to repeat
+    last_find: Option<CharFind>,
+    // typed before the operator waiting for its motion, like the 2 in 2dw
+    operator_count: Option<usize>,
+    // typed before a normal mode command, to repeat it
+    count: Option<usize>,
+    theme: Theme,
+    registers: Registers,
+    // named with " for the next yank, delete or paste
+    register: Option<char>,
+    // the register and events recorded so far, while recording a macro
+    recording: Option<(char, Vec<AppEvent>)>,
+    last_macro: Option<char>,
+    // how many macros are being played inside each other
+    macro_depth: usize,
+    // the keys typed since normal mode was last idle, which may be a change
+    change_events: Vec<AppEvent>,
+    // the buffer and its change count when those keys started, or None if
+    // they can't be repeated
+    change_start: Option<(usize, usize)>,
+    // the keys of the last command that changed the buffer, for . to repeat
+    last_change: Option<Vec<AppEvent>>,
+    // whether . is replaying the last change, which isn't recorded again
+    replaying: bool,
+    // macros replay events through handle_events, which needs the screen size
+    screen_size: Size<u32>,
+    // in pixels from the top left of the window
+    mouse_pos: Position<f64>,
+    // touchpads scroll by pixels, which add up to whole lines and cells over events
+    vertical_scroll: f64,
+    horizontal_scroll: f64,
+    // text being composed with an input method, which isn't in the buffer yet
+    preedit: Option<String>,
+    // where the input method's cursor is in the preedit, in bytes, which
+    // covers the part being converted when it isn't empty
+    preedit_cursor: Option<(usize, usize)>,
+    // for how far to move animations each frame
+    last_frame_time: Instant,
+    // whether any view is still scrolling smoothly
+    scrolling: bool,
+    // whether the window has the keyboard focus, which is when the cursor blinks
+    window_focused: bool,
+    // whether the cursor is in the shown half of blinking
+    cursor_visible: bool,
+    last_blink_toggle: Instant,
+    // whether anything changed since the last frame was drawn, which has
+    // nothing to do with the buffer having unsaved changes
+    is_dirty: bool,
 }
 
 impl AppHandler for App {
     fn handle_events(&mut self, event: AppEvent, screen_size: Size<u32>) {
+        self.screen_size = screen_size;
+        // the mouse is only drawn as the cell it is over, so moving it within
+        // a cell leaves the frame as it was
+        if !matches!(event, AppEvent::MouseMoved { .. }) {
+            self.is_dirty = true;
+        }
+
+        // keys typed while playing a macro come from the macro, not the user
+        if let (Some((_, events)), AppEvent::KeyboardEvent { .. }) = (&mut self.recording, &event) {
+            if self.macro_depth == 0 && !self.replaying {
+                events.push(event.clone());
+            }
+        }
+        // a macro is repeated as a whole, so the keys it plays aren't changes of their own
+        let track_change = self.macro_depth == 0
+            && !self.replaying
+            && matches!(
+                event,
+                AppEvent::KeyboardEvent { .. } | AppEvent::ImeCommit { .. }
+            );
+        if track_change {
+            if self.change_events.is_empty() {
+                let buffer = self.view.buffer();
+                self.change_start = Some((buffer.id(), buffer.change_count()));
+            }
+            self.change_events.push(event.clone());
+        }
+
         // TODO: This should not be everywhere?
         let font_size = 16.0;
         let bounds = self
@@ -47,41 +216,176 @@ impl AppHandler for App {
         let max_x = screen_size.w / (bounds.w.ceil() as u32);
         let max_y = screen_size.h / (bounds.h.ceil() as u32);
 
+        // the last row is reserved for the command line, and the first for
+        // the tab bar if it is shown
+        let tab_rows = self.tab_rows();
+        self.layout_area = PaneArea {
+            pos: Position { x: 0, y: tab_rows },
+            size: Size {
+                w: max_x as usize,
+                h: max_y.saturating_sub(1 + tab_rows as u32) as usize,
+            },
+        };
+        self.resize_panes();
+
+        let jump_start = self.jump_entry();
+
         match event {
             AppEvent::KeyboardEvent {
                 event,
                 is_synthetic,
+                modifiers,
             } => {
-                if matches!(event.state, ElementState::Pressed) {
-                    match event.physical_key {
-                        PhysicalKey::Code(KeyCode::KeyH) => {
-                            self.cursor_pos.x = self.cursor_pos.x.saturating_sub(1);
-                        }
-                        PhysicalKey::Code(KeyCode::KeyK) => {
-                            self.cursor_pos.y = self.cursor_pos.y.saturating_sub(1);
-                        }
-                        PhysicalKey::Code(KeyCode::KeyL) => {
-                            self.cursor_pos.x = (self.cursor_pos.x + 1).min(max_x);
-                        }
-                        PhysicalKey::Code(KeyCode::KeyJ) => {
-                            self.cursor_pos.y = (self.cursor_pos.y + 1).min(max_y);
+                // the cursor stays visible while typing
+                self.show_cursor();
+
+                // there is nothing to edit until the buffer has loaded, and
+                // synthetic presses are keys that were already held down when
+                // the window got the focus, which weren't typed into it
+                if matches!(event.state, ElementState::Pressed)
+                    && !is_synthetic
+                    && self.pending_load.is_none()
+                {
+                    if self.view.substitute_progress().is_some() {
+                        self.handle_busy_key(&event);
+                    } else if self.confirm.is_some() {
+                        self.handle_confirm_key(&event);
+                    } else {
+                        match self.view.mode() {
+                            Mode::Normal => self.handle_normal_key(&event, modifiers),
+                            Mode::Insert => self.handle_insert_key(&event),
+                            Mode::Replace => self.handle_replace_key(&event),
+                            Mode::Command => self.handle_command_key(&event),
+                            Mode::Search => self.handle_search_key(&event),
+                            Mode::Visual | Mode::VisualLine | Mode::VisualBlock => {
+                                self.handle_visual_key(&event)
+                            }
+                            Mode::OperatorPending(operator) => {
+                                self.handle_operator_key(&event, operator)
+                            }
                         }
-                        _ => {}
                     }
                 }
+            }
+            // the panes were already fit to the new size above
+            AppEvent::ResizeEvent => {}
+            AppEvent::MouseButtonEvent {
+                state,
+                button,
+                position,
+            } => {
+                if state == ElementState::Pressed
+                    && button == MouseButton::Left
+                    && self.pending_load.is_none()
+                    && self.view.substitute_progress().is_none()
+                {
+                    // the view works in cells, like the rest of the editor
+                    let cell = Position {
+                        x: position.x / bounds.w as f64,
+                        y: position.y / bounds.h as f64,
+                    };
 
-                self.text = format!("Event: is_synthetic is {}, rest: {:?}", is_synthetic, event);
+                    if cell.y < tab_rows as f64 {
+                        self.handle_tab_click(cell.x as usize, max_x as usize);
+                    } else {
+                        self.handle_pane_click(cell);
+                    }
+                }
             }
-            AppEvent::ResizeEvent { new_size } => {
-                self.cursor_pos.x = self.cursor_pos.x.min(max_x);
-                self.cursor_pos.y = self.cursor_pos.y.min(max_y);
+            AppEvent::MouseWheelEvent { delta }
+                if self.pending_load.is_none() && self.view.substitute_progress().is_none() =>
+            {
+                // positive deltas move the content down and right, revealing
+                // what is above and on the left
+                match delta {
+                    MouseScrollDelta::LineDelta(x, y) => {
+                        self.horizontal_scroll -= (x * WHEEL_SCROLL_LINES) as f64;
+                        self.vertical_scroll -= (y * WHEEL_SCROLL_LINES) as f64;
+                    }
+                    MouseScrollDelta::PixelDelta(delta) => {
+                        self.horizontal_scroll -= delta.x / bounds.w as f64;
+                        self.vertical_scroll -= delta.y / bounds.h as f64;
+                    }
+                }
 
-                self.text = format!("Event: Resize to {:?}", new_size);
+                let cells = self.horizontal_scroll.trunc();
+                self.horizontal_scroll -= cells;
+                self.view.scroll_horizontally(cells as isize);
+
+                let lines = self.vertical_scroll.trunc();
+                self.vertical_scroll -= lines;
+                self.view.scroll_vertically(lines as isize);
+            }
+            // there is nothing to scroll until the buffer has loaded
+            AppEvent::MouseWheelEvent { .. } => {}
+            AppEvent::ImePreedit { text, cursor } => {
+                self.show_cursor();
+                // an empty preedit is the input method clearing it
+                self.preedit = Some(text).filter(|text| !text.is_empty());
+                self.preedit_cursor = cursor;
+            }
+            AppEvent::ImeCommit { text } => {
+                self.show_cursor();
+                self.preedit = None;
+                self.preedit_cursor = None;
+                // the input method is only on in insert mode, but may still
+                // commit what it had after leaving it
+                if matches!(self.view.mode(), Mode::Insert)
+                    && self.pending_load.is_none()
+                    && self.confirm.is_none()
+                    && self.view.substitute_progress().is_none()
+                {
+                    text.chars()
+                        .filter(|ch| !ch.is_control())
+                        .for_each(|ch| self.view.insert_typed_char(ch));
+                }
+            }
+            AppEvent::MouseMoved { position } => {
+                let cell_size = Size {
+                    w: bounds.w as f64,
+                    h: bounds.h as f64,
+                };
+                let old_cell = self.hovered_cell(cell_size);
+                self.mouse_pos = position;
+                if self.hovered_cell(cell_size) != old_cell {
+                    self.is_dirty = true;
+                }
+            }
+            AppEvent::FocusChanged { focused } => {
+                self.window_focused = focused;
+                // a window without focus has a steady cursor
+                self.show_cursor();
+            }
+            AppEvent::FileChangedOnDisk { path } => {
+                self.handle_file_changed(&path);
+            }
+            AppEvent::RendererWarning { message } => {
+                self.notify(message, NotificationLevel::Warning, NOTIFICATION_DURATION);
+            }
+            AppEvent::RenderFailed { message } => {
+                self.notify(message, NotificationLevel::Error, NOTIFICATION_DURATION);
             }
         }
+
+        self.record_jump(jump_start);
+        if track_change {
+            self.finish_change();
+        }
     }
 
     fn render(&mut self, renderer: &mut AppRenderer, screen_size: Size<u32>) {
+        renderer.set_background(self.theme.background);
+        self.poll_pending_load();
+        if let Some(result) = self.view.continue_substitute() {
+            self.message = result.err().map(|error| error.to_string());
+        }
+        self.animate_scroll();
+        self.blink_cursor();
+        self.view.update_highlights();
+        for view in self.layout.views_mut() {
+            view.update_highlights();
+        }
+
         let font_size = 16.0;
 
         let bounds = self
@@ -91,66 +395,1955 @@ impl AppHandler for App {
         let single_space_width = bounds.w as f64;
         let font_height = bounds.h as f64;
 
-        renderer.draw_fill_rectangle(DrawFillRectangleOptions {
-            pos: Position {
-                x: self.cursor_pos.x as f64 * single_space_width,
-                y: self.cursor_pos.y as f64 * font_height,
+        let tab_rows = self.tab_rows();
+        if tab_rows > 0 {
+            self.render_tab_bar(
+                renderer,
+                screen_size,
+                font_size,
+                single_space_width,
+                font_height,
+            );
+        }
+
+        self.render_panes(renderer, font_size, single_space_width, font_height);
+        self.render_preedit(renderer, font_size, single_space_width, font_height);
+        self.render_hovered_cell(renderer, single_space_width, font_height);
+
+        let total_rows = (screen_size.h as f64 / font_height).ceil() as usize;
+
+        if self.pending_load.is_some() {
+            renderer.draw_text(DrawTextOptions::<&Brush, _, _> {
+                font: &self.monospace_font,
+                size: font_size,
+                transform: Affine::translate((
+                    single_space_width * 6.0,
+                    font_height * ((total_rows / 3) as f64),
+                )),
+                glyph_transform: None,
+                brush: &Brush::Solid(self.theme.foreground),
+                style: Fill::NonZero,
+                text: "Loading...".to_string(),
+                _marker: PhantomData,
+            });
+        } else if self.view.buffer().is_empty() {
+            let message_row = total_rows / 3;
+
+            renderer.draw_text(DrawTextOptions::<&Brush, _, _> {
+                font: &self.monospace_font,
+                size: font_size,
+                transform: Affine::translate((
+                    single_space_width * 6.0,
+                    font_height * (message_row as f64),
+                )),
+                glyph_transform: None,
+                brush: &Brush::Solid(self.theme.foreground),
+                style: Fill::NonZero,
+                text: format!("{APP_NAME} editor -- version {APP_VERSION}"),
+                _marker: PhantomData,
+            });
+        }
+
+        self.render_notification(renderer, screen_size, font_size, font_height);
+
+        let bottom_line = match self.view.mode() {
+            Mode::Command => Some(format!(":{}", self.command_line)),
+            Mode::Search => Some(format!("/{}", self.command_line)),
+            _ => match self.view.substitute_progress() {
+                Some(progress) => Some(format!(
+                    "Substituting... {:.0}% (Esc to cancel)",
+                    progress * 100.0
+                )),
+                None => self.message.clone().or_else(|| {
+                    self.recording
+                        .as_ref()
+                        .map(|(register, _)| format!("recording @{register}"))
+                }),
+            },
+        };
+
+        let last_row = (screen_size.h as f64 / font_height).floor() - 1.0;
+        // the filetype is shown at the right end of the last row, unless
+        // something else takes the row
+        let shows_filetype = !matches!(self.view.mode(), Mode::Command | Mode::Search)
+            && bottom_line
+                .as_ref()
+                .is_none_or(|bottom_line| bottom_line.lines().count() <= 1);
+        let filetype = self.view.buffer().filetype().name();
+        if shows_filetype && !filetype.is_empty() {
+            let x =
+                screen_size.w as f64 - single_space_width * (filetype.chars().count() + 1) as f64;
+            renderer.draw_text(DrawTextOptions::<&Brush, _, _> {
+                font: &self.monospace_font,
+                size: font_size,
+                transform: Affine::translate((x.max(0.0), font_height * last_row)),
+                glyph_transform: None,
+                brush: &Brush::Solid(self.theme.foreground),
+                style: Fill::NonZero,
+                text: filetype,
+                _marker: PhantomData,
+            });
+        }
+
+        if let Some(bottom_line) = bottom_line {
+            // output of several lines, like :registers, grows up from the last row
+            let line_count = bottom_line.lines().count().max(1);
+            let first_row = (last_row - (line_count - 1) as f64).max(0.0);
+
+            if line_count > 1 {
+                renderer.draw_fill_rectangle(DrawFillRectangleOptions {
+                    pos: Position {
+                        x: 0.0,
+                        y: font_height * first_row,
+                    },
+                    size: Size {
+                        w: screen_size.w as f64,
+                        h: font_height * line_count as f64,
+                    },
+                    fill_color: self.theme.message_bg,
+                });
+            }
+
+            renderer.draw_text(DrawTextOptions::<&Brush, _, _> {
+                font: &self.monospace_font,
+                size: font_size,
+                transform: Affine::translate((0.0, font_height * first_row)),
+                glyph_transform: None,
+                brush: &Brush::Solid(self.theme.foreground),
+                style: Fill::NonZero,
+                text: bottom_line,
+                _marker: PhantomData,
+            });
+        }
+
+        if let Some(confirm) = &self.confirm {
+            confirm.render(
+                renderer,
+                &self.monospace_font,
+                font_size,
+                Size {
+                    w: single_space_width,
+                    h: font_height,
+                },
+                Size {
+                    w: screen_size.w as f64,
+                    h: screen_size.h as f64,
+                },
+                &self.theme,
+            );
+        }
+
+        self.is_dirty = false;
+    }
+
+    fn needs_redraw(&self) -> bool {
+        // keep checking whether the buffer has loaded, and doing long substitutions
+        self.pending_load.is_some() || self.scrolling || self.view.substitute_progress().is_some()
+    }
+
+    fn is_dirty(&self) -> bool {
+        // what is waited on and timers change the frame without events
+        self.is_dirty
+            || self.needs_redraw()
+            || self
+                .next_redraw_time()
+                .is_some_and(|time| time <= Instant::now())
+    }
+
+    fn next_redraw_time(&self) -> Option<Instant> {
+        let blink = self
+            .blink_interval()
+            .map(|interval| self.last_blink_toggle + interval);
+        // redrawn when a notification expires, so that it goes away
+        let expiry = self
+            .notifications
+            .iter()
+            .map(|notification| notification.expires_at)
+            .min();
+
+        blink.into_iter().chain(expiry).min()
+    }
+
+    fn window_title(&self) -> Option<String> {
+        // named like the buffer's tab, with a * while it has unsaved changes
+        let tab = Tab::for_buffer(self.view.buffer());
+        let modified = if tab.modified { "*" } else { "" };
+        Some(format!("{modified}{} - {APP_NAME}", tab.name))
+    }
+
+    fn ime_cursor_area(&self) -> Option<(Position<f64>, Size<f64>)> {
+        // the input method is for typing text, which is only done in insert mode
+        if !matches!(self.view.mode(), Mode::Insert) || self.pending_load.is_some() {
+            return None;
+        }
+
+        let bounds = self.monospace_font.variations(&[]).measure_text(16.0, " ");
+        let cell_size = Size {
+            w: bounds.w as f64,
+            h: bounds.h as f64,
+        };
+
+        let cursor = self.cursor_cell()?;
+
+        Some((
+            Position {
+                x: cursor.x as f64 * cell_size.w,
+                y: cursor.y as f64 * cell_size.h,
             },
+            cell_size,
+        ))
+    }
+}
+
+impl App {
+    // shows the buffer being loaded once it has arrived
+    fn poll_pending_load(&mut self) {
+        let Some(receiver) = &self.pending_load else {
+            return;
+        };
+
+        match receiver.try_recv() {
+            Ok(Ok(buffer)) => {
+                self.view.set_buffer(buffer);
+                self.watch_buffer_file();
+
+                if let Some(jump) = self.pending_jump.take() {
+                    if let Err(error) = self.view.jump_to(&jump) {
+                        self.message = Some(error.to_string());
+                    }
+                }
+            }
+            Ok(Err(error)) => {
+                self.notify(
+                    error.to_string(),
+                    NotificationLevel::Error,
+                    NOTIFICATION_DURATION,
+                );
+            }
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => {
+                self.notify(
+                    "Failed to load file",
+                    NotificationLevel::Error,
+                    NOTIFICATION_DURATION,
+                );
+            }
+        }
+
+        self.pending_load = None;
+    }
+
+    // starts watching the file of the buffer, instead of any previous file.
+    // only the shown buffer is watched, so changes made to its file while
+    // another one was shown are looked for now
+    fn watch_buffer_file(&mut self) {
+        self.watcher = None;
+
+        let Some(path) = self.view.buffer().path().map(Path::to_path_buf) else {
+            return;
+        };
+        match FileWatcher::new(&path, self.event_sender.clone()) {
+            Ok(watcher) => self.watcher = Some(watcher),
+            Err(error) => self.notify(
+                format!("Can't watch file: {error}"),
+                NotificationLevel::Warning,
+                NOTIFICATION_DURATION,
+            ),
+        }
+
+        if self.view.buffer().disk_state() != DiskState::Unchanged {
+            self.handle_file_changed(&path.to_string_lossy());
+        }
+    }
+
+    fn handle_file_changed(&mut self, path: &str) {
+        match self.view.buffer().disk_state() {
+            DiskState::Unchanged => {}
+            DiskState::Modified => {
+                self.confirm = Some(Confirm::new(
+                    "File changed on disk. Reload it?",
+                    |app: &mut App, reload| {
+                        if reload {
+                            if let Err(err) = app.view.reload() {
+                                app.notify(
+                                    format!("Cannot reload file: {err}"),
+                                    NotificationLevel::Error,
+                                    NOTIFICATION_DURATION,
+                                );
+                            }
+                            app.watch_buffer_file();
+                        } else {
+                            app.view.buffer_mut().ignore_disk_changes();
+                        }
+                    },
+                ));
+            }
+            DiskState::Deleted => {
+                self.confirm = None;
+                self.notify(
+                    format!("{path} was deleted on disk"),
+                    NotificationLevel::Warning,
+                    NOTIFICATION_DURATION,
+                );
+                self.view.buffer_mut().ignore_disk_changes();
+            }
+        }
+    }
+
+    fn notify(&mut self, message: impl Into<String>, level: NotificationLevel, duration: Duration) {
+        self.notifications
+            .push(Notification::new(message.into(), level, duration));
+    }
+
+    // draws the latest notification in a bar above the last row, once the
+    // expired ones are dropped
+    fn render_notification(
+        &mut self,
+        renderer: &mut AppRenderer,
+        screen_size: Size<u32>,
+        font_size: f32,
+        font_height: f64,
+    ) {
+        let now = Instant::now();
+        self.notifications
+            .retain(|notification| !notification.is_expired(now));
+
+        let Some(notification) = self.notifications.last() else {
+            return;
+        };
+
+        let last_row = (screen_size.h as f64 / font_height).floor() - 1.0;
+        let y = font_height * (last_row - 1.0).max(0.0);
+
+        renderer.draw_fill_rectangle(DrawFillRectangleOptions {
+            pos: Position { x: 0.0, y },
             size: Size {
-                w: single_space_width,
+                w: screen_size.w as f64,
                 h: font_height,
             },
-            fill_color: Color::rgb(0.0, 1.0, 0.0),
+            fill_color: self.theme.notification_bg(notification.level),
         });
 
-        let total_tildes = (screen_size.h as f64 / font_height).ceil() as usize;
-
         renderer.draw_text(DrawTextOptions::<&Brush, _, _> {
             font: &self.monospace_font,
             size: font_size,
-            transform: Affine::translate((0.0, 0.0)),
+            transform: Affine::translate((0.0, y)),
             glyph_transform: None,
-            brush: &Brush::Solid(Color::WHITE),
+            brush: &Brush::Solid(self.theme.foreground),
             style: Fill::NonZero,
-            text: "~\n".repeat(total_tildes),
+            text: &notification.message,
             _marker: PhantomData,
         });
+    }
 
-        let message_row = total_tildes / 3;
+    // while a long substitution is going, the only key is Escape to cancel it
+    fn handle_busy_key(&mut self, event: &AppKeyEvent) {
+        if event.logical_key == Key::Named(NamedKey::Escape) {
+            self.view.cancel_substitute();
+            self.notify(
+                "Substitution cancelled",
+                NotificationLevel::Info,
+                NOTIFICATION_DURATION,
+            );
+        }
+    }
 
-        renderer.draw_text(DrawTextOptions::<&Brush, _, _> {
-            font: &self.monospace_font,
-            size: font_size,
-            transform: Affine::translate((
-                single_space_width * 6.0,
-                font_height * (message_row as f64),
-            )),
-            glyph_transform: None,
-            brush: &Brush::Solid(Color::WHITE),
-            style: Fill::NonZero,
-            text: format!("{APP_NAME} editor -- version {APP_VERSION}"),
-            _marker: PhantomData,
-        });
+    fn handle_confirm_key(&mut self, event: &AppKeyEvent) {
+        // the question has to be answered first
+        let Some(answer) = self
+            .confirm
+            .as_ref()
+            .and_then(|confirm| confirm.answer(&event.logical_key))
+        else {
+            return;
+        };
 
-        renderer.draw_text(DrawTextOptions::<&Brush, _, _> {
-            font: &self.monospace_font,
-            size: font_size,
-            transform: Affine::translate((single_space_width * 6.0, font_height * 7.0)),
-            glyph_transform: None,
-            brush: &Brush::Solid(Color::WHITE),
-            style: Fill::NonZero,
-            text: &self.text,
-            _marker: PhantomData,
-        });
+        if let Some(confirm) = self.confirm.take() {
+            confirm.finish(self, answer);
+        }
     }
-}
 
-impl App {
-    pub fn run() -> Result<()> {
-        AppContext::new(APP_NAME.to_string()).run(App {
-            monospace_font: load_monospace_font(),
-            text: "No events yet!".to_string(),
-            cursor_pos: Position { x: 0, y: 0 },
-        })
+    fn handle_normal_key(&mut self, event: &AppKeyEvent, modifiers: ModifiersState) {
+        if self.handle_register_key(event) {
+            return;
+        }
+
+        if let Some(pending) = self.pending_normal_key {
+            // keys without text (like shift) can be pressed on the way to the second key
+            let Some(text) = event.text.as_deref() else {
+                return;
+            };
+            self.pending_normal_key = None;
+            let typed_count = self.count.take();
+            let count = typed_count.unwrap_or(1);
+
+            match (pending, text) {
+                ('g', "-") => self.view.undo_chronologically(false),
+                ('g', "+") => self.view.undo_chronologically(true),
+                ('g', "g") => self.view.go_to_line(count - 1),
+                ('g', "q") => self.enter_operator_pending(Operator::Format, typed_count),
+                ('g', "c") if self.check_comment_prefix() => {
+                    self.enter_operator_pending(Operator::Comment, typed_count);
+                }
+                ('g', "J") => {
+                    self.view.join_lines(count, false);
+                }
+                (pending @ ('f' | 'F' | 't' | 'T'), text) => {
+                    self.find_char(pending, text, count);
+                }
+                ('r', text) => {
+                    // the text of keys like Escape is a control character
+                    if let Some(ch) = text.chars().next().filter(|ch| !ch.is_control()) {
+                        self.view.replace_chars(ch, count);
+                    }
+                }
+                ('z', "t") => self.view.scroll_cursor_to_top(),
+                ('z', "z") => self.view.scroll_cursor_to_center(),
+                ('z', "b") => self.view.scroll_cursor_to_bottom(),
+                ('z', "\r") => {
+                    self.view.scroll_cursor_to_top();
+                    self.view.move_to_first_non_blank();
+                }
+                ('q', register) => self.start_recording(register),
+                ('m', mark) => self.set_mark(mark),
+                ('\'', mark) => self.jump_to_mark(mark, true),
+                ('`', mark) => self.jump_to_mark(mark, false),
+                ('@', register) => self.play_macro(register, count),
+                (WINDOW_KEY, key) => self.handle_window_key(key),
+                _ => {}
+            }
+            return;
+        }
+
+        if modifiers.control_key() && event.physical_key == PhysicalKey::Code(KeyCode::KeyW) {
+            self.pending_normal_key = Some(WINDOW_KEY);
+            return;
+        }
+
+        if self.handle_count_key(event) {
+            return;
+        }
+
+        if let Some(operator) = event.text.as_deref().and_then(Operator::from_key) {
+            let count = self.count.take();
+            self.enter_operator_pending(operator, count);
+            return;
+        }
+
+        match event.text.as_deref() {
+            Some("q") if self.recording.is_some() => {
+                self.stop_recording();
+                self.count = None;
+                return;
+            }
+            Some(
+                text @ ("g" | "q" | "z" | "f" | "F" | "t" | "T" | "r" | "@" | "m" | "'" | "`"),
+            ) => {
+                self.pending_normal_key = text.chars().next();
+                return;
+            }
+            // goes to the line of the count, or the last line without one
+            Some("G") => {
+                let line = self.count.take().map_or(usize::MAX, |count| count - 1);
+                self.view.go_to_line(line);
+                return;
+            }
+            _ => {}
+        }
+
+        // any other key, like Escape, uses up the count
+        let count = self.take_count();
+
+        match event.text.as_deref() {
+            Some(text @ ("p" | "P")) => {
+                self.paste(count, text == "P");
+                return;
+            }
+            Some(".") => {
+                self.repeat_change(count);
+                return;
+            }
+            Some("J") => {
+                self.view.join_lines(count, true);
+                return;
+            }
+            // repeat the last f, F, t or T, the same way or the other way
+            Some(text @ (";" | ",")) => {
+                if let Some(find) = self.last_find {
+                    let find = if text == "," { find.reversed() } else { find };
+                    self.view.find_char(find, count, true);
+                }
+                return;
+            }
+            // shorthands for changing with a motion: C is c$, s is cl and S is cc
+            Some("C") => {
+                self.apply_shorthand(Operator::Change, MotionKind::Inclusive, |view| {
+                    (1..count).for_each(|_| view.move_cursor_down());
+                    view.move_to_line_end();
+                });
+                return;
+            }
+            Some("s") => {
+                self.apply_shorthand(Operator::Change, MotionKind::Exclusive, |view| {
+                    (0..count).for_each(|_| view.move_cursor_right());
+                });
+                return;
+            }
+            Some("S") => {
+                self.apply_shorthand(Operator::Change, MotionKind::Linewise, |view| {
+                    view.go_to_line(view.cursor().y + count - 1);
+                });
+                return;
+            }
+            Some(":") => {
+                self.message = None;
+                self.view.enter_command_mode();
+                return;
+            }
+            Some("/") => {
+                self.message = None;
+                self.view.begin_search();
+                return;
+            }
+            Some("R") => {
+                self.view.enter_replace_mode();
+                return;
+            }
+            Some("v") => {
+                self.view.enter_visual_mode(SelectionKind::Char);
+                return;
+            }
+            Some("V") => {
+                self.view.enter_visual_mode(SelectionKind::Line);
+                return;
+            }
+            _ => {}
+        }
+
+        if self.move_by_key(event, count) {
+            return;
+        }
+
+        match event.physical_key {
+            PhysicalKey::Code(KeyCode::KeyO) if modifiers.control_key() => {
+                self.go_back_in_jump_list();
+            }
+            PhysicalKey::Code(KeyCode::KeyI) if modifiers.control_key() => {
+                self.go_forward_in_jump_list();
+            }
+            PhysicalKey::Code(KeyCode::KeyI) => {
+                self.view.enter_insert_mode();
+            }
+            PhysicalKey::Code(KeyCode::KeyA) => {
+                self.view.enter_append_mode();
+            }
+            PhysicalKey::Code(KeyCode::KeyD) if modifiers.control_key() => {
+                self.view.scroll_half_pages(count as isize);
+            }
+            PhysicalKey::Code(KeyCode::KeyU) if modifiers.control_key() => {
+                self.view.scroll_half_pages(-(count as isize));
+            }
+            PhysicalKey::Code(KeyCode::KeyU) => {
+                (0..count).for_each(|_| self.view.undo());
+            }
+            PhysicalKey::Code(KeyCode::KeyR) if modifiers.control_key() => {
+                (0..count).for_each(|_| self.view.redo());
+            }
+            PhysicalKey::Code(KeyCode::KeyF) if modifiers.control_key() => {
+                self.view.scroll_pages(count as isize);
+            }
+            PhysicalKey::Code(KeyCode::KeyB) if modifiers.control_key() => {
+                self.view.scroll_pages(-(count as isize));
+            }
+            PhysicalKey::Code(KeyCode::KeyV) if modifiers.control_key() => {
+                self.view.enter_visual_mode(SelectionKind::Block);
+            }
+            _ => {
+                self.handle_page_key(event, count);
+            }
+        }
+    }
+
+    // scrolls by screens for the page keys, returning whether it was one
+    fn handle_page_key(&mut self, event: &AppKeyEvent, count: usize) -> bool {
+        match event.logical_key {
+            Key::Named(NamedKey::PageDown) => self.view.scroll_pages(count as isize),
+            Key::Named(NamedKey::PageUp) => self.view.scroll_pages(-(count as isize)),
+            _ => return false,
+        }
+
+        true
+    }
+
+    fn jump_entry(&self) -> JumpEntry {
+        let cursor = self.view.cursor();
+
+        JumpEntry {
+            buffer_id: self.view.buffer().id(),
+            line: cursor.y,
+            col: cursor.x,
+        }
+    }
+
+    // remembers `before` as a jump if the cursor moved far from it, or to another buffer
+    fn record_jump(&mut self, before: JumpEntry) {
+        if mem::take(&mut self.following_jump) {
+            return;
+        }
+
+        let after = self.jump_entry();
+        if after.buffer_id != before.buffer_id || after.line.abs_diff(before.line) > JUMP_LINES {
+            self.remember_jump(before);
+        }
+    }
+
+    fn remember_jump(&mut self, from: JumpEntry) {
+        self.jump_list.record(from);
+        self.last_jump = Some(from);
+    }
+
+    fn set_mark(&mut self, mark: &str) {
+        let Some(mark) = mark.chars().next() else {
+            return;
+        };
+
+        if mark.is_ascii_lowercase() {
+            let cursor = self.view.cursor();
+            self.view.buffer_mut().marks_mut().set(mark, cursor);
+        } else if mark.is_ascii_uppercase() {
+            self.global_marks.insert(mark, self.jump_entry());
+        } else {
+            self.message = Some(format!("Invalid mark: {mark}"));
+        }
+    }
+
+    // jumps to the exact position of `mark`, or to the start of its line if `linewise`
+    fn jump_to_mark(&mut self, mark: &str, linewise: bool) {
+        let Some(mark) = mark.chars().next() else {
+            return;
+        };
+
+        let buffer = self.view.buffer();
+        let target = match mark {
+            'a'..='z' | '[' | ']' | '.' => buffer.marks().get(mark).map(|position| JumpEntry {
+                buffer_id: buffer.id(),
+                line: position.y,
+                col: position.x,
+            }),
+            'A'..='Z' => self.global_marks.get(&mark).copied(),
+            '\'' | '`' => self.last_jump,
+            _ => None,
+        };
+        let Some(target) = target else {
+            self.message = Some(format!("Mark not set: {mark}"));
+            return;
+        };
+
+        // like a jump, a mark can be jumped back from
+        self.following_jump = true;
+        self.remember_jump(self.jump_entry());
+
+        if !self.go_to_jump(target) {
+            self.message = Some(format!("Buffer of mark {mark} is closed"));
+        } else if linewise {
+            self.view.move_to_first_non_blank();
+        }
+    }
+
+    fn go_back_in_jump_list(&mut self) {
+        self.following_jump = true;
+        let from = self.jump_entry();
+
+        // jumps into buffers that have been closed are skipped
+        while let Some(entry) = self.jump_list.prev(from) {
+            if self.go_to_jump(entry) {
+                break;
+            }
+        }
+    }
+
+    fn go_forward_in_jump_list(&mut self) {
+        self.following_jump = true;
+
+        while let Some(entry) = self.jump_list.next() {
+            if self.go_to_jump(entry) {
+                break;
+            }
+        }
+    }
+
+    // how long each half of blinking lasts, if the cursor is blinking
+    fn blink_interval(&self) -> Option<Duration> {
+        let blink_ms = self.view.options().cursor_blink_ms;
+        (self.window_focused && blink_ms > 0).then(|| Duration::from_millis(blink_ms))
+    }
+
+    fn blink_cursor(&mut self) {
+        let Some(interval) = self.blink_interval() else {
+            return;
+        };
+
+        if self.last_blink_toggle.elapsed() >= interval {
+            self.cursor_visible = !self.cursor_visible;
+            self.last_blink_toggle = Instant::now();
+        }
+    }
+
+    fn show_cursor(&mut self) {
+        self.cursor_visible = true;
+        self.last_blink_toggle = Instant::now();
+    }
+
+    fn animate_scroll(&mut self) {
+        let now = Instant::now();
+        let delta_time = (now - self.last_frame_time)
+            .as_secs_f64()
+            .min(MAX_FRAME_TIME);
+        self.last_frame_time = now;
+
+        // every view has to take its step, so don't stop at the first one
+        let mut scrolling = self.view.animate_scroll(delta_time);
+        for view in self.layout.views_mut() {
+            scrolling |= view.animate_scroll(delta_time);
+        }
+        self.scrolling = scrolling;
+    }
+
+    // makes the views fit their panes
+    fn resize_panes(&mut self) {
+        self.layout
+            .panes(self.layout_area)
+            .into_iter()
+            .for_each(|(path, area)| {
+                if path == self.focus {
+                    self.view.resize(area.size);
+                } else if let Some(Some(view)) = self.layout.pane_mut(&path) {
+                    view.resize(area.size);
+                }
+            });
+    }
+
+    // moves the focus to the pane at `path`, swapping its view with the focused one
+    fn focus_pane(&mut self, path: FocusPath) {
+        if path == self.focus {
+            return;
+        }
+        let Some(view) = self.layout.pane_mut(&path).and_then(Option::take) else {
+            return;
+        };
+
+        let previous = mem::replace(&mut self.view, *view);
+        if let Some(pane) = self.layout.pane_mut(&self.focus) {
+            *pane = Some(Box::new(previous));
+        }
+        self.focus = path;
+        self.watch_buffer_file();
+    }
+
+    // the window commands typed after CTRL-W
+    fn handle_window_key(&mut self, key: &str) {
+        let step = match key {
+            "h" => Some(Step::Left),
+            "j" => Some(Step::Bottom),
+            "k" => Some(Step::Top),
+            "l" => Some(Step::Right),
+            _ => None,
+        };
+        if let Some(step) = step {
+            if let Some(path) = self.layout.neighbor(self.layout_area, &self.focus, step) {
+                self.focus_pane(path);
+            }
+            return;
+        }
+
+        match key {
+            "w" => {
+                let panes: Vec<FocusPath> = self
+                    .layout
+                    .panes(self.layout_area)
+                    .into_iter()
+                    .map(|(path, _)| path)
+                    .collect();
+                let current = panes.iter().position(|path| *path == self.focus);
+                let next = current.map_or(0, |current| (current + 1) % panes.len());
+                self.focus_pane(panes[next].clone());
+            }
+            "=" => self.layout.equalize(),
+            "_" => self.layout.maximize(&self.focus, SplitDirection::Vertical),
+            "|" => self
+                .layout
+                .maximize(&self.focus, SplitDirection::Horizontal),
+            "c" => self.close_pane(),
+            // a buffer can only be shown in one view, so the new pane starts
+            // with an empty buffer
+            "s" => self.split_pane(SplitDirection::Vertical),
+            "v" => self.split_pane(SplitDirection::Horizontal),
+            _ => {}
+        }
+
+        self.resize_panes();
+    }
+
+    fn split_pane(&mut self, direction: SplitDirection) {
+        let mut view = View::new(Buffer::new());
+        *view.options_mut() = self.view.options().clone();
+
+        let previous = mem::replace(&mut self.view, view);
+        self.focus = self.layout.split(&self.focus, direction, previous);
+        self.watch_buffer_file();
+    }
+
+    fn close_pane(&mut self) {
+        let Some(path) = self.layout.close(&self.focus) else {
+            self.message = Some("Cannot close last window".to_string());
+            return;
+        };
+        let Some(view) = self.layout.pane_mut(&path).and_then(Option::take) else {
+            return;
+        };
+
+        let closed = mem::replace(&mut self.view, *view).into_buffer();
+        // an empty buffer without a file has nothing worth going back to
+        if closed.path().is_some() || !closed.is_empty() {
+            self.buffers.push(closed);
+        }
+        self.focus = path;
+        self.watch_buffer_file();
+    }
+
+    fn pane_at(&self, cell: Position<usize>) -> Option<(FocusPath, PaneArea)> {
+        self.layout
+            .panes(self.layout_area)
+            .into_iter()
+            .find(|(_, area)| area.contains(cell))
+    }
+
+    // focuses the clicked pane, then lets its view handle the click
+    fn handle_pane_click(&mut self, cell: Position<f64>) {
+        let clicked_cell = Position {
+            x: cell.x as usize,
+            y: cell.y as usize,
+        };
+        let Some((path, area)) = self.pane_at(clicked_cell) else {
+            return;
+        };
+
+        self.focus_pane(path);
+        self.view.handle_mouse_click(Position {
+            x: cell.x - area.pos.x as f64,
+            y: cell.y - area.pos.y as f64,
+        });
+    }
+
+    fn render_panes(
+        &self,
+        renderer: &mut AppRenderer,
+        font_size: f32,
+        cell_width: f64,
+        cell_height: f64,
+    ) {
+        let panes = self.layout.panes(self.layout_area);
+
+        panes.iter().for_each(|(path, area)| {
+            let focused = *path == self.focus;
+            let view = if focused {
+                Some(&self.view)
+            } else {
+                self.layout.view(path)
+            };
+            let Some(view) = view else {
+                return;
+            };
+
+            renderer.push_transform(Affine::translate((
+                area.pos.x as f64 * cell_width,
+                area.pos.y as f64 * cell_height,
+            )));
+            // only the focused view blinks its cursor, and it is hidden behind
+            // text being composed with an input method
+            let cursor_visible = !focused || (self.cursor_visible && self.preedit.is_none());
+            view.render(
+                renderer,
+                &self.monospace_font,
+                font_size,
+                &self.theme,
+                focused,
+                cursor_visible,
+            );
+            renderer.pop_transform();
+        });
+
+        if panes.len() < 2 {
+            return;
+        }
+
+        // the focused border goes last, so that it is drawn over the others
+        let mut borders: Vec<&(FocusPath, PaneArea)> = panes.iter().collect();
+        borders.sort_by_key(|(path, _)| *path == self.focus);
+        borders.into_iter().for_each(|(path, area)| {
+            let color = if *path == self.focus {
+                self.theme.focused_pane_border
+            } else {
+                self.theme.pane_border
+            };
+
+            let left = area.pos.x as f64 * cell_width;
+            let top = area.pos.y as f64 * cell_height;
+            let right = (area.pos.x + area.size.w) as f64 * cell_width;
+            let bottom = (area.pos.y + area.size.h) as f64 * cell_height;
+            let corners = [
+                Position { x: left, y: top },
+                Position { x: right, y: top },
+                Position {
+                    x: right,
+                    y: bottom,
+                },
+                Position { x: left, y: bottom },
+            ];
+
+            (0..corners.len()).for_each(|i| {
+                renderer.draw_line(DrawLineOptions {
+                    start: corners[i],
+                    end: corners[(i + 1) % corners.len()],
+                    width: 1.0,
+                    color,
+                });
+            });
+        });
+    }
+
+    // the cell of the window the cursor of the focused view is in
+    fn cursor_cell(&self) -> Option<Position<usize>> {
+        let (_, area) = self
+            .layout
+            .panes(self.layout_area)
+            .into_iter()
+            .find(|(path, _)| *path == self.focus)?;
+        let cursor = self.view.cursor_screen_pos()?;
+
+        Some(Position {
+            x: area.pos.x + cursor.x,
+            y: area.pos.y + cursor.y,
+        })
+    }
+
+    // text being composed with an input method, drawn underlined over the
+    // text at the cursor until it is typed
+    fn render_preedit(
+        &self,
+        renderer: &mut AppRenderer,
+        font_size: f32,
+        cell_width: f64,
+        cell_height: f64,
+    ) {
+        let (Some(preedit), Some(cursor)) = (&self.preedit, self.cursor_cell()) else {
+            return;
+        };
+
+        let pos = Position {
+            x: cursor.x as f64 * cell_width,
+            y: cursor.y as f64 * cell_height,
+        };
+        let width = preedit.chars().map(char_width).sum::<usize>() as f64 * cell_width;
+
+        renderer.draw_fill_rectangle(DrawFillRectangleOptions {
+            pos,
+            size: Size {
+                w: width,
+                h: cell_height,
+            },
+            fill_color: self.theme.current_line_bg,
+        });
+        renderer.draw_text(DrawTextOptions::<&Brush, _, _> {
+            font: &self.monospace_font,
+            size: font_size,
+            transform: Affine::translate((pos.x, pos.y)),
+            glyph_transform: None,
+            brush: &Brush::Solid(self.theme.foreground),
+            style: Fill::NonZero,
+            text: preedit,
+            _marker: PhantomData,
+        });
+        renderer.draw_fill_rectangle(DrawFillRectangleOptions {
+            pos: Position {
+                x: pos.x,
+                y: pos.y + cell_height - 1.0,
+            },
+            size: Size { w: width, h: 1.0 },
+            fill_color: self.theme.foreground,
+        });
+
+        let Some((start, end)) = self.preedit_cursor else {
+            return;
+        };
+        // the input method gives byte offsets, which may not be on a
+        // character boundary if it is misbehaving
+        let cells = |index: usize| {
+            preedit
+                .get(..index)
+                .map_or(0, |text| text.chars().map(char_width).sum::<usize>()) as f64
+                * cell_width
+        };
+        let (start_x, end_x) = (cells(start), cells(end));
+
+        // the part being converted is underlined thicker than the rest
+        if end_x > start_x {
+            renderer.draw_fill_rectangle(DrawFillRectangleOptions {
+                pos: Position {
+                    x: pos.x + start_x,
+                    y: pos.y + cell_height - 2.0,
+                },
+                size: Size {
+                    w: end_x - start_x,
+                    h: 2.0,
+                },
+                fill_color: self.theme.foreground,
+            });
+        }
+        renderer.draw_cursor(DrawCursorOptions {
+            shape: CursorShape::Bar { percent: 15 },
+            pos: Position {
+                x: pos.x + start_x,
+                y: pos.y,
+            },
+            cell_size: Size {
+                w: cell_width,
+                h: cell_height,
+            },
+            color: self.theme.cursor_color(Mode::Insert),
+        });
+    }
+
+    // the cell of the window the mouse is over, if there is a character of a
+    // view in it
+    fn hovered_cell(&self, cell_size: Size<f64>) -> Option<Position<usize>> {
+        let cell = Position {
+            x: self.mouse_pos.x / cell_size.w,
+            y: self.mouse_pos.y / cell_size.h,
+        };
+        if cell.x < 0.0 || cell.y < 0.0 {
+            return None;
+        }
+        let hovered_cell = Position {
+            x: cell.x as usize,
+            y: cell.y as usize,
+        };
+
+        let (path, area) = self.pane_at(hovered_cell)?;
+        let view = if path == self.focus {
+            Some(&self.view)
+        } else {
+            self.layout.view(&path)
+        }?;
+
+        let in_view = Position {
+            x: cell.x - area.pos.x as f64,
+            y: cell.y - area.pos.y as f64,
+        };
+        view.screen_pos_to_cursor(in_view)?;
+        Some(hovered_cell)
+    }
+
+    // highlights the character under the mouse
+    fn render_hovered_cell(&self, renderer: &mut AppRenderer, cell_width: f64, cell_height: f64) {
+        let cell_size = Size {
+            w: cell_width,
+            h: cell_height,
+        };
+        let Some(hovered_cell) = self.hovered_cell(cell_size) else {
+            return;
+        };
+
+        renderer.draw_fill_rectangle(DrawFillRectangleOptions {
+            pos: Position {
+                x: hovered_cell.x as f64 * cell_width,
+                y: hovered_cell.y as f64 * cell_height,
+            },
+            size: Size {
+                w: cell_width,
+                h: cell_height,
+            },
+            fill_color: self.theme.hover_cell_bg,
+        });
+    }
+
+    // moves to `entry`, switching buffers if needed, returning whether the buffer was found
+    fn go_to_jump(&mut self, entry: JumpEntry) -> bool {
+        if !self.switch_to_buffer(entry.buffer_id) {
+            return false;
+        }
+
+        let jump = Jump::Position {
+            line: entry.line,
+            col: entry.col,
+        };
+        if let Err(error) = self.view.jump_to(&jump) {
+            self.message = Some(error.to_string());
+        }
+
+        true
+    }
+
+    // shows the buffer with `id` in the view, or focuses the pane already
+    // showing it, returning whether it was found
+    fn switch_to_buffer(&mut self, id: usize) -> bool {
+        if id == self.view.buffer().id() {
+            return true;
+        }
+
+        if let Some(path) = self.layout.find_buffer(id) {
+            self.focus_pane(path);
+            return true;
+        }
+
+        let Some(buffer) = self.buffers.take(id) else {
+            return false;
+        };
+        let previous = self.view.set_buffer(buffer);
+        self.buffers.push(previous);
+        self.watch_buffer_file();
+
+        true
+    }
+
+    fn tab_rows(&self) -> usize {
+        if self.view.options().show_tab_line {
+            1
+        } else {
+            0
+        }
+    }
+
+    // the open buffers, in the order they were opened
+    fn tabs(&self) -> Vec<Tab> {
+        let mut tabs: Vec<Tab> = self
+            .buffers
+            .iter()
+            .chain(self.layout.views().into_iter().map(View::buffer))
+            .chain([self.view.buffer()])
+            .map(Tab::for_buffer)
+            .collect();
+        tabs.sort_by_key(|tab| tab.buffer_id);
+        tabs
+    }
+
+    fn handle_tab_click(&mut self, col: usize, width: usize) {
+        let active_id = self.view.buffer().id();
+        let clicked = layout_tabs(&self.tabs(), active_id, width)
+            .into_iter()
+            .find(|tab| (tab.col..tab.col + tab.width).contains(&col));
+
+        if let Some(tab) = clicked {
+            self.switch_to_buffer(tab.buffer_id);
+        }
+    }
+
+    fn render_tab_bar(
+        &self,
+        renderer: &mut AppRenderer,
+        screen_size: Size<u32>,
+        font_size: f32,
+        cell_width: f64,
+        cell_height: f64,
+    ) {
+        renderer.draw_fill_rectangle(DrawFillRectangleOptions {
+            pos: Position { x: 0.0, y: 0.0 },
+            size: Size {
+                w: screen_size.w as f64,
+                h: cell_height,
+            },
+            fill_color: self.theme.tab_bar_bg,
+        });
+
+        let width = (screen_size.w as f64 / cell_width) as usize;
+        let active_id = self.view.buffer().id();
+
+        layout_tabs(&self.tabs(), active_id, width)
+            .into_iter()
+            .for_each(|tab| {
+                let x = tab.col as f64 * cell_width;
+
+                let text_color = if tab.active {
+                    renderer.draw_fill_rectangle(DrawFillRectangleOptions {
+                        pos: Position { x, y: 0.0 },
+                        size: Size {
+                            w: tab.width as f64 * cell_width,
+                            h: cell_height,
+                        },
+                        fill_color: self.theme.tab_active_bg,
+                    });
+                    self.theme.foreground
+                } else {
+                    self.theme.tab_inactive_text
+                };
+
+                renderer.draw_text(DrawTextOptions::<&Brush, _, _> {
+                    font: &self.monospace_font,
+                    size: font_size,
+                    transform: Affine::translate((x, 0.0)),
+                    glyph_transform: None,
+                    brush: &Brush::Solid(text_color),
+                    style: Fill::NonZero,
+                    text: tab.label,
+                    _marker: PhantomData,
+                });
+            });
+    }
+
+    // handles " and the register named after it, returning whether the key was used
+    fn handle_register_key(&mut self, event: &AppKeyEvent) -> bool {
+        if self.pending_normal_key == Some('"') {
+            let Some(text) = event.text.as_deref() else {
+                return true;
+            };
+            self.pending_normal_key = None;
+            self.register = text
+                .chars()
+                .next()
+                .filter(|&register| Registers::is_valid(register));
+            return true;
+        }
+
+        if self.pending_normal_key.is_none() && event.text.as_deref() == Some("\"") {
+            self.pending_normal_key = Some('"');
+            return true;
+        }
+
+        false
+    }
+
+    fn paste(&mut self, count: usize, before: bool) {
+        let register = self.register.take().unwrap_or(UNNAMED_REGISTER);
+        if register == CLIPBOARD_REGISTER {
+            self.registers.sync_clipboard();
+        }
+
+        match self.registers.get(register) {
+            Some(text) => {
+                let text = RegisterText {
+                    text: text.text.repeat(count),
+                    kind: text.kind,
+                };
+                self.view.paste(&text, before);
+            }
+            None => self.message = Some(format!("Nothing in register {register}")),
+        }
+    }
+
+    fn start_recording(&mut self, register: &str) {
+        let mut chars = register.chars();
+
+        if let (Some(register), None) = (chars.next(), chars.next()) {
+            if register.is_ascii_lowercase() {
+                self.recording = Some((register, vec![]));
+            }
+        }
+    }
+
+    fn stop_recording(&mut self) {
+        if let Some((register, mut events)) = self.recording.take() {
+            // the last event is the q that stopped the recording
+            events.pop();
+            self.registers.set_macro(register, events);
+        }
+    }
+
+    // once the keys typed since normal mode was last idle make up a command
+    // that changed the buffer, keeps them for . to repeat
+    fn finish_change(&mut self) {
+        // commands typed on the command line aren't repeated, like vim
+        if matches!(self.view.mode(), Mode::Command | Mode::Search) {
+            self.change_start = None;
+        }
+
+        let idle = self.view.mode() == Mode::Normal
+            && self.pending_normal_key.is_none()
+            && self.count.is_none()
+            && self.confirm.is_none();
+        if !idle {
+            return;
+        }
+
+        // commands that switch to another buffer aren't changes either
+        let buffer = self.view.buffer();
+        let events = mem::take(&mut self.change_events);
+        if let Some((id, change_count)) = self.change_start.take() {
+            if id == buffer.id() && change_count != buffer.change_count() {
+                self.last_change = Some(events);
+            }
+        }
+    }
+
+    // replays the keys of the last change `count` times, like .
+    fn repeat_change(&mut self, count: usize) {
+        let Some(events) = self.last_change.clone() else {
+            return;
+        };
+
+        self.replaying = true;
+        (0..count).for_each(|_| {
+            events
+                .iter()
+                .for_each(|event| self.handle_events(event.clone(), self.screen_size));
+        });
+        self.replaying = false;
+
+        // the . itself isn't the change to repeat next time
+        self.change_start = None;
+    }
+
+    // replays the events recorded in `register`, where @ is the last played register
+    fn play_macro(&mut self, register: &str, count: usize) {
+        let register = match register {
+            "@" => self.last_macro,
+            _ => register.chars().next(),
+        };
+        let Some(register) = register else {
+            return;
+        };
+        let Some(events) = self.registers.get_macro(register).map(<[_]>::to_vec) else {
+            return;
+        };
+
+        if self.macro_depth >= MAX_MACRO_DEPTH {
+            self.message = Some("Macro calls itself too many times".to_string());
+            return;
+        }
+
+        self.last_macro = Some(register);
+        self.macro_depth += 1;
+
+        (0..count).for_each(|_| {
+            events
+                .iter()
+                .for_each(|event| self.handle_events(event.clone(), self.screen_size));
+        });
+
+        self.macro_depth -= 1;
+    }
+
+    fn handle_visual_key(&mut self, event: &AppKeyEvent) {
+        if self.handle_register_key(event) || self.handle_count_key(event) {
+            return;
+        }
+        let count = self.take_count();
+
+        if let Some(pending) = self.pending_normal_key {
+            // keys without text (like shift) can be pressed on the way to the second key
+            let Some(text) = event.text.as_deref() else {
+                return;
+            };
+            self.pending_normal_key = None;
+            match (pending, text) {
+                ('g', "c") if self.check_comment_prefix() => self.view.toggle_comment_selection(),
+                ('r', text) => {
+                    // the text of keys like Escape is a control character
+                    if let Some(ch) = text.chars().next().filter(|ch| !ch.is_control()) {
+                        self.view.replace_block(ch);
+                    }
+                }
+                _ => {}
+            }
+            return;
+        }
+
+        if self.view.mode() == Mode::VisualBlock {
+            match event.text.as_deref() {
+                Some(text @ ("I" | "A")) => {
+                    self.view.insert_in_block(text == "A");
+                    return;
+                }
+                Some("c" | "s") => {
+                    if let Some(text) = self.view.change_block() {
+                        self.registers.delete(self.register.take(), text);
+                    }
+                    return;
+                }
+                Some("r") => {
+                    self.pending_normal_key = Some('r');
+                    return;
+                }
+                _ => {}
+            }
+        }
+
+        match event.text.as_deref() {
+            Some("y") => {
+                if let Some(text) = self.view.yank_selection() {
+                    self.registers.yank(self.register.take(), text);
+                }
+                return;
+            }
+            Some("d" | "x") => {
+                if let Some(text) = self.view.delete_selection() {
+                    self.registers.delete(self.register.take(), text);
+                }
+                return;
+            }
+            Some("g") => {
+                self.pending_normal_key = Some('g');
+                return;
+            }
+            // a count shifts the lines that many levels
+            Some(text @ (">" | "<")) => {
+                self.view.shift_selection(text == "<", count);
+                return;
+            }
+            _ => {}
+        }
+
+        if event.text.as_deref() == Some(":") {
+            self.message = None;
+            self.view.enter_command_mode();
+            // the command applies to the selection
+            self.command_line = "'<,'>".to_string();
+            return;
+        }
+
+        if self.move_by_key(event, count) || self.handle_page_key(event, count) {
+            return;
+        }
+
+        if let Key::Named(NamedKey::Escape) = &event.logical_key {
+            self.view.enter_normal_mode();
+        }
+    }
+
+    // whether lines of the current buffer can be commented out, warning if
+    // its filetype has no line comments
+    fn check_comment_prefix(&mut self) -> bool {
+        if self.view.buffer().comment_prefix().is_some() {
+            return true;
+        }
+        let message = match self.view.buffer().filetype() {
+            FileType::Unknown => "No filetype to comment lines for".to_string(),
+            filetype => format!("No line comments for filetype {}", filetype.name()),
+        };
+        self.notify(message, NotificationLevel::Warning, NOTIFICATION_DURATION);
+        false
+    }
+
+    // adds to the count typed before a command for digit keys, returning
+    // whether it was one
+    fn handle_count_key(&mut self, event: &AppKeyEvent) -> bool {
+        let Some(digit) = event
+            .text
+            .as_deref()
+            .and_then(|text| text.parse::<usize>().ok())
+        else {
+            return false;
+        };
+
+        // a count can't start with 0, which is a motion of its own
+        if digit == 0 && self.count.is_none() {
+            return false;
+        }
+        self.count = Some(
+            self.count
+                .unwrap_or(0)
+                .saturating_mul(10)
+                .saturating_add(digit),
+        );
+        true
+    }
+
+    // the count typed before the current command, which is 1 without one
+    fn take_count(&mut self) -> usize {
+        self.count.take().unwrap_or(1)
+    }
+
+    // moves the cursor `count` times for keys that are motions, returning
+    // whether it was one. Motions that can't move are reported.
+    fn move_by_key(&mut self, event: &AppKeyEvent, count: usize) -> bool {
+        match self.handle_motion_key(event, count) {
+            Some(Ok(_)) => true,
+            Some(Err(err)) => {
+                self.notify(
+                    err.to_string(),
+                    NotificationLevel::Warning,
+                    NOTIFICATION_DURATION,
+                );
+                true
+            }
+            None => false,
+        }
+    }
+
+    // moves the cursor `count` times for keys that are motions, returning how
+    // an operator applies to what it moved over. None if it isn't a motion.
+    fn handle_motion_key(
+        &mut self,
+        event: &AppKeyEvent,
+        count: usize,
+    ) -> Option<Result<MotionKind>> {
+        // these are on different keys in different layouts
+        match event.text.as_deref() {
+            Some("0") => {
+                self.view.move_to_line_start();
+                return Some(Ok(MotionKind::Exclusive));
+            }
+            Some("^") => {
+                self.view.move_to_first_non_blank();
+                return Some(Ok(MotionKind::Exclusive));
+            }
+            // a count goes to the end of a line further down
+            Some("$") => {
+                (1..count).for_each(|_| self.view.move_cursor_down());
+                self.view.move_to_line_end();
+                return Some(Ok(MotionKind::Inclusive));
+            }
+            Some("%") => {
+                if !self.view.move_to_matching_bracket() {
+                    return Some(Err(anyhow!("No matching bracket")));
+                }
+                return Some(Ok(MotionKind::Inclusive));
+            }
+            _ => {}
+        }
+
+        let (motion, kind): (fn(&mut View), _) = match event.physical_key {
+            PhysicalKey::Code(KeyCode::KeyH) => (View::move_cursor_left, MotionKind::Exclusive),
+            PhysicalKey::Code(KeyCode::KeyK) => (View::move_cursor_up, MotionKind::Linewise),
+            PhysicalKey::Code(KeyCode::KeyL) => (View::move_cursor_right, MotionKind::Exclusive),
+            PhysicalKey::Code(KeyCode::KeyJ) => (View::move_cursor_down, MotionKind::Linewise),
+            _ => return None,
+        };
+        (0..count).for_each(|_| motion(&mut self.view));
+
+        Some(Ok(kind))
+    }
+
+    // moves to the `count`th `text` in the line for the f, F, t or T in
+    // `pending`, returning whether there were that many
+    fn find_char(&mut self, pending: char, text: &str, count: usize) -> bool {
+        let Some(ch) = text.chars().next() else {
+            return false;
+        };
+        let find = CharFind {
+            ch,
+            forward: pending.is_lowercase(),
+            till: pending.eq_ignore_ascii_case(&'t'),
+        };
+        self.last_find = Some(find);
+        self.view.find_char(find, count, false)
+    }
+
+    fn enter_operator_pending(&mut self, operator: Operator, count: Option<usize>) {
+        self.operator_count = count;
+        self.view.enter_operator_pending(operator);
+    }
+
+    // the motion after an operator, which the operator is then applied to
+    fn handle_operator_key(&mut self, event: &AppKeyEvent, operator: Operator) {
+        if let Key::Named(NamedKey::Escape) = &event.logical_key {
+            self.pending_normal_key = None;
+            self.count = None;
+            self.view.enter_normal_mode();
+            return;
+        }
+
+        if self.pending_normal_key.is_none() && self.handle_count_key(event) {
+            return;
+        }
+
+        // keys without text (like shift) can be pressed on the way to the motion
+        let Some(text) = event.text.as_deref() else {
+            return;
+        };
+        // a count before the operator and one before the motion multiply
+        let typed_count = match (self.operator_count.take(), self.count.take()) {
+            (None, None) => None,
+            (before, after) => Some(before.unwrap_or(1) * after.unwrap_or(1)),
+        };
+        let count = typed_count.unwrap_or(1);
+        let from = self.view.cursor();
+
+        let kind = match (self.pending_normal_key.take(), text) {
+            (None, "f" | "F" | "t" | "T" | "g" | "i" | "a") => {
+                self.pending_normal_key = text.chars().next();
+                self.count = typed_count;
+                return;
+            }
+            // text objects act on what is around the cursor instead of moving it
+            (Some(pending @ ('i' | 'a')), text) => {
+                let range = TextObject::from_key(text)
+                    .and_then(|object| object.range(self.view.buffer(), from, pending == 'a'));
+                match range {
+                    Some((start, end, kind)) => self.finish_operator(operator, start, end, kind),
+                    None => self.view.enter_normal_mode(),
+                }
+                return;
+            }
+            // doubling the operator, like dd and gqq, applies it to `count` lines
+            (None, text) if text == operator.line_key() => {
+                self.view.go_to_line(from.y + count - 1);
+                Some(MotionKind::Linewise)
+            }
+            (Some('g'), text) if operator.is_typed_after_g() && text == operator.line_key() => {
+                self.view.go_to_line(from.y + count - 1);
+                Some(MotionKind::Linewise)
+            }
+            (Some('g'), "g") => {
+                self.view.go_to_line(count - 1);
+                Some(MotionKind::Linewise)
+            }
+            (None, "G") => {
+                let line = typed_count.map_or(usize::MAX, |count| count - 1);
+                self.view.go_to_line(line);
+                Some(MotionKind::Linewise)
+            }
+            // searching backward stops short of the cursor, like h
+            (Some(pending @ ('f' | 'F' | 't' | 'T')), text) => self
+                .find_char(pending, text, count)
+                .then_some(if pending.is_lowercase() {
+                    MotionKind::Inclusive
+                } else {
+                    MotionKind::Exclusive
+                }),
+            (None, ";" | ",") => self.last_find.and_then(|find| {
+                let find = if text == "," { find.reversed() } else { find };
+                let kind = if find.forward {
+                    MotionKind::Inclusive
+                } else {
+                    MotionKind::Exclusive
+                };
+                self.view.find_char(find, count, true).then_some(kind)
+            }),
+            (None, _) => match self.handle_motion_key(event, count) {
+                Some(Ok(kind)) => Some(kind),
+                Some(Err(err)) => {
+                    self.notify(
+                        err.to_string(),
+                        NotificationLevel::Warning,
+                        NOTIFICATION_DURATION,
+                    );
+                    None
+                }
+                None => None,
+            },
+            _ => None,
+        };
+
+        // anything that isn't a motion, or a motion that can't move, cancels
+        let Some(kind) = kind else {
+            self.view.enter_normal_mode();
+            return;
+        };
+
+        let to = self.view.cursor();
+        self.finish_operator(operator, from, to, kind);
+    }
+
+    // applies `operator` over what `motion` moves the cursor across, for keys
+    // that stand for an operator and a motion
+    fn apply_shorthand(
+        &mut self,
+        operator: Operator,
+        kind: MotionKind,
+        motion: impl FnOnce(&mut View),
+    ) {
+        self.view.enter_operator_pending(operator);
+        let from = self.view.cursor();
+        motion(&mut self.view);
+        let to = self.view.cursor();
+        self.finish_operator(operator, from, to, kind);
+    }
+
+    // applies `operator` from `from` to `to`, keeping what it deletes or yanks
+    fn finish_operator(
+        &mut self,
+        operator: Operator,
+        from: Position<usize>,
+        to: Position<usize>,
+        kind: MotionKind,
+    ) {
+        let kind = if operator.is_linewise() {
+            MotionKind::Linewise
+        } else {
+            kind
+        };
+        let Some(text) = self.view.apply_operator(operator, from, to, kind) else {
+            return;
+        };
+        let register = self.register.take();
+        if operator == Operator::Yank {
+            self.registers.yank(register, text);
+        } else {
+            self.registers.delete(register, text);
+        }
+    }
+
+    fn handle_insert_key(&mut self, event: &AppKeyEvent) {
+        match &event.logical_key {
+            Key::Named(NamedKey::Escape) => {
+                self.preedit = None;
+                self.preedit_cursor = None;
+                self.view.enter_normal_mode();
+            }
+            Key::Named(NamedKey::Enter) => {
+                self.view.insert_newline();
+            }
+            Key::Named(NamedKey::Backspace) => {
+                self.view.remove_char_before_cursor();
+            }
+            _ if self.handle_page_key(event, 1) => {}
+            _ => {
+                if let Some(text) = &event.text {
+                    text.chars()
+                        .filter(|ch| !ch.is_control())
+                        .for_each(|ch| self.view.insert_typed_char(ch));
+                }
+            }
+        }
+    }
+
+    fn handle_replace_key(&mut self, event: &AppKeyEvent) {
+        match &event.logical_key {
+            Key::Named(NamedKey::Escape) => self.view.enter_normal_mode(),
+            // a line break is inserted rather than typed over the text
+            Key::Named(NamedKey::Enter) => self.view.insert_newline(),
+            Key::Named(NamedKey::Backspace) => self.view.restore_replaced_char(),
+            _ => {
+                if let Some(text) = &event.text {
+                    text.chars()
+                        .filter(|ch| !ch.is_control())
+                        .for_each(|ch| self.view.replace_typed_char(ch));
+                }
+            }
+        }
+    }
+
+    fn handle_command_key(&mut self, event: &AppKeyEvent) {
+        match &event.logical_key {
+            Key::Named(NamedKey::Escape) => {
+                self.command_line.clear();
+                self.view.enter_normal_mode();
+            }
+            Key::Named(NamedKey::Enter) => {
+                let command = mem::take(&mut self.command_line);
+                self.view.enter_normal_mode();
+                let path = self.view.buffer().path().map(Path::to_path_buf);
+
+                match execute_command(
+                    &mut self.view,
+                    &mut self.buffers,
+                    &mut self.registers,
+                    &mut self.theme,
+                    &mut self.monospace_font,
+                    &command,
+                ) {
+                    Ok(output) => self.message = output,
+                    Err(err) => {
+                        self.message = None;
+                        self.notify(
+                            err.to_string(),
+                            NotificationLevel::Error,
+                            NOTIFICATION_DURATION,
+                        );
+                    }
+                }
+
+                // commands like :e open a different file
+                if self.view.buffer().path() != path.as_deref() {
+                    self.watch_buffer_file();
+                }
+            }
+            Key::Named(NamedKey::Backspace) => {
+                if self.command_line.pop().is_none() {
+                    self.view.enter_normal_mode();
+                }
+            }
+            _ => {
+                if let Some(text) = &event.text {
+                    self.command_line
+                        .extend(text.chars().filter(|ch| !ch.is_control()));
+                }
+            }
+        }
+    }
+
+    fn handle_search_key(&mut self, event: &AppKeyEvent) {
+        match &event.logical_key {
+            Key::Named(NamedKey::Escape) => {
+                self.command_line.clear();
+                self.view.cancel_search();
+            }
+            Key::Named(NamedKey::Enter) => {
+                let query = mem::take(&mut self.command_line);
+
+                if let Err(err) = self.view.finish_search(&query) {
+                    self.message = Some(err.to_string());
+                }
+            }
+            Key::Named(NamedKey::Backspace) => {
+                if self.command_line.pop().is_none() {
+                    self.view.cancel_search();
+                } else {
+                    self.view.update_search(&self.command_line);
+                }
+            }
+            _ => {
+                if let Some(text) = &event.text {
+                    self.command_line
+                        .extend(text.chars().filter(|ch| !ch.is_control()));
+                    self.view.update_search(&self.command_line);
+                }
+            }
+        }
+    }
+
+    pub fn run() -> Result<()> {
+        let context = AppContext::new(APP_NAME.to_string())?
+            .with_cpu_fallback(true)
+            .with_cpu_rendering(cpu_rendering_from_args());
+        let event_sender = context.event_sender();
+        let location = location_from_args();
+
+        // a broken theme file leaves the built-in theme in place
+        let (theme, notifications) = match Theme::startup_theme() {
+            Ok(theme) => (theme.unwrap_or_default(), vec![]),
+            Err(error) => (
+                Theme::default(),
+                vec![Notification::new(
+                    error.to_string(),
+                    NotificationLevel::Error,
+                    NOTIFICATION_DURATION,
+                )],
+            ),
+        };
+
+        let mut app = App::new(load_monospace_font(), event_sender);
+        app.pending_load = location
+            .as_ref()
+            .map(|location| load_buffer(location.path.clone()));
+        app.pending_jump = location.and_then(|location| location.jump);
+        app.theme = theme;
+        app.notifications = notifications;
+        context.run(app)
+    }
+
+    // an app with an empty buffer and the built-in theme
+    fn new(monospace_font: AppFont, event_sender: AppEventSender) -> Self {
+        Self {
+            monospace_font,
+            view: View::new(Buffer::new()),
+            layout: Layout::new(),
+            focus: FocusPath::default(),
+            layout_area: PaneArea {
+                pos: Position { x: 0, y: 0 },
+                size: Size { w: 0, h: 0 },
+            },
+            buffers: BufferList::new(),
+            jump_list: JumpList::new(),
+            last_jump: None,
+            global_marks: HashMap::new(),
+            following_jump: false,
+            pending_load: None,
+            pending_jump: None,
+            event_sender,
+            watcher: None,
+            confirm: None,
+            command_line: String::new(),
+            message: None,
+            notifications: vec![],
+            pending_normal_key: None,
+            last_find: None,
+            operator_count: None,
+            count: None,
+            theme: Theme::default(),
+            registers: Registers::new(),
+            register: None,
+            recording: None,
+            last_macro: None,
+            macro_depth: 0,
+            change_events: vec![],
+            change_start: None,
+            last_change: None,
+            replaying: false,
+            screen_size: Size { w: 0, h: 0 },
+            mouse_pos: Position { x: 0.0, y: 0.0 },
+            vertical_scroll: 0.0,
+            horizontal_scroll: 0.0,
+            preedit: None,
+            preedit_cursor: None,
+            last_frame_time: Instant::now(),
+            scrolling: false,
+            window_focused: true,
+            cursor_visible: true,
+            last_blink_toggle: Instant::now(),
+            is_dirty: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::keyboard::NamedKey;
+
+    use super::*;
+    use crate::base::{test_font, test_key, test_named_key};
+
+    fn app() -> App {
+        App::new(test_font(), AppEventSender::detached())
+    }
+
+    // types each character of `keys` as a key press, with \n as Enter and
+    // \u{1b} as Escape
+    fn press_keys(app: &mut App, keys: &str) {
+        for ch in keys.chars() {
+            let event = match ch {
+                '\n' => test_named_key(NamedKey::Enter),
+                '\u{1b}' => test_named_key(NamedKey::Escape),
+                _ => test_key(&ch.to_string()),
+            };
+            app.handle_events(
+                AppEvent::KeyboardEvent {
+                    event,
+                    is_synthetic: false,
+                    modifiers: ModifiersState::empty(),
+                },
+                Size { w: 800, h: 600 },
+            );
+        }
+    }
+
+    fn lines(app: &App) -> Vec<&str> {
+        let buffer = app.view.buffer();
+        (0..buffer.line_count())
+            .filter_map(|line| buffer.line(line))
+            .collect()
+    }
+
+    #[test]
+    fn a_macro_plays_back_the_keys_recorded_into_it() {
+        let mut app = app();
+        press_keys(&mut app, "ione\ntwo\nthree\u{1b}kk");
+
+        press_keys(&mut app, "qa$a!\u{1b}jq");
+        assert_eq!(lines(&app), ["one!", "two", "three"]);
+
+        press_keys(&mut app, "@a");
+        assert_eq!(lines(&app), ["one!", "two!", "three"]);
+    }
+
+    #[test]
+    fn a_macro_is_played_as_many_times_as_the_count() {
+        let mut app = app();
+        press_keys(&mut app, "ia\nb\nc\nd\u{1b}kkk");
+
+        press_keys(&mut app, "qq$a;\u{1b}jq2@q");
+        assert_eq!(lines(&app), ["a;", "b;", "c;", "d"]);
+
+        press_keys(&mut app, "@@");
+        assert_eq!(lines(&app), ["a;", "b;", "c;", "d;"]);
+    }
+
+    #[test]
+    fn dot_repeats_the_last_change_from_its_keys() {
+        let mut app = app();
+        press_keys(&mut app, "ione\ntwo\u{1b}k");
+
+        press_keys(&mut app, "$a!?\u{1b}j.");
+        assert_eq!(lines(&app), ["one!?", "two!?"]);
     }
 }
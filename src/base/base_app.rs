@@ -1,28 +1,114 @@
 use anyhow::Result;
-use std::sync::Arc;
+use std::{sync::Arc, time::Instant};
 use vello::util::RenderSurface;
 use winit::{
     application::ApplicationHandler,
-    dpi::LogicalSize,
-    event::{KeyEvent, WindowEvent},
-    event_loop::{ActiveEventLoop, EventLoop},
+    dpi::{LogicalSize, PhysicalPosition, PhysicalSize},
+    event::{ElementState, Ime, MouseButton, MouseScrollDelta, WindowEvent},
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop, EventLoopProxy},
+    keyboard::ModifiersState,
     window::{Window, WindowId},
 };
 
 use super::{
+    key::AppKeyEvent,
     renderer::{AppRenderer, BaseAppRenderer},
-    Size,
+    Position, Size,
 };
 
 pub trait AppHandler {
     fn handle_events(&mut self, event: AppEvent, screen_size: Size<u32>);
     fn render(&mut self, renderer: &mut AppRenderer, screen_size: Size<u32>);
+
+    /// Whether another frame should be drawn right after the current one, for
+    /// handlers that are waiting on something other than events.
+    fn needs_redraw(&self) -> bool {
+        false
+    }
+
+    /// Whether anything has changed since the last frame was drawn. The last
+    /// frame is shown again instead of being drawn anew when nothing has, so
+    /// handlers that can't tell should always say it has.
+    fn is_dirty(&self) -> bool {
+        true
+    }
+
+    /// When the next frame has to be drawn even if no events come in, for
+    /// handlers with timers like a blinking cursor.
+    fn next_redraw_time(&self) -> Option<Instant> {
+        None
+    }
+
+    /// The title the window should have, for handlers whose title changes,
+    /// like with the file being edited. The app name is kept when `None`.
+    fn window_title(&self) -> Option<String> {
+        None
+    }
+
+    /// Where text is being typed, in pixels from the top left of the window,
+    /// for the input method to show its candidates next to. The input method
+    /// is only on while this is `Some`.
+    fn ime_cursor_area(&self) -> Option<(Position<f64>, Size<f64>)> {
+        None
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum AppEvent {
-    KeyboardEvent { event: KeyEvent, is_synthetic: bool },
-    ResizeEvent { new_size: Size<u32> },
+    KeyboardEvent {
+        event: AppKeyEvent,
+        is_synthetic: bool,
+        modifiers: ModifiersState,
+    },
+    /// The window was resized, to the screen size every event comes with.
+    ResizeEvent,
+    MouseButtonEvent {
+        state: ElementState,
+        button: MouseButton,
+        /// where the mouse is, in pixels from the top left of the window
+        position: Position<f64>,
+    },
+    /// The mouse wheel or the touchpad scrolled by `delta`.
+    MouseWheelEvent { delta: MouseScrollDelta },
+    /// The mouse moved to `position`, in pixels from the top left of the window.
+    MouseMoved { position: Position<f64> },
+    /// The input method is composing `text`, which isn't typed yet. `cursor`
+    /// is where the input method's cursor is in `text`, in bytes.
+    ImePreedit {
+        text: String,
+        cursor: Option<(usize, usize)>,
+    },
+    /// The input method typed `text`.
+    ImeCommit { text: String },
+    /// The window gained or lost the keyboard focus.
+    FocusChanged { focused: bool },
+    /// The file at `path` was modified or deleted by something else.
+    FileChangedOnDisk { path: String },
+    /// The renderer works, but not as well as it should, like when it had to
+    /// fall back to rendering on the CPU.
+    RendererWarning { message: String },
+    /// A frame couldn't be drawn, so the window shows an older one.
+    RenderFailed { message: String },
+}
+
+/// Sends events to the app from other threads.
+#[derive(Clone)]
+pub struct AppEventSender(Option<EventLoopProxy<AppEvent>>);
+
+impl AppEventSender {
+    /// A sender whose events go nowhere, for tests that run without an
+    /// event loop.
+    #[cfg(test)]
+    pub fn detached() -> Self {
+        Self(None)
+    }
+
+    pub fn send(&self, event: AppEvent) {
+        // the app is exiting if the event loop is closed, so there's nothing to do
+        if let Some(proxy) = &self.0 {
+            let _ = proxy.send_event(event);
+        }
+    }
 }
 
 struct ActiveAppState {
@@ -36,7 +122,8 @@ struct SuspendedAppState {
 }
 
 enum AppState {
-    Active(ActiveAppState),
+    // boxed, as the surface makes it much bigger than the suspended state
+    Active(Box<ActiveAppState>),
     Suspended(SuspendedAppState),
 }
 
@@ -45,6 +132,16 @@ struct BaseApp<T: AppHandler> {
     renderer: BaseAppRenderer,
     handler: T,
     name: String,
+    // what the window title was last set to
+    title: String,
+    // winit reports modifier changes separately from key presses
+    modifiers: ModifiersState,
+    // and the mouse position separately from button presses
+    mouse_position: Position<f64>,
+    // whether the window takes input from the input method
+    ime_allowed: bool,
+    // what stopped the app, returned from AppContext::run once it exits
+    error: Option<anyhow::Error>,
 }
 
 fn create_winit_window<T: AsRef<str>>(
@@ -62,7 +159,7 @@ fn create_winit_window<T: AsRef<str>>(
     )
 }
 
-impl<T: AppHandler> ApplicationHandler for BaseApp<T> {
+impl<T: AppHandler> ApplicationHandler<AppEvent> for BaseApp<T> {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         let AppState::Suspended(SuspendedAppState { cached_window }) = &mut self.state else {
             return;
@@ -72,15 +169,33 @@ impl<T: AppHandler> ApplicationHandler for BaseApp<T> {
             .take()
             .unwrap_or_else(|| create_winit_window(event_loop, &self.name));
 
-        let surface = self.renderer.create_vello_surface(&window);
+        let (surface, warning) = match self.renderer.create_vello_surface(&window) {
+            Ok(created) => created,
+            Err(error) => {
+                // there is nothing to show the app in without a surface
+                self.error = Some(error);
+                event_loop.exit();
+                return;
+            }
+        };
+        if let Some(message) = warning {
+            let size = window.inner_size();
+            self.handler.handle_events(
+                AppEvent::RendererWarning { message },
+                Size {
+                    w: size.width,
+                    h: size.height,
+                },
+            );
+        }
 
-        self.state = AppState::Active(ActiveAppState { window, surface });
+        self.state = AppState::Active(Box::new(ActiveAppState { window, surface }));
     }
 
     fn suspended(&mut self, _event_loop: &ActiveEventLoop) {
-        if let AppState::Active(ActiveAppState { window, .. }) = &self.state {
+        if let AppState::Active(state) = &self.state {
             self.state = AppState::Suspended(SuspendedAppState {
-                cached_window: Some(window.clone()),
+                cached_window: Some(state.window.clone()),
             });
         }
     }
@@ -113,19 +228,91 @@ impl<T: AppHandler> ApplicationHandler for BaseApp<T> {
                     h: size.height,
                 };
 
+                self.handler
+                    .handle_events(AppEvent::ResizeEvent, screen_size);
+            }
+            WindowEvent::RedrawRequested => {
+                // the scene of the last frame is kept until something changes,
+                // so an unchanged frame is only presented again
+                if self.handler.is_dirty() {
+                    self.renderer.start_new_frame();
+
+                    self.handler
+                        .render(&mut ((&mut self.renderer).into()), surface_size);
+                }
+                if let Err(error) = self.renderer.present_frame(&active_state.surface) {
+                    self.handler.handle_events(
+                        AppEvent::RenderFailed {
+                            message: format!("{error:#}"),
+                        },
+                        surface_size,
+                    );
+                }
+
+                // the input method follows the cursor, and is only on while typing text
+                let ime_area = self.handler.ime_cursor_area();
+                if ime_area.is_some() != self.ime_allowed {
+                    self.ime_allowed = ime_area.is_some();
+                    active_state.window.set_ime_allowed(self.ime_allowed);
+                }
+                if let Some((pos, size)) = ime_area {
+                    active_state.window.set_ime_cursor_area(
+                        PhysicalPosition::new(pos.x, pos.y),
+                        PhysicalSize::new(size.w, size.h),
+                    );
+                }
+
+                if self.handler.needs_redraw() {
+                    active_state.window.request_redraw();
+                }
+            }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers.state();
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                // winit already gives physical pixels, the same as the surface
+                // is drawn in, so there is no scale factor to apply
+                self.mouse_position = Position {
+                    x: position.x,
+                    y: position.y,
+                };
                 self.handler.handle_events(
-                    AppEvent::ResizeEvent {
-                        new_size: screen_size,
+                    AppEvent::MouseMoved {
+                        position: self.mouse_position,
                     },
-                    screen_size,
+                    surface_size,
                 );
+                active_state.window.request_redraw();
             }
-            WindowEvent::RedrawRequested => {
-                self.renderer.start_new_frame();
-
+            WindowEvent::MouseWheel { delta, .. } => {
                 self.handler
-                    .render(&mut ((&mut self.renderer).into()), surface_size);
-                self.renderer.present_frame(&active_state.surface);
+                    .handle_events(AppEvent::MouseWheelEvent { delta }, surface_size);
+                active_state.window.request_redraw();
+            }
+            WindowEvent::Ime(ime) => {
+                let event = match ime {
+                    Ime::Preedit(text, cursor) => AppEvent::ImePreedit { text, cursor },
+                    Ime::Commit(text) => AppEvent::ImeCommit { text },
+                    Ime::Enabled | Ime::Disabled => return,
+                };
+                self.handler.handle_events(event, surface_size);
+                active_state.window.request_redraw();
+            }
+            WindowEvent::Focused(focused) => {
+                self.handler
+                    .handle_events(AppEvent::FocusChanged { focused }, surface_size);
+                active_state.window.request_redraw();
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                self.handler.handle_events(
+                    AppEvent::MouseButtonEvent {
+                        state,
+                        button,
+                        position: self.mouse_position,
+                    },
+                    surface_size,
+                );
+                active_state.window.request_redraw();
             }
             WindowEvent::KeyboardInput {
                 event,
@@ -134,8 +321,9 @@ impl<T: AppHandler> ApplicationHandler for BaseApp<T> {
             } => {
                 self.handler.handle_events(
                     AppEvent::KeyboardEvent {
-                        event,
+                        event: (&event).into(),
                         is_synthetic,
+                        modifiers: self.modifiers,
                     },
                     surface_size,
                 );
@@ -144,36 +332,103 @@ impl<T: AppHandler> ApplicationHandler for BaseApp<T> {
             _ => {}
         }
     }
+
+    fn user_event(&mut self, _event_loop: &ActiveEventLoop, event: AppEvent) {
+        let AppState::Active(active_state) = &self.state else {
+            return;
+        };
+
+        let surface_size = Size {
+            w: active_state.surface.config.width,
+            h: active_state.surface.config.height,
+        };
+
+        self.handler.handle_events(event, surface_size);
+        active_state.window.request_redraw();
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        let AppState::Active(active_state) = &self.state else {
+            return;
+        };
+
+        // events may have changed the title, which is only set when it did
+        let title = self
+            .handler
+            .window_title()
+            .unwrap_or_else(|| self.name.clone());
+        if title != self.title {
+            active_state.window.set_title(&title);
+            self.title = title;
+        }
+
+        // sleep until the handler wants the next frame, instead of drawing
+        // frames that don't change anything
+        match self.handler.next_redraw_time() {
+            Some(time) if time <= Instant::now() => {
+                active_state.window.request_redraw();
+                event_loop.set_control_flow(ControlFlow::Wait);
+            }
+            Some(time) => event_loop.set_control_flow(ControlFlow::WaitUntil(time)),
+            None => event_loop.set_control_flow(ControlFlow::Wait),
+        }
+    }
 }
 
 pub struct AppContext {
+    event_loop: EventLoop<AppEvent>,
     state: AppState,
     renderer: BaseAppRenderer,
     name: String,
 }
 
 impl AppContext {
-    pub fn new(name: String) -> Self {
-        Self {
+    pub fn new(name: String) -> Result<Self> {
+        Ok(Self {
+            event_loop: EventLoop::with_user_event().build()?,
             state: AppState::Suspended(SuspendedAppState {
                 cached_window: None,
             }),
             renderer: BaseAppRenderer::new(),
             name,
-        }
+        })
+    }
+
+    /// Renders on the CPU instead when rendering on the GPU fails, like on
+    /// machines without a GPU vello supports.
+    pub fn with_cpu_fallback(mut self, enabled: bool) -> Self {
+        self.renderer.set_cpu_fallback(enabled);
+        self
+    }
+
+    /// Renders on the CPU from the start, without trying the GPU.
+    pub fn with_cpu_rendering(mut self, enabled: bool) -> Self {
+        self.renderer.set_use_cpu(enabled);
+        self
+    }
+
+    /// A sender for events to be handled like any other event, once running.
+    pub fn event_sender(&self) -> AppEventSender {
+        AppEventSender(Some(self.event_loop.create_proxy()))
     }
 
     pub fn run(self, handler: impl AppHandler) -> Result<()> {
-        let event_loop = EventLoop::new()?;
-        event_loop
-            .run_app(&mut BaseApp {
-                state: self.state,
-                renderer: self.renderer,
-                name: self.name,
-                handler,
-            })
-            .expect("cannot run event loop");
-
-        Ok(())
+        let mut app = BaseApp {
+            state: self.state,
+            renderer: self.renderer,
+            title: self.name.clone(),
+            name: self.name,
+            handler,
+            modifiers: ModifiersState::empty(),
+            mouse_position: Position { x: 0.0, y: 0.0 },
+            ime_allowed: false,
+            error: None,
+        };
+        self.event_loop.run_app(&mut app)?;
+
+        match app.error {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
     }
 }
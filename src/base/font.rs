@@ -1,15 +1,57 @@
-use std::sync::Arc;
+use std::{cell::RefCell, collections::HashMap, fs, sync::Arc};
+
+use anyhow::{anyhow, bail, Result};
 use vello::{
     glyph::skrifa::{
         charmap::Charmap,
-        instance::Location,
+        instance::{Location, NormalizedCoord},
         metrics::{GlyphMetrics, Metrics},
+        raw::{tables::gsub::SubstitutionLookup, types::Tag, TableProvider},
         FontRef, GlyphId, MetadataProvider,
     },
     peniko::{Blob, Font},
 };
 
-use super::Size;
+use super::{
+    font_discovery::{discover_font, FontStyle, FontWeight},
+    Size,
+};
+
+// ranges of combining marks, which are drawn over the character before them
+const COMBINING_MARK_RANGES: &[(char, char)] = &[
+    ('\u{0300}', '\u{036F}'),
+    ('\u{0483}', '\u{0489}'),
+    ('\u{0591}', '\u{05BD}'),
+    ('\u{0610}', '\u{061A}'),
+    ('\u{064B}', '\u{065F}'),
+    ('\u{0E31}', '\u{0E31}'),
+    ('\u{0E34}', '\u{0E3A}'),
+    ('\u{0E47}', '\u{0E4E}'),
+    ('\u{1AB0}', '\u{1AFF}'),
+    ('\u{1DC0}', '\u{1DFF}'),
+    ('\u{20D0}', '\u{20FF}'),
+    ('\u{3099}', '\u{309A}'),
+    ('\u{FE20}', '\u{FE2F}'),
+];
+
+/// Whether `ch` is a combining mark, which takes up no space of its own and
+/// is stacked on the character before it.
+pub fn is_combining_mark(ch: char) -> bool {
+    COMBINING_MARK_RANGES
+        .iter()
+        .any(|(start, end)| (*start..=*end).contains(&ch))
+}
+
+/// Values of a variable font's axes by their tag, like `wght` for its weight.
+pub type VariationSettings = HashMap<String, f32>;
+
+/// An axis a variable font can be varied along, with the values it takes.
+pub struct FontAxis {
+    pub tag: String,
+    pub min: f32,
+    pub default: f32,
+    pub max: f32,
+}
 
 fn to_font_ref(font: &Font) -> Option<FontRef<'_>> {
     use vello::skrifa::raw::FileRef;
@@ -20,47 +62,225 @@ fn to_font_ref(font: &Font) -> Option<FontRef<'_>> {
     }
 }
 
+// identifies a font at a size and place on its axes, as far as its metrics go
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct MetricsKey {
+    font_id: u64,
+    font_index: u32,
+    size_bits: u32,
+    coords: Vec<i16>,
+}
+
+// what has been read of the metrics of a font, which skrifa would read again
+// every time they are asked for
+struct CachedMetrics {
+    glyph_height: f32,
+    advance_widths: HashMap<GlyphId, f32>,
+}
+
+type MetricsCache = HashMap<MetricsKey, CachedMetrics>;
+
 pub struct AppFont {
     font: Font,
+    // the family it was looked up by, if it was
+    family: Option<String>,
+    // applied wherever the font is used, before any variations of the caller
+    variation_settings: VariationSettings,
+    // read once, as big fonts have thousands of them
+    ligatures: AppFontLigatures,
+    ligatures_enabled: bool,
+    // draws the characters the font has no glyph for, like emoji
+    emoji_fallback: Option<Font>,
+    // the metrics read so far, of the font and its emoji fallback
+    metrics_cache: RefCell<MetricsCache>,
 }
 
 pub fn get_font(app_font: &AppFont) -> &Font {
     &app_font.font
 }
 
-impl From<Vec<u8>> for AppFont {
-    fn from(value: Vec<u8>) -> Self {
-        Self {
-            font: Font::new(Blob::new(Arc::new(value)), 0),
-        }
+pub fn get_emoji_font(app_font: &AppFont) -> Option<&Font> {
+    app_font.emoji_fallback.as_ref()
+}
+
+impl TryFrom<Vec<u8>> for AppFont {
+    type Error = anyhow::Error;
+
+    /// Fails if `value` isn't a font file (or collection) that can be read.
+    fn try_from(value: Vec<u8>) -> Result<Self> {
+        let font = Font::new(Blob::new(Arc::new(value)), 0);
+        let font_ref = to_font_ref(&font).ok_or_else(|| anyhow!("Not a font file"))?;
+        let ligatures = AppFontLigatures::new(&font_ref);
+
+        Ok(Self {
+            font,
+            family: None,
+            variation_settings: VariationSettings::new(),
+            ligatures,
+            ligatures_enabled: true,
+            emoji_fallback: None,
+            metrics_cache: RefCell::new(MetricsCache::new()),
+        })
     }
 }
 
 impl AppFont {
-    pub fn variations(&self, variations: &[(&str, f32)]) -> AppFontVariations {
-        AppFontVariations::new(&self.font, variations)
+    /// Loads the installed font of `family` with the given weight and style.
+    pub fn from_system_name(family: &str, weight: FontWeight, style: FontStyle) -> Result<Self> {
+        let path = discover_font(family, weight, style)
+            .ok_or_else(|| anyhow!("Cannot find font: {family}"))?;
+        let bytes = fs::read(&path)
+            .map_err(|error| anyhow!("Cannot read font {}: {error}", path.display()))?;
+
+        let mut font = Self::try_from(bytes)
+            .map_err(|error| anyhow!("Cannot load font {}: {error}", path.display()))?;
+        font.family = Some(family.to_string());
+        Ok(font)
+    }
+
+    /// The family the font was loaded by, if it was loaded by name.
+    pub fn family(&self) -> Option<&str> {
+        self.family.as_deref()
+    }
+
+    /// Takes the settings of `other`, like its variations and emoji font, for
+    /// a font replacing it.
+    pub fn inherit_settings(&mut self, other: &AppFont) {
+        self.variation_settings = other.variation_settings.clone();
+        self.ligatures_enabled = other.ligatures_enabled;
+        self.emoji_fallback = other.emoji_fallback.clone();
+        self.metrics_cache.borrow_mut().clear();
+    }
+
+    /// Draws the characters the font has no glyph for with the font in `bytes`.
+    /// Fails, leaving the fallback as it was, if `bytes` isn't a font file.
+    pub fn set_emoji_fallback(&mut self, bytes: Vec<u8>) -> Result<()> {
+        let font = Font::new(Blob::new(Arc::new(bytes)), 0);
+        if to_font_ref(&font).is_none() {
+            bail!("Not a font file");
+        }
+
+        self.emoji_fallback = Some(font);
+        self.metrics_cache.borrow_mut().clear();
+        Ok(())
+    }
+
+    /// The emoji fallback as it is drawn, which is never varied.
+    pub fn emoji_variations(&self) -> Option<AppFontVariations<'_>> {
+        let font = self.emoji_fallback.as_ref()?;
+        Some(AppFontVariations::new(
+            font,
+            &VariationSettings::new(),
+            &[],
+            &self.metrics_cache,
+        ))
+    }
+
+    pub fn variation_settings(&self) -> &VariationSettings {
+        &self.variation_settings
+    }
+
+    /// Sets the axis `tag` to `value` everywhere the font is used.
+    pub fn set_variation(&mut self, tag: &str, value: f32) {
+        self.variation_settings.insert(tag.to_string(), value);
+        // the metrics at other places on the axes won't be asked for again
+        self.metrics_cache.borrow_mut().clear();
+    }
+
+    /// The ligatures of the font, unless they are turned off.
+    pub fn ligatures(&self) -> Option<&AppFontLigatures> {
+        self.ligatures_enabled.then_some(&self.ligatures)
+    }
+
+    pub fn set_ligatures_enabled(&mut self, enabled: bool) {
+        self.ligatures_enabled = enabled;
+    }
+
+    /// The axes of the font, if it is a variable font.
+    pub fn axes(&self) -> Vec<FontAxis> {
+        let font_ref = to_font_ref(&self.font).expect("cannot get font ref");
+        font_ref
+            .axes()
+            .iter()
+            .map(|axis| FontAxis {
+                tag: axis.tag().to_string(),
+                min: axis.min_value(),
+                default: axis.default_value(),
+                max: axis.max_value(),
+            })
+            .collect()
+    }
+
+    pub fn variations(&self, variations: &[(&str, f32)]) -> AppFontVariations<'_> {
+        AppFontVariations::new(
+            &self.font,
+            &self.variation_settings,
+            variations,
+            &self.metrics_cache,
+        )
     }
 }
 
 pub struct AppFontVariations<'a> {
+    font: &'a Font,
     font_ref: FontRef<'a>,
     var_loc: Location,
+    metrics_cache: &'a RefCell<MetricsCache>,
 }
 
 impl<'a> AppFontVariations<'a> {
-    fn new(font: &'a Font, variations: &[(&str, f32)]) -> Self {
+    fn new(
+        font: &'a Font,
+        settings: &VariationSettings,
+        variations: &[(&str, f32)],
+        metrics_cache: &'a RefCell<MetricsCache>,
+    ) -> Self {
         let font_ref = to_font_ref(font).expect("cannot get font ref");
-        let var_loc = font_ref.axes().location(variations.iter().copied());
+        // the given variations come last, so they win over the settings
+        let variations: Vec<(&str, f32)> = settings
+            .iter()
+            .map(|(tag, value)| (tag.as_str(), *value))
+            .chain(variations.iter().copied())
+            .collect();
+        let var_loc = font_ref.axes().location(variations);
 
-        Self { font_ref, var_loc }
+        Self {
+            font,
+            font_ref,
+            var_loc,
+            metrics_cache,
+        }
+    }
+
+    /// Where on its axes the font is, for drawing its glyphs.
+    pub fn normalized_coords(&self) -> &[NormalizedCoord] {
+        self.var_loc.coords()
     }
 
-    pub fn glyphs(&self) -> AppFontGlyphs {
+    pub fn glyphs(&self) -> AppFontGlyphs<'_> {
         AppFontGlyphs::new(&self.font_ref)
     }
 
-    pub fn metrics(&self, font_size: f32) -> AppFontMetrics {
-        AppFontMetrics::new(&self.font_ref, font_size, &self.var_loc)
+    pub fn metrics(&self, font_size: f32) -> AppFontMetrics<'_> {
+        let key = MetricsKey {
+            font_id: self.font.data.id(),
+            font_index: self.font.index,
+            size_bits: font_size.to_bits(),
+            coords: self
+                .var_loc
+                .coords()
+                .iter()
+                .map(|coord| coord.to_bits())
+                .collect(),
+        };
+
+        AppFontMetrics::new(
+            self.font_ref.clone(),
+            font_size,
+            &self.var_loc,
+            key,
+            self.metrics_cache,
+        )
     }
 
     pub fn measure_text<T: AsRef<str>>(&self, font_size: f32, text: T) -> Size<f32> {
@@ -74,11 +294,15 @@ impl<'a> AppFontVariations<'a> {
             height += font_metrics.glyph_height();
             let mut line_width = 0.0;
 
-            line.chars().for_each(|ch| {
-                let gid = font_glyphs.glyph(ch);
-                let advance = font_metrics.glyph_width(gid);
-                line_width += advance;
-            });
+            // combining marks are stacked on the previous character, so a
+            // cluster is only as wide as its base character
+            line.chars()
+                .filter(|ch| !is_combining_mark(*ch))
+                .for_each(|ch| {
+                    let gid = font_glyphs.glyph(ch);
+                    let advance = font_metrics.glyph_width(gid);
+                    line_width += advance;
+                });
 
             width = width.max(line_width);
         });
@@ -90,29 +314,68 @@ impl<'a> AppFontVariations<'a> {
     }
 }
 
+/// The metrics of a font at a size, which are only read from the font the
+/// first time they are asked for.
 pub struct AppFontMetrics<'a> {
-    metrics: Metrics,
-    glyph_metrics: GlyphMetrics<'a>,
+    font_ref: FontRef<'a>,
+    font_size: vello::skrifa::instance::Size,
+    var_loc: &'a Location,
+    key: MetricsKey,
+    metrics_cache: &'a RefCell<MetricsCache>,
+    glyph_height: f32,
 }
 
 impl<'a> AppFontMetrics<'a> {
-    fn new(font_ref: &FontRef<'a>, font_size: f32, var_loc: &'a Location) -> Self {
+    fn new(
+        font_ref: FontRef<'a>,
+        font_size: f32,
+        var_loc: &'a Location,
+        key: MetricsKey,
+        metrics_cache: &'a RefCell<MetricsCache>,
+    ) -> Self {
         let font_size = vello::skrifa::instance::Size::new(font_size);
-        let metrics = font_ref.metrics(font_size, var_loc);
-        let glyph_metrics = font_ref.glyph_metrics(font_size, var_loc);
+        let glyph_height = metrics_cache
+            .borrow_mut()
+            .entry(key.clone())
+            .or_insert_with(|| {
+                let metrics: Metrics = font_ref.metrics(font_size, var_loc);
+                CachedMetrics {
+                    glyph_height: metrics.ascent - metrics.descent + metrics.leading,
+                    advance_widths: HashMap::new(),
+                }
+            })
+            .glyph_height;
 
         Self {
-            metrics,
-            glyph_metrics,
+            font_ref,
+            font_size,
+            var_loc,
+            key,
+            metrics_cache,
+            glyph_height,
         }
     }
 
     pub fn glyph_height(&self) -> f32 {
-        self.metrics.ascent - self.metrics.descent + self.metrics.leading
+        self.glyph_height
     }
 
     pub fn glyph_width(&self, gid: GlyphId) -> f32 {
-        self.glyph_metrics.advance_width(gid).unwrap_or_default()
+        let mut metrics_cache = self.metrics_cache.borrow_mut();
+        // always there, as the cache can't be cleared while the font is borrowed
+        let Some(cached) = metrics_cache.get_mut(&self.key) else {
+            return self.read_glyph_width(gid);
+        };
+
+        *cached
+            .advance_widths
+            .entry(gid)
+            .or_insert_with(|| self.read_glyph_width(gid))
+    }
+
+    fn read_glyph_width(&self, gid: GlyphId) -> f32 {
+        let glyph_metrics: GlyphMetrics = self.font_ref.glyph_metrics(self.font_size, self.var_loc);
+        glyph_metrics.advance_width(gid).unwrap_or_default()
     }
 }
 
@@ -131,3 +394,137 @@ impl<'a> AppFontGlyphs<'a> {
         self.charmap.map(ch).unwrap_or_default()
     }
 }
+
+// by their first glyph, the rest of the glyphs each ligature replaces and
+// the ligature, in the order the font prefers them
+type LigatureMap = HashMap<GlyphId, Vec<(Vec<GlyphId>, GlyphId)>>;
+
+/// The glyphs a font draws in place of runs of glyphs, like a single arrow
+/// for `->`, from the `liga` feature of its GSUB table.
+pub struct AppFontLigatures {
+    ligatures: LigatureMap,
+}
+
+impl AppFontLigatures {
+    fn new(font_ref: &FontRef) -> Self {
+        Self {
+            // a font without a GSUB table simply has no ligatures
+            ligatures: read_ligatures(font_ref).unwrap_or_default(),
+        }
+    }
+
+    /// The ligature `glyphs` start with, and how many glyphs it replaces.
+    pub fn find(&self, glyphs: &[GlyphId]) -> Option<(GlyphId, usize)> {
+        let (first, rest) = glyphs.split_first()?;
+        self.ligatures
+            .get(first)?
+            .iter()
+            .find(|(components, _)| rest.starts_with(components))
+            .map(|(components, ligature)| (*ligature, components.len() + 1))
+    }
+}
+
+// only the ligature lookups of `liga` are read. contextual alternates (`calt`),
+// which some fonts use for their ligatures instead, are not supported.
+fn read_ligatures(font_ref: &FontRef) -> Option<LigatureMap> {
+    let gsub = font_ref.gsub().ok()?;
+    let feature_list = gsub.feature_list().ok()?;
+    let lookup_list = gsub.lookup_list().ok()?;
+
+    let liga = Tag::new(b"liga");
+    let mut lookup_indices: Vec<u16> = feature_list
+        .feature_records()
+        .iter()
+        .filter(|record| record.feature_tag() == liga)
+        .filter_map(|record| record.feature(feature_list.offset_data()).ok())
+        .flat_map(|feature| {
+            feature
+                .lookup_list_indices()
+                .iter()
+                .map(|index| index.get())
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    // the feature is listed once per script and language
+    lookup_indices.sort_unstable();
+    lookup_indices.dedup();
+
+    let mut ligatures = LigatureMap::new();
+    for index in lookup_indices {
+        let Ok(SubstitutionLookup::Ligature(lookup)) = lookup_list.lookups().get(index as usize)
+        else {
+            continue;
+        };
+
+        for subtable in lookup.subtables().iter().flatten() {
+            let Ok(coverage) = subtable.coverage() else {
+                continue;
+            };
+
+            // the ligature sets are in the order of the glyphs they start with
+            for (first, set) in coverage.iter().zip(subtable.ligature_sets().iter()) {
+                let Ok(set) = set else {
+                    continue;
+                };
+
+                for ligature in set.ligatures().iter().flatten() {
+                    let components = ligature
+                        .component_glyph_ids()
+                        .iter()
+                        .map(|gid| gid.get())
+                        .collect();
+                    ligatures
+                        .entry(first)
+                        .or_default()
+                        .push((components, ligature.ligature_glyph()));
+                }
+            }
+        }
+    }
+
+    Some(ligatures)
+}
+
+/// DejaVu Sans Mono, for tests that need to measure or draw real glyphs.
+#[cfg(test)]
+pub fn test_font() -> AppFont {
+    AppFont::try_from(include_bytes!("../../tests/fixtures/DejaVuSansMono.ttf").to_vec()).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_combining_mark_only_matches_marks() {
+        assert!(is_combining_mark('\u{0301}'));
+        assert!(is_combining_mark('\u{20D7}'));
+        assert!(is_combining_mark('\u{3099}'));
+        assert!(!is_combining_mark('e'));
+        assert!(!is_combining_mark('é'));
+    }
+
+    #[test]
+    fn combining_marks_take_no_width_of_their_own() {
+        let font = test_font();
+        let variations = font.variations(&[]);
+
+        assert_eq!(
+            variations.measure_text(16.0, "e\u{0301}"),
+            variations.measure_text(16.0, "e")
+        );
+        assert_eq!(
+            variations.measure_text(16.0, "e\u{0301}e").w,
+            variations.measure_text(16.0, "ee").w
+        );
+    }
+
+    #[test]
+    fn fonts_are_only_made_from_font_files() {
+        assert!(AppFont::try_from(b"not a font".to_vec()).is_err());
+
+        let mut font = test_font();
+        assert!(font.set_emoji_fallback(vec![]).is_err());
+        assert!(font.emoji_variations().is_none());
+    }
+}
@@ -0,0 +1,301 @@
+use std::{fs, path::Path, path::PathBuf};
+
+// how deep font folders are searched, like /usr/share/fonts/truetype/dejavu
+const MAX_FONT_DIR_DEPTH: usize = 3;
+
+/// How heavy the strokes of a font are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FontWeight {
+    Regular,
+    Bold,
+}
+
+/// Whether a font is upright or slanted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FontStyle {
+    Normal,
+    Italic,
+}
+
+/// Finds a monospace font installed on the system, preferring the one the
+/// system is set up to use where it can tell.
+pub fn discover_monospace_font() -> Option<PathBuf> {
+    #[cfg(target_os = "macos")]
+    if let Some(path) = super::platform::macos::find_monospace_font() {
+        return Some(path);
+    }
+
+    font_candidates().into_iter().find(|path| path.is_file())
+}
+
+/// Finds an emoji font installed on the system, for the characters the
+/// monospace font has no glyph for.
+pub fn discover_emoji_font() -> Option<PathBuf> {
+    EMOJI_FONT_PATHS
+        .iter()
+        .map(PathBuf::from)
+        .find(|path| path.is_file())
+}
+
+/// Finds the installed font file of `family` with the given weight and style,
+/// like `JetBrains Mono` in bold.
+pub fn discover_font(family: &str, weight: FontWeight, style: FontStyle) -> Option<PathBuf> {
+    #[cfg(not(any(windows, target_os = "macos")))]
+    if let Some(path) = fontconfig_find(family, weight, style) {
+        return Some(path);
+    }
+    #[cfg(target_os = "macos")]
+    if let Some(path) = super::platform::macos::find_font(family, weight, style) {
+        return Some(path);
+    }
+
+    font_dirs()
+        .iter()
+        .find_map(|dir| find_font_file(dir, family, weight, style, MAX_FONT_DIR_DEPTH))
+}
+
+// `text` in lowercase with only its letters and digits, so that the family
+// `JetBrains Mono` matches the file JetBrainsMono-Bold.ttf
+fn normalize(text: &str) -> String {
+    text.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|ch| ch.to_ascii_lowercase())
+        .collect()
+}
+
+// looks through `dir` and the folders in it for a font file named after
+// `family`, followed by the name of the weight and style
+fn find_font_file(
+    dir: &Path,
+    family: &str,
+    weight: FontWeight,
+    style: FontStyle,
+    depth: usize,
+) -> Option<PathBuf> {
+    let suffixes: &[&str] = match (weight, style) {
+        (FontWeight::Regular, FontStyle::Normal) => &["", "regular", "book", "roman"],
+        (FontWeight::Regular, FontStyle::Italic) => &["italic", "oblique", "regularitalic"],
+        (FontWeight::Bold, FontStyle::Normal) => &["bold"],
+        (FontWeight::Bold, FontStyle::Italic) => &["bolditalic", "boldoblique"],
+    };
+    let family = normalize(family);
+
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .collect();
+    // the same font is found each time, whatever order the folder lists it in
+    entries.sort();
+
+    let found = entries.iter().find(|path| {
+        let is_font = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| {
+                ["ttf", "otf", "ttc"].contains(&extension.to_ascii_lowercase().as_str())
+            });
+        let stem = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .map(normalize)
+            .unwrap_or_default();
+        is_font
+            && stem
+                .strip_prefix(&family)
+                .is_some_and(|suffix| suffixes.contains(&suffix))
+    });
+    if let Some(found) = found {
+        return Some(found.clone());
+    }
+
+    if depth == 0 {
+        return None;
+    }
+    entries
+        .iter()
+        .filter(|path| path.is_dir())
+        .find_map(|path| find_font_file(path, family.as_str(), weight, style, depth - 1))
+}
+
+// `dir` with a leading ~ replaced by the home directory
+#[cfg(not(windows))]
+fn expand_home(dir: &str) -> Option<PathBuf> {
+    match dir.strip_prefix("~/") {
+        Some(rest) => Some(Path::new(&std::env::var_os("HOME")?).join(rest)),
+        None => Some(PathBuf::from(dir)),
+    }
+}
+
+#[cfg(windows)]
+const WINDOWS_FONT_PATHS: &[&str] = &[
+    r"C:\Windows\Fonts\consola.ttf",
+    r"C:\Windows\Fonts\cour.ttf",
+];
+
+// emoji fonts, best first. only the outlines of glyphs are drawn, so fonts
+// that have them come before those with only color bitmaps
+#[cfg(windows)]
+const EMOJI_FONT_PATHS: &[&str] = &[
+    r"C:\Windows\Fonts\seguiemj.ttf",
+    r"C:\Windows\Fonts\seguisym.ttf",
+];
+
+// the fonts to try, best first
+#[cfg(windows)]
+fn font_candidates() -> Vec<PathBuf> {
+    WINDOWS_FONT_PATHS.iter().map(PathBuf::from).collect()
+}
+
+// where fonts are installed, for everyone and for the user
+#[cfg(windows)]
+fn font_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![PathBuf::from(r"C:\Windows\Fonts")];
+    if let Some(local) = std::env::var_os("LOCALAPPDATA") {
+        dirs.push(Path::new(&local).join(r"Microsoft\Windows\Fonts"));
+    }
+    dirs
+}
+
+// where macOS keeps fonts, the user's own first
+#[cfg(target_os = "macos")]
+const MACOS_FONT_DIRS: &[&str] = &["~/Library/Fonts", "/Library/Fonts", "/System/Library/Fonts"];
+
+// monospace fonts looked for in MACOS_FONT_DIRS, best first, for when Core
+// Text can't be asked
+#[cfg(target_os = "macos")]
+const MACOS_FONT_FILES: &[&str] = &["Menlo.ttc", "Monaco.ttf", "Courier New.ttf"];
+
+#[cfg(target_os = "macos")]
+const EMOJI_FONT_PATHS: &[&str] = &[
+    "/System/Library/Fonts/Apple Color Emoji.ttc",
+    "/System/Library/Fonts/Apple Symbols.ttf",
+];
+
+#[cfg(target_os = "macos")]
+fn font_dirs() -> Vec<PathBuf> {
+    MACOS_FONT_DIRS
+        .iter()
+        .filter_map(|dir| expand_home(dir))
+        .collect()
+}
+
+#[cfg(target_os = "macos")]
+fn font_candidates() -> Vec<PathBuf> {
+    MACOS_FONT_FILES
+        .iter()
+        .flat_map(|file| {
+            MACOS_FONT_DIRS
+                .iter()
+                .filter_map(move |dir| Some(expand_home(dir)?.join(file)))
+        })
+        .collect()
+}
+
+// monospace fonts that come with common Linux distributions, best first, for
+// when fontconfig can't be asked
+#[cfg(not(any(windows, target_os = "macos")))]
+const LINUX_FONT_PATHS: &[&str] = &[
+    "/usr/share/fonts/truetype/dejavu/DejaVuSansMono.ttf",
+    "/usr/share/fonts/TTF/DejaVuSansMono.ttf",
+    "/usr/share/fonts/dejavu/DejaVuSansMono.ttf",
+    "/usr/share/fonts/dejavu-sans-mono-fonts/DejaVuSansMono.ttf",
+    "/usr/share/fonts/truetype/liberation/LiberationMono-Regular.ttf",
+    "/usr/share/fonts/liberation-mono/LiberationMono-Regular.ttf",
+    "/usr/share/fonts/truetype/noto/NotoSansMono-Regular.ttf",
+    "/usr/share/fonts/noto/NotoSansMono-Regular.ttf",
+    "/usr/share/fonts/truetype/ubuntu/UbuntuMono-R.ttf",
+];
+
+#[cfg(not(any(windows, target_os = "macos")))]
+const EMOJI_FONT_PATHS: &[&str] = &[
+    "/usr/share/fonts/truetype/noto/NotoEmoji-Regular.ttf",
+    "/usr/share/fonts/noto/NotoEmoji-Regular.ttf",
+    "/usr/share/fonts/truetype/ancient-scripts/Symbola_hint.ttf",
+    "/usr/share/fonts/gdouros-symbola/Symbola.ttf",
+    "/usr/share/fonts/truetype/noto/NotoColorEmoji.ttf",
+    "/usr/share/fonts/noto/NotoColorEmoji.ttf",
+    "/usr/share/fonts/google-noto-emoji/NotoColorEmoji.ttf",
+];
+
+// where fonts are installed on Linux, the user's own first
+#[cfg(not(any(windows, target_os = "macos")))]
+const LINUX_FONT_DIRS: &[&str] = &[
+    "~/.local/share/fonts",
+    "~/.fonts",
+    "/usr/local/share/fonts",
+    "/usr/share/fonts",
+];
+
+#[cfg(not(any(windows, target_os = "macos")))]
+fn font_dirs() -> Vec<PathBuf> {
+    LINUX_FONT_DIRS
+        .iter()
+        .filter_map(|dir| expand_home(dir))
+        .collect()
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+fn font_candidates() -> Vec<PathBuf> {
+    fontconfig_match()
+        .into_iter()
+        .chain(LINUX_FONT_PATHS.iter().map(PathBuf::from))
+        .collect()
+}
+
+// asks fontconfig which font it would use for monospace text, through the
+// fc-match tool that comes with it
+#[cfg(not(any(windows, target_os = "macos")))]
+fn fontconfig_match() -> Option<PathBuf> {
+    let output = std::process::Command::new("fc-match")
+        .args(["--format=%{file}", "monospace:spacing=100"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let path = String::from_utf8(output.stdout).ok()?;
+    let path = path.trim();
+    (!path.is_empty()).then(|| PathBuf::from(path))
+}
+
+// asks fontconfig for `family` in the given weight and style. fontconfig
+// always answers with its closest font, so only one of that family counts.
+#[cfg(not(any(windows, target_os = "macos")))]
+fn fontconfig_find(family: &str, weight: FontWeight, style: FontStyle) -> Option<PathBuf> {
+    // in fontconfig's own scale
+    let weight = match weight {
+        FontWeight::Regular => 80,
+        FontWeight::Bold => 200,
+    };
+    let slant = match style {
+        FontStyle::Normal => 0,
+        FontStyle::Italic => 100,
+    };
+    // these separate the parts of a pattern
+    let escaped: String = family
+        .chars()
+        .flat_map(|ch| match ch {
+            '-' | ':' | ',' | '\\' => vec!['\\', ch],
+            ch => vec![ch],
+        })
+        .collect();
+
+    let output = std::process::Command::new("fc-match")
+        .args([
+            "--format=%{family}\n%{file}",
+            &format!("{escaped}:weight={weight}:slant={slant}"),
+        ])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let output = String::from_utf8(output.stdout).ok()?;
+    let (families, path) = output.split_once('\n')?;
+    families
+        .split(',')
+        .any(|name| name.eq_ignore_ascii_case(family))
+        .then(|| PathBuf::from(path.trim()))
+}
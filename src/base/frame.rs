@@ -0,0 +1,174 @@
+use vello::wgpu::{
+    BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, Color, ColorTargetState, ColorWrites,
+    CommandEncoderDescriptor, Device, Extent3d, FragmentState, LoadOp, MultisampleState,
+    Operations, PipelineCompilationOptions, PipelineLayoutDescriptor, PrimitiveState, Queue,
+    RenderPassColorAttachment, RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor,
+    ShaderModuleDescriptor, ShaderSource, ShaderStages, StoreOp, TextureDescriptor,
+    TextureDimension, TextureFormat, TextureSampleType, TextureUsages, TextureView,
+    TextureViewDescriptor, TextureViewDimension, VertexState,
+};
+
+// draws a texture over the whole of the target, with its colors premultiplied
+// by their alpha as the surface expects
+const BLIT_SHADER: &str = r#"
+    @vertex
+    fn vs_main(@builtin(vertex_index) ix: u32) -> @builtin(position) vec4<f32> {
+        // two triangles covering the target, in normalized device coordinates
+        var vertex = vec2(-1.0, 1.0);
+        switch ix {
+            case 1u: {
+                vertex = vec2(-1.0, -1.0);
+            }
+            case 2u, 4u: {
+                vertex = vec2(1.0, -1.0);
+            }
+            case 5u: {
+                vertex = vec2(1.0, 1.0);
+            }
+            default: {}
+        }
+        return vec4(vertex, 0.0, 1.0);
+    }
+
+    @group(0) @binding(0)
+    var frame: texture_2d<f32>;
+
+    @fragment
+    fn fs_main(@builtin(position) pos: vec4<f32>) -> @location(0) vec4<f32> {
+        let rgba = textureLoad(frame, vec2<i32>(pos.xy), 0);
+        return vec4(rgba.rgb * rgba.a, rgba.a);
+    }
+"#;
+
+/// The last frame rendered, kept in a texture so that it can be presented
+/// again without rendering its scene again.
+pub struct KeptFrame {
+    view: TextureView,
+    width: u32,
+    height: u32,
+    bind_layout: BindGroupLayout,
+    pipeline: RenderPipeline,
+}
+
+impl KeptFrame {
+    /// A frame of `width` by `height` pixels, copied to surfaces of `format`.
+    pub fn new(device: &Device, format: TextureFormat, width: u32, height: u32) -> Self {
+        // vello can only render to storage textures of this format
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("kept frame"),
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            usage: TextureUsages::STORAGE_BINDING | TextureUsages::TEXTURE_BINDING,
+            format: TextureFormat::Rgba8Unorm,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("kept frame blit"),
+            source: ShaderSource::Wgsl(BLIT_SHADER.into()),
+        });
+        let bind_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: true },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bind_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("kept frame blit"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                compilation_options: PipelineCompilationOptions::default(),
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                compilation_options: PipelineCompilationOptions::default(),
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            view,
+            width,
+            height,
+            bind_layout,
+            pipeline,
+        }
+    }
+
+    pub fn view(&self) -> &TextureView {
+        &self.view
+    }
+
+    /// Whether the frame is `width` by `height` pixels, the size it has to
+    /// be to cover a surface of that size.
+    pub fn has_size(&self, width: u32, height: u32) -> bool {
+        self.width == width && self.height == height
+    }
+
+    /// Copies the frame onto `target`, the texture of a surface.
+    pub fn copy_to(&self, device: &Device, queue: &Queue, target: &TextureView) {
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &self.bind_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(&self.view),
+            }],
+        });
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("kept frame blit"),
+        });
+        {
+            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: target,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Clear(Color::BLACK),
+                        store: StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.draw(0..6, 0..1);
+        }
+        queue.submit([encoder.finish()]);
+    }
+}
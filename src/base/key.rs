@@ -0,0 +1,87 @@
+#[cfg(test)]
+use winit::keyboard::{KeyCode, NamedKey, NativeKeyCode};
+use winit::{
+    event::{ElementState, KeyEvent},
+    keyboard::{Key, PhysicalKey},
+};
+
+/// A key pressed or released. Unlike winit's `KeyEvent`, it can be made
+/// outside of winit, like for keys that are replayed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppKeyEvent {
+    /// the key, as the keyboard layout and modifiers make it
+    pub logical_key: Key,
+    /// where the key is on the keyboard, whatever the layout
+    pub physical_key: PhysicalKey,
+    /// the text the key types, if any
+    pub text: Option<String>,
+    pub state: ElementState,
+}
+
+impl From<&KeyEvent> for AppKeyEvent {
+    fn from(event: &KeyEvent) -> Self {
+        Self {
+            logical_key: event.logical_key.clone(),
+            physical_key: event.physical_key,
+            text: event.text.as_ref().map(ToString::to_string),
+            state: event.state,
+        }
+    }
+}
+
+/// A press of the key on a US layout that types `text`, for tests.
+#[cfg(test)]
+pub fn test_key(text: &str) -> AppKeyEvent {
+    const LETTERS: [KeyCode; 26] = [
+        KeyCode::KeyA,
+        KeyCode::KeyB,
+        KeyCode::KeyC,
+        KeyCode::KeyD,
+        KeyCode::KeyE,
+        KeyCode::KeyF,
+        KeyCode::KeyG,
+        KeyCode::KeyH,
+        KeyCode::KeyI,
+        KeyCode::KeyJ,
+        KeyCode::KeyK,
+        KeyCode::KeyL,
+        KeyCode::KeyM,
+        KeyCode::KeyN,
+        KeyCode::KeyO,
+        KeyCode::KeyP,
+        KeyCode::KeyQ,
+        KeyCode::KeyR,
+        KeyCode::KeyS,
+        KeyCode::KeyT,
+        KeyCode::KeyU,
+        KeyCode::KeyV,
+        KeyCode::KeyW,
+        KeyCode::KeyX,
+        KeyCode::KeyY,
+        KeyCode::KeyZ,
+    ];
+
+    let physical_key = match text.chars().next() {
+        Some(ch) if ch.is_ascii_alphabetic() => {
+            PhysicalKey::Code(LETTERS[(ch.to_ascii_lowercase() as u8 - b'a') as usize])
+        }
+        _ => PhysicalKey::Unidentified(NativeKeyCode::Unidentified),
+    };
+    AppKeyEvent {
+        logical_key: Key::Character(text.into()),
+        physical_key,
+        text: Some(text.to_string()),
+        state: ElementState::Pressed,
+    }
+}
+
+/// A press of a key that types no text, like Escape, for tests.
+#[cfg(test)]
+pub fn test_named_key(key: NamedKey) -> AppKeyEvent {
+    AppKeyEvent {
+        logical_key: Key::Named(key),
+        physical_key: PhysicalKey::Unidentified(NativeKeyCode::Unidentified),
+        text: None,
+        state: ElementState::Pressed,
+    }
+}
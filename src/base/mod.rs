@@ -1,14 +1,40 @@
 mod base_app;
 mod font;
+mod font_discovery;
+mod frame;
+mod key;
 mod math;
+#[cfg(target_os = "macos")]
+mod platform;
 mod renderer;
 
 pub use base_app::AppContext;
 pub use base_app::AppEvent;
+pub use base_app::AppEventSender;
 pub use base_app::AppHandler;
+pub use font::is_combining_mark;
+#[cfg(test)]
+pub use font::test_font;
 pub use font::AppFont;
+pub use font_discovery::discover_emoji_font;
+pub use font_discovery::discover_monospace_font;
+pub use font_discovery::FontStyle;
+pub use font_discovery::FontWeight;
+pub use key::AppKeyEvent;
+#[cfg(test)]
+pub use key::{test_key, test_named_key};
 pub use math::Position;
 pub use math::Size;
 pub use renderer::AppRenderer;
+#[cfg(test)]
+pub use renderer::BaseAppRenderer;
+pub use renderer::CursorShape;
+pub use renderer::DrawColoredRunsOptions;
+pub use renderer::DrawCursorOptions;
 pub use renderer::DrawFillRectangleOptions;
+pub use renderer::DrawLineOptions;
+pub use renderer::DrawRoundedRectangleOptions;
 pub use renderer::DrawTextOptions;
+pub use renderer::DrawUnderlineOptions;
+pub use renderer::TextRun;
+pub use renderer::UnderlineStyle;
@@ -0,0 +1,45 @@
+use std::path::PathBuf;
+
+use core_text::{
+    font_collection,
+    font_descriptor::{CTFontDescriptor, SymbolicTraitAccessors, TraitAccessors},
+};
+
+use crate::base::{FontStyle, FontWeight};
+
+// monospace families that come with macOS, best first
+const MONOSPACE_FAMILIES: &[&str] = &["Menlo", "SF Mono", "Monaco", "Courier New"];
+
+/// Asks Core Text for a monospace font, preferring those that come with
+/// macOS over whichever other one is installed.
+pub fn find_monospace_font() -> Option<PathBuf> {
+    MONOSPACE_FAMILIES
+        .iter()
+        .find_map(|family| find_font(family, FontWeight::Regular, FontStyle::Normal))
+        .or_else(|| {
+            let descriptors = font_collection::create_for_all_families().get_descriptors()?;
+            let found = descriptors.iter().find_map(|descriptor| {
+                let is_monospace = descriptor.traits().symbolic_traits().is_monospace();
+                (is_monospace && has_style(&descriptor, FontWeight::Regular, FontStyle::Normal))
+                    .then(|| descriptor.font_path())
+                    .flatten()
+            });
+            found
+        })
+}
+
+/// Asks Core Text for the file of `family` with the given weight and style.
+pub fn find_font(family: &str, weight: FontWeight, style: FontStyle) -> Option<PathBuf> {
+    let descriptors = font_collection::create_for_family(family)?.get_descriptors()?;
+    let found = descriptors
+        .iter()
+        .filter(|descriptor| has_style(descriptor, weight, style))
+        .find_map(|descriptor| descriptor.font_path());
+    found
+}
+
+fn has_style(descriptor: &CTFontDescriptor, weight: FontWeight, style: FontStyle) -> bool {
+    let traits = descriptor.traits().symbolic_traits();
+    traits.is_bold() == (weight == FontWeight::Bold)
+        && traits.is_italic() == (style == FontStyle::Italic)
+}
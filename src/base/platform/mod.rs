@@ -0,0 +1,2 @@
+#[cfg(target_os = "macos")]
+pub mod macos;
@@ -1,27 +1,77 @@
+use anyhow::{anyhow, Context, Result};
 use std::{marker::PhantomData, num::NonZeroUsize, sync::Arc};
 use vello::{
-    glyph::Glyph,
-    kurbo::{Affine, Rect},
+    glyph::{skrifa::GlyphId, Glyph},
+    kurbo::{Affine, BezPath, Line, Rect, RoundedRect, Stroke},
     peniko::{BrushRef, Color, Fill, StyleRef},
     util::{RenderContext, RenderSurface},
-    wgpu::{Maintain, PresentMode},
+    wgpu::{Maintain, PresentMode, TextureViewDescriptor},
     AaConfig, AaSupport, RenderParams, Renderer, RendererOptions, Scene,
 };
 use winit::{dpi::PhysicalSize, window::Window};
 
-use super::{font::get_font, AppFont, Position, Size};
+use super::{
+    font::{
+        get_emoji_font, get_font, AppFontGlyphs, AppFontLigatures, AppFontMetrics,
+        AppFontVariations,
+    },
+    frame::KeptFrame,
+    is_combining_mark, AppFont, FontStyle, FontWeight, Position, Size,
+};
+
+// in pixels, from one crest of a wavy underline to the next
+const UNDERLINE_WAVE_LENGTH: f64 = 4.0;
+
+// how far italic glyphs lean, as the distance to the right per unit up
+const ITALIC_SKEW: f64 = 0.2;
 
-fn create_vello_renderer(context: &RenderContext, surface: &RenderSurface) -> Renderer {
+// the axis of a variable font that bold text is drawn at the heaviest end of
+const WEIGHT_AXIS: &str = "wght";
+
+// how thick the outline drawn around bold glyphs of fonts that can't vary
+// their weight is, in ems
+const FAKE_BOLD_STROKE: f32 = 0.04;
+
+// the renderer for `surface`, running its shaders on the CPU if `use_cpu`,
+// which fails when the GPU lacks what vello needs
+fn try_create_renderer(
+    context: &RenderContext,
+    surface: &RenderSurface,
+    use_cpu: bool,
+) -> Result<Renderer> {
     Renderer::new(
         &context.devices[surface.dev_id].device,
         RendererOptions {
             surface_format: Some(surface.format),
-            use_cpu: false,
+            use_cpu,
             antialiasing_support: AaSupport::all(),
             num_init_threads: NonZeroUsize::new(1),
         },
     )
-    .expect("couldn't create renderer")
+    .map_err(|error| anyhow!("Cannot create renderer: {error}"))
+}
+
+// the renderer for `surface`, with a warning for the user when it had to
+// fall back to the CPU
+fn create_vello_renderer(
+    context: &RenderContext,
+    surface: &RenderSurface,
+    use_cpu: bool,
+    cpu_fallback: bool,
+) -> Result<(Renderer, Option<String>)> {
+    if use_cpu {
+        return Ok((try_create_renderer(context, surface, true)?, None));
+    }
+
+    match try_create_renderer(context, surface, false) {
+        Ok(renderer) => Ok((renderer, None)),
+        Err(error) if cpu_fallback => {
+            let renderer = try_create_renderer(context, surface, true)?;
+            let warning = format!("{error:#}, rendering on the CPU instead");
+            Ok((renderer, Some(warning)))
+        }
+        Err(error) => Err(error),
+    }
 }
 
 pub struct BaseAppRenderer {
@@ -30,6 +80,19 @@ pub struct BaseAppRenderer {
     // reuse scene every frame, so that we don't spend resources
     // recreating it every frame
     scene: Scene,
+    // the scene rendered last, presented again while the scene stays the same
+    frame: Option<KeptFrame>,
+    // whether the scene was drawn again since it was last rendered
+    scene_changed: bool,
+    // composed transforms pushed by AppRenderer::push_transform, the last
+    // one is applied to everything drawn
+    transforms: Vec<Affine>,
+    // the color behind everything drawn
+    background: Color,
+    // whether to render on the CPU from the start, instead of the GPU
+    use_cpu: bool,
+    // whether to render on the CPU when rendering on the GPU fails
+    cpu_fallback: bool,
 }
 
 impl BaseAppRenderer {
@@ -38,11 +101,31 @@ impl BaseAppRenderer {
             context: RenderContext::new(),
             renderers: vec![],
             scene: Scene::new(),
+            frame: None,
+            scene_changed: true,
+            transforms: vec![],
+            background: Color::BLACK,
+            use_cpu: false,
+            cpu_fallback: false,
         }
     }
 
+    pub fn set_use_cpu(&mut self, use_cpu: bool) {
+        self.use_cpu = use_cpu;
+    }
+
+    pub fn set_cpu_fallback(&mut self, cpu_fallback: bool) {
+        self.cpu_fallback = cpu_fallback;
+    }
+
     // our window is backed by an Arc, so we actually can use static lifetime for RenderSurface
-    pub fn create_vello_surface(&mut self, window: &Arc<Window>) -> RenderSurface<'static> {
+    //
+    // returns a warning for the user along with the surface when its renderer
+    // had to fall back to the CPU
+    pub fn create_vello_surface(
+        &mut self,
+        window: &Arc<Window>,
+    ) -> Result<(RenderSurface<'static>, Option<String>)> {
         let size = window.inner_size();
 
         // wgpu may crash if width or height is 0, don't allow that
@@ -52,14 +135,20 @@ impl BaseAppRenderer {
         let surface_future =
             self.context
                 .create_surface(window.clone(), width, height, PresentMode::AutoVsync);
-        let surface = pollster::block_on(surface_future).expect("error creating surface");
+        let surface = pollster::block_on(surface_future)
+            .map_err(|error| anyhow!("Cannot create surface: {error}"))?;
 
         self.renderers
             .resize_with(self.context.devices.len(), || None);
-        self.renderers[surface.dev_id]
-            .get_or_insert_with(|| create_vello_renderer(&self.context, &surface));
+        let mut warning = None;
+        if self.renderers[surface.dev_id].is_none() {
+            let (renderer, fallback_warning) =
+                create_vello_renderer(&self.context, &surface, self.use_cpu, self.cpu_fallback)?;
+            self.renderers[surface.dev_id] = Some(renderer);
+            warning = fallback_warning;
+        }
 
-        surface
+        Ok((surface, warning))
     }
 
     pub fn resize_surface(&self, surface: &mut RenderSurface, size: &PhysicalSize<u32>) {
@@ -72,9 +161,15 @@ impl BaseAppRenderer {
 
     pub fn start_new_frame(&mut self) {
         self.scene.reset();
+        self.transforms.clear();
+        self.scene_changed = true;
+    }
+
+    fn current_transform(&self) -> Affine {
+        self.transforms.last().copied().unwrap_or(Affine::IDENTITY)
     }
 
-    pub fn present_frame(&mut self, surface: &RenderSurface) {
+    pub fn present_frame(&mut self, surface: &RenderSurface) -> Result<()> {
         let width = surface.config.width;
         let height = surface.config.height;
         let device_handle = &self.context.devices[surface.dev_id];
@@ -82,28 +177,48 @@ impl BaseAppRenderer {
         let surface_texture = surface
             .surface
             .get_current_texture()
-            .expect("cannot get surface texture");
-
-        self.renderers[surface.dev_id]
-            .as_mut()
-            .unwrap()
-            .render_to_surface(
-                &device_handle.device,
-                &device_handle.queue,
-                &self.scene,
-                &surface_texture,
-                &RenderParams {
-                    base_color: Color::BLACK,
-                    width,
-                    height,
-                    antialiasing_method: AaConfig::Msaa16,
-                },
-            )
-            .expect("failed to render to surface");
+            .context("Cannot get the window's surface texture")?;
+
+        // the scene is only rendered again when it changed, or the frame no
+        // longer covers the surface
+        let frame = match self.frame.take() {
+            Some(frame) if frame.has_size(width, height) => frame,
+            _ => {
+                self.scene_changed = true;
+                KeptFrame::new(&device_handle.device, surface.format, width, height)
+            }
+        };
+        if self.scene_changed {
+            self.renderers[surface.dev_id]
+                .as_mut()
+                .unwrap()
+                .render_to_texture(
+                    &device_handle.device,
+                    &device_handle.queue,
+                    &self.scene,
+                    frame.view(),
+                    &RenderParams {
+                        base_color: self.background,
+                        width,
+                        height,
+                        antialiasing_method: AaConfig::Msaa16,
+                    },
+                )
+                .map_err(|error| anyhow!("Cannot render frame: {error}"))?;
+            self.scene_changed = false;
+        }
+
+        let surface_view = surface_texture
+            .texture
+            .create_view(&TextureViewDescriptor::default());
+        frame.copy_to(&device_handle.device, &device_handle.queue, &surface_view);
+        self.frame = Some(frame);
 
         surface_texture.present();
 
         device_handle.device.poll(Maintain::Poll);
+
+        Ok(())
     }
 }
 
@@ -123,12 +238,237 @@ where
     pub _marker: PhantomData<&'a ()>,
 }
 
+/// Text made of runs of different colors, drawn as a single text.
+pub struct DrawColoredRunsOptions<'a> {
+    pub font: &'a AppFont,
+    pub size: f32,
+    /// where the text starts
+    pub origin: Position<f64>,
+    pub runs: &'a [TextRun],
+}
+
+/// A part of a text drawn in one color and style.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextRun {
+    pub text: String,
+    pub color: Color,
+    pub weight: FontWeight,
+    pub style: FontStyle,
+}
+
 pub struct DrawFillRectangleOptions {
     pub pos: Position<f64>,
     pub size: Size<f64>,
     pub fill_color: Color,
 }
 
+pub struct DrawRoundedRectangleOptions {
+    pub pos: Position<f64>,
+    pub size: Size<f64>,
+    pub radius: f64,
+    pub fill_color: Color,
+    /// drawn around the edge, one pixel wide
+    pub border_color: Option<Color>,
+}
+
+/// How the cursor is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorShape {
+    Block,
+    /// a bar on the left of the cell, `percent` of the cell wide
+    Bar {
+        percent: u8,
+    },
+    /// a line at the bottom of the cell, `percent` of the cell high
+    Underline {
+        percent: u8,
+    },
+}
+
+pub struct DrawCursorOptions {
+    pub shape: CursorShape,
+    /// the top left of the cell the cursor is on
+    pub pos: Position<f64>,
+    /// the cell the cursor is on, which is wider for wide characters
+    pub cell_size: Size<f64>,
+    pub color: Color,
+}
+
+/// How a line under text is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnderlineStyle {
+    Straight,
+    Wavy,
+    Dotted,
+}
+
+pub struct DrawUnderlineOptions {
+    /// the left end of the line, just under the text
+    pub pos: Position<f64>,
+    pub width: f64,
+    pub color: Color,
+    pub style: UnderlineStyle,
+}
+
+pub struct DrawLineOptions {
+    pub start: Position<f64>,
+    pub end: Position<f64>,
+    pub width: f64,
+    pub color: Color,
+}
+
+/// Where the next glyph of a text is placed. It is kept across runs of
+/// differently colored text, so that they line up exactly like a single text.
+#[derive(Default)]
+struct Pen {
+    x: f32,
+    y: f32,
+    // where the last non-combining glyph was drawn, and its advance, which is
+    // 0 at the start of a line, as there is nothing to combine with
+    base_x: f32,
+    base_advance: f32,
+}
+
+impl Pen {
+    // the glyph `gid` drawn for `ch`, moving the pen past it. newlines move
+    // the pen to the next line instead of having a glyph
+    fn place(&mut self, ch: char, gid: GlyphId, font_metrics: &AppFontMetrics) -> Option<Glyph> {
+        // a ligature moves the pen by its own advance, however many
+        // characters it stands for
+        let advance = font_metrics.glyph_width(gid);
+        self.place_with_advance(ch, gid, advance, font_metrics)
+    }
+
+    // like `place`, moving the pen by `advance`, for glyphs of another font
+    fn place_with_advance(
+        &mut self,
+        ch: char,
+        gid: GlyphId,
+        advance: f32,
+        font_metrics: &AppFontMetrics,
+    ) -> Option<Glyph> {
+        if ch == '\n' {
+            self.y += font_metrics.glyph_height();
+            self.x = 0.0;
+            self.base_x = 0.0;
+            self.base_advance = 0.0;
+            return None;
+        }
+
+        if is_combining_mark(ch) {
+            // zero-advance marks are designed to be drawn after their base,
+            // otherwise center them over it. either way, don't advance the pen.
+            // without a base, the mark is drawn where the pen is
+            let x = if advance == 0.0 || self.base_advance == 0.0 {
+                self.x
+            } else {
+                self.base_x + (self.base_advance - advance) / 2.0
+            };
+            return Some(Glyph {
+                id: gid.to_u32(),
+                x,
+                y: self.y,
+            });
+        }
+
+        let x = self.x;
+        self.x += advance;
+        self.base_x = x;
+        self.base_advance = advance;
+        Some(Glyph {
+            id: gid.to_u32(),
+            x,
+            y: self.y,
+        })
+    }
+}
+
+// the glyphs of `text`, each with the character it is drawn for. runs the font
+// has a ligature for, like `->`, become the ligature, drawn for the first
+// character of the run.
+fn ligature_shaping(
+    text: &str,
+    font_glyphs: &AppFontGlyphs,
+    ligatures: Option<&AppFontLigatures>,
+) -> Vec<(char, GlyphId)> {
+    let chars: Vec<char> = text.chars().collect();
+    let gids: Vec<GlyphId> = chars.iter().map(|ch| font_glyphs.glyph(*ch)).collect();
+
+    let Some(ligatures) = ligatures else {
+        return chars.into_iter().zip(gids).collect();
+    };
+
+    let mut shaped = Vec::with_capacity(chars.len());
+    let mut index = 0;
+    while index < chars.len() {
+        match ligatures.find(&gids[index..]) {
+            Some((ligature, len)) => {
+                shaped.push((chars[index], ligature));
+                index += len;
+            }
+            None => {
+                shaped.push((chars[index], gids[index]));
+                index += 1;
+            }
+        }
+    }
+    shaped
+}
+
+/// The font drawing the characters the monospace font has no glyph for.
+struct EmojiFallback<'a> {
+    glyphs: AppFontGlyphs<'a>,
+    metrics: AppFontMetrics<'a>,
+    // wide emoji take up two cells at most
+    max_advance: f32,
+}
+
+impl<'a> EmojiFallback<'a> {
+    fn new(
+        variations: &'a AppFontVariations,
+        font_size: f32,
+        font_glyphs: &AppFontGlyphs,
+        font_metrics: &AppFontMetrics,
+    ) -> Self {
+        Self {
+            glyphs: variations.glyphs(),
+            metrics: variations.metrics(font_size),
+            max_advance: 2.0 * font_metrics.glyph_width(font_glyphs.glyph(' ')),
+        }
+    }
+}
+
+// places the glyphs of `text` with `pen`, split into those drawn with the
+// font and those drawn with its emoji fallback
+fn place_glyphs(
+    text: &str,
+    font: &AppFont,
+    pen: &mut Pen,
+    font_glyphs: &AppFontGlyphs,
+    font_metrics: &AppFontMetrics,
+    emoji: Option<&EmojiFallback>,
+) -> (Vec<Glyph>, Vec<Glyph>) {
+    let mut glyphs = vec![];
+    let mut emoji_glyphs = vec![];
+
+    for (ch, gid) in ligature_shaping(text, font_glyphs, font.ligatures()) {
+        let emoji_gid = emoji.filter(|_| gid == GlyphId::NOTDEF).and_then(|emoji| {
+            let emoji_gid = emoji.glyphs.glyph(ch);
+            (emoji_gid != GlyphId::NOTDEF).then_some((emoji, emoji_gid))
+        });
+
+        match emoji_gid {
+            Some((emoji, emoji_gid)) => {
+                let advance = emoji.metrics.glyph_width(emoji_gid).min(emoji.max_advance);
+                emoji_glyphs.extend(pen.place_with_advance(ch, emoji_gid, advance, font_metrics));
+            }
+            None => glyphs.extend(pen.place(ch, gid, font_metrics)),
+        }
+    }
+
+    (glyphs, emoji_glyphs)
+}
+
 pub struct AppRenderer<'a>(&'a mut BaseAppRenderer);
 
 impl<'a> From<&'a mut BaseAppRenderer> for AppRenderer<'a> {
@@ -138,6 +478,22 @@ impl<'a> From<&'a mut BaseAppRenderer> for AppRenderer<'a> {
 }
 
 impl<'ar> AppRenderer<'ar> {
+    /// Applies `transform` to everything drawn until the matching
+    /// `pop_transform`, on top of any transform that is already pushed.
+    pub fn push_transform(&mut self, transform: Affine) {
+        let composed = self.0.current_transform() * transform;
+        self.0.transforms.push(composed);
+    }
+
+    pub fn pop_transform(&mut self) {
+        self.0.transforms.pop();
+    }
+
+    /// Sets the color behind everything drawn, which stays until it is set again.
+    pub fn set_background(&mut self, color: Color) {
+        self.0.background = color;
+    }
+
     pub fn draw_fill_rectangle(&mut self, options: DrawFillRectangleOptions) {
         let rect = Rect::new(
             options.pos.x,
@@ -146,59 +502,450 @@ impl<'ar> AppRenderer<'ar> {
             options.pos.y + options.size.h,
         );
 
-        self.0.scene.fill(
-            Fill::NonZero,
-            Affine::IDENTITY,
-            options.fill_color,
+        let transform = self.0.current_transform();
+
+        self.0
+            .scene
+            .fill(Fill::NonZero, transform, options.fill_color, None, &rect);
+    }
+
+    pub fn draw_rounded_rectangle(&mut self, options: DrawRoundedRectangleOptions) {
+        let rect = RoundedRect::new(
+            options.pos.x,
+            options.pos.y,
+            options.pos.x + options.size.w,
+            options.pos.y + options.size.h,
+            options.radius,
+        );
+
+        let transform = self.0.current_transform();
+
+        self.0
+            .scene
+            .fill(Fill::NonZero, transform, options.fill_color, None, &rect);
+
+        if let Some(border_color) = options.border_color {
+            self.0
+                .scene
+                .stroke(&Stroke::new(1.0), transform, border_color, None, &rect);
+        }
+    }
+
+    pub fn draw_cursor(&mut self, options: DrawCursorOptions) {
+        let DrawCursorOptions {
+            shape,
+            pos,
+            cell_size,
+            color,
+        } = options;
+
+        let (pos, size) = match shape {
+            CursorShape::Block => (pos, cell_size),
+            CursorShape::Bar { percent } => (
+                pos,
+                Size {
+                    w: cell_size.w * percent as f64 / 100.0,
+                    h: cell_size.h,
+                },
+            ),
+            CursorShape::Underline { percent } => {
+                let height = cell_size.h * percent as f64 / 100.0;
+                (
+                    Position {
+                        x: pos.x,
+                        y: pos.y + cell_size.h - height,
+                    },
+                    Size {
+                        w: cell_size.w,
+                        h: height,
+                    },
+                )
+            }
+        };
+
+        self.draw_fill_rectangle(DrawFillRectangleOptions {
+            pos,
+            size,
+            fill_color: color,
+        });
+    }
+
+    pub fn draw_underline(&mut self, options: DrawUnderlineOptions) {
+        let DrawUnderlineOptions {
+            pos,
+            width,
+            color,
+            style,
+        } = options;
+
+        match style {
+            UnderlineStyle::Straight => self.draw_fill_rectangle(DrawFillRectangleOptions {
+                pos,
+                size: Size { w: width, h: 1.0 },
+                fill_color: color,
+            }),
+            UnderlineStyle::Dotted => {
+                // a pixel on, a pixel off
+                let dots = (width / 2.0).ceil() as usize;
+                (0..dots).for_each(|dot| {
+                    self.draw_fill_rectangle(DrawFillRectangleOptions {
+                        pos: Position {
+                            x: pos.x + dot as f64 * 2.0,
+                            y: pos.y,
+                        },
+                        size: Size { w: 1.0, h: 1.0 },
+                        fill_color: color,
+                    })
+                });
+            }
+            UnderlineStyle::Wavy => {
+                // each half of a wave curves a pixel above or below the line
+                let half_wave = UNDERLINE_WAVE_LENGTH / 2.0;
+                let end_x = pos.x + width;
+
+                let mut path = BezPath::new();
+                path.move_to((pos.x, pos.y));
+                let mut x = pos.x;
+                let mut upwards = true;
+                while x < end_x {
+                    let next_x = (x + half_wave).min(end_x);
+                    let control_y = if upwards { pos.y - 2.0 } else { pos.y + 2.0 };
+                    path.quad_to(((x + next_x) / 2.0, control_y), (next_x, pos.y));
+                    x = next_x;
+                    upwards = !upwards;
+                }
+
+                let transform = self.0.current_transform();
+                self.0
+                    .scene
+                    .stroke(&Stroke::new(1.0), transform, color, None, &path);
+            }
+        }
+    }
+
+    pub fn draw_line(&mut self, options: DrawLineOptions) {
+        let line = Line::new(
+            (options.start.x, options.start.y),
+            (options.end.x, options.end.y),
+        );
+        let transform = self.0.current_transform();
+
+        self.0.scene.stroke(
+            &Stroke::new(options.width),
+            transform,
+            options.color,
             None,
-            &rect,
+            &line,
         );
     }
 
-    pub fn draw_text<'a, B, S, T>(&'a mut self, options: DrawTextOptions<'a, B, S, T>)
+    pub fn draw_text<'a, B, S, T>(&mut self, options: DrawTextOptions<'a, B, S, T>)
     where
         B: Into<BrushRef<'a>>,
         S: Into<StyleRef<'a>>,
         T: AsRef<str>,
     {
-        // TODO: Support customising font axes
-        let variations: &[(&str, f32)] = &[];
-
-        let variations = options.font.variations(variations);
+        let variations = options.font.variations(&[]);
 
         let font_glyphs = variations.glyphs();
         let font_metrics = variations.metrics(options.size);
 
-        let mut pen_x = 0f32;
-        let mut pen_y = 0f32;
+        let emoji_variations = options.font.emoji_variations();
+        let emoji = emoji_variations.as_ref().map(|emoji_variations| {
+            EmojiFallback::new(emoji_variations, options.size, &font_glyphs, &font_metrics)
+        });
+
+        let mut pen = Pen::default();
+        let (glyphs, emoji_glyphs) = place_glyphs(
+            options.text.as_ref(),
+            options.font,
+            &mut pen,
+            &font_glyphs,
+            &font_metrics,
+            emoji.as_ref(),
+        );
+
+        let transform = self.0.current_transform() * options.transform;
+        let brush: BrushRef = options.brush.into();
+        let style: StyleRef = options.style.into();
 
         self.0
             .scene
             .draw_glyphs(get_font(options.font))
             .font_size(options.size)
-            .transform(options.transform)
+            .normalized_coords(variations.normalized_coords())
+            .transform(transform)
             .glyph_transform(options.glyph_transform)
-            .brush(options.brush)
+            .brush(brush.clone())
             .hint(false)
-            .draw(
-                options.style,
-                options.text.as_ref().chars().filter_map(|ch| {
-                    if ch == '\n' {
-                        pen_y += font_metrics.glyph_height();
-                        pen_x = 0.0;
-                        return None;
-                    }
-
-                    let gid = font_glyphs.glyph(ch);
-                    let advance = font_metrics.glyph_width(gid);
-                    let x = pen_x;
-                    pen_x += advance;
-                    Some(Glyph {
-                        id: gid.to_u32(),
-                        x,
-                        y: pen_y,
-                    })
-                }),
+            .draw(style, glyphs.into_iter());
+
+        self.draw_emoji_glyphs(
+            options.font,
+            options.size,
+            transform,
+            options.glyph_transform,
+            brush,
+            emoji_glyphs,
+        );
+    }
+
+    // glyphs placed with the emoji fallback of `font`, which has no style of
+    // its own, so is drawn filled
+    fn draw_emoji_glyphs(
+        &mut self,
+        font: &AppFont,
+        size: f32,
+        transform: Affine,
+        glyph_transform: Option<Affine>,
+        brush: BrushRef,
+        glyphs: Vec<Glyph>,
+    ) {
+        let Some(emoji_font) = get_emoji_font(font) else {
+            return;
+        };
+        if glyphs.is_empty() {
+            return;
+        }
+
+        self.0
+            .scene
+            .draw_glyphs(emoji_font)
+            .font_size(size)
+            .transform(transform)
+            .glyph_transform(glyph_transform)
+            .brush(brush)
+            .hint(false)
+            .draw(Fill::NonZero, glyphs.into_iter());
+    }
+
+    pub fn draw_colored_runs(&mut self, options: DrawColoredRunsOptions) {
+        let variations = options.font.variations(&[]);
+
+        let font_glyphs = variations.glyphs();
+        let font_metrics = variations.metrics(options.size);
+
+        let emoji_variations = options.font.emoji_variations();
+        let emoji = emoji_variations.as_ref().map(|emoji_variations| {
+            EmojiFallback::new(emoji_variations, options.size, &font_glyphs, &font_metrics)
+        });
+
+        // variable fonts are made bold along their weight axis, others by
+        // stroking the outlines of their glyphs on top of filling them
+        let has_bold = options
+            .runs
+            .iter()
+            .any(|run| run.weight == FontWeight::Bold);
+        let bold_weight = has_bold
+            .then(|| {
+                options
+                    .font
+                    .axes()
+                    .into_iter()
+                    .find(|axis| axis.tag == WEIGHT_AXIS)
+                    .map(|axis| axis.max)
+            })
+            .flatten();
+        let bold_variations =
+            bold_weight.map(|weight| options.font.variations(&[(WEIGHT_AXIS, weight)]));
+
+        let mut pen = Pen::default();
+
+        let transform =
+            self.0.current_transform() * Affine::translate((options.origin.x, options.origin.y));
+
+        options.runs.iter().for_each(|run| {
+            // placed with the regular metrics, so that bold text stays on the
+            // grid of the rest
+            let (glyphs, emoji_glyphs) = place_glyphs(
+                &run.text,
+                options.font,
+                &mut pen,
+                &font_glyphs,
+                &font_metrics,
+                emoji.as_ref(),
+            );
+
+            let normalized_coords = match (&bold_variations, run.weight) {
+                (Some(bold_variations), FontWeight::Bold) => bold_variations.normalized_coords(),
+                _ => variations.normalized_coords(),
+            };
+            // glyphs are skewed in font units, where y points up
+            let glyph_transform =
+                (run.style == FontStyle::Italic).then(|| Affine::skew(ITALIC_SKEW, 0.0));
+
+            self.0
+                .scene
+                .draw_glyphs(get_font(options.font))
+                .font_size(options.size)
+                .normalized_coords(normalized_coords)
+                .transform(transform)
+                .glyph_transform(glyph_transform)
+                .brush(run.color)
+                .hint(false)
+                .draw(Fill::NonZero, glyphs.iter().copied());
+
+            if run.weight == FontWeight::Bold && bold_variations.is_none() {
+                self.0
+                    .scene
+                    .draw_glyphs(get_font(options.font))
+                    .font_size(options.size)
+                    .normalized_coords(normalized_coords)
+                    .transform(transform)
+                    .glyph_transform(glyph_transform)
+                    .brush(run.color)
+                    .hint(false)
+                    .draw(
+                        &Stroke::new((options.size * FAKE_BOLD_STROKE) as f64),
+                        glyphs.into_iter(),
+                    );
+            }
+
+            self.draw_emoji_glyphs(
+                options.font,
+                options.size,
+                transform,
+                glyph_transform,
+                run.color.into(),
+                emoji_glyphs,
             );
+        });
+    }
+}
+
+#[cfg(test)]
+impl BaseAppRenderer {
+    /// Number of paths drawn since the frame started.
+    pub fn path_count(&self) -> u32 {
+        self.scene.encoding().n_paths
+    }
+
+    /// Whether anything was drawn in `color` since the frame started, as long
+    /// as everything was drawn in solid colors.
+    pub fn has_drawn_color(&self, color: Color) -> bool {
+        self.scene
+            .encoding()
+            .draw_data
+            .chunks_exact(4)
+            .any(|rgba| u32::from_le_bytes(rgba.try_into().unwrap()) == color.to_premul_u32())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::test_font;
+
+    // the glyphs drawn since the frame started, as ids and positions
+    fn drawn_glyphs(base: &BaseAppRenderer) -> Vec<(u32, f32, f32)> {
+        base.scene
+            .encoding()
+            .resources
+            .glyphs
+            .iter()
+            .map(|glyph| (glyph.id, glyph.x, glyph.y))
+            .collect()
+    }
+
+    #[test]
+    fn pushed_transforms_compose_until_popped() {
+        let mut base = BaseAppRenderer::new();
+        let mut renderer = AppRenderer::from(&mut base);
+
+        renderer.push_transform(Affine::translate((10.0, 0.0)));
+        renderer.push_transform(Affine::translate((0.0, 5.0)));
+        assert_eq!(
+            renderer.0.current_transform(),
+            Affine::translate((10.0, 5.0))
+        );
+
+        renderer.pop_transform();
+        assert_eq!(
+            renderer.0.current_transform(),
+            Affine::translate((10.0, 0.0))
+        );
+        renderer.pop_transform();
+        assert_eq!(renderer.0.current_transform(), Affine::IDENTITY);
+    }
+
+    #[test]
+    fn new_frame_starts_without_transforms() {
+        let mut base = BaseAppRenderer::new();
+        AppRenderer::from(&mut base).push_transform(Affine::scale(2.0));
+
+        base.start_new_frame();
+
+        assert_eq!(base.current_transform(), Affine::IDENTITY);
+    }
+
+    #[test]
+    fn draw_line_strokes_under_the_current_transform() {
+        let mut base = BaseAppRenderer::new();
+        let mut renderer = AppRenderer::from(&mut base);
+
+        renderer.push_transform(Affine::translate((3.0, 4.0)));
+        renderer.draw_line(DrawLineOptions {
+            start: Position { x: 0.0, y: 0.0 },
+            end: Position { x: 0.0, y: 10.0 },
+            width: 1.0,
+            color: Color::WHITE,
+        });
+
+        let encoding = base.scene.encoding();
+        assert_eq!(encoding.n_paths, 1);
+        assert_eq!(encoding.transforms.last().unwrap().translation, [3.0, 4.0]);
+    }
+
+    #[test]
+    fn draw_colored_runs_advance_like_draw_text_of_the_whole_text() {
+        let font = test_font();
+        // the second run starts with a mark that combines with the first run
+        let run = |text: &str, color| TextRun {
+            text: text.to_string(),
+            color,
+            weight: FontWeight::Regular,
+            style: FontStyle::Normal,
+        };
+        let runs = [
+            run("let e", Color::WHITE),
+            run("\u{301} = ", Color::RED),
+            run("1;", Color::WHITE),
+        ];
+        let text: String = runs.iter().map(|run| run.text.as_str()).collect();
+
+        let mut text_base = BaseAppRenderer::new();
+        AppRenderer::from(&mut text_base).draw_text(DrawTextOptions {
+            font: &font,
+            size: 16.0,
+            transform: Affine::IDENTITY,
+            glyph_transform: None,
+            brush: Color::WHITE,
+            style: Fill::NonZero,
+            text: &text,
+            _marker: PhantomData,
+        });
+
+        let mut runs_base = BaseAppRenderer::new();
+        AppRenderer::from(&mut runs_base).draw_colored_runs(DrawColoredRunsOptions {
+            font: &font,
+            size: 16.0,
+            origin: Position { x: 0.0, y: 0.0 },
+            runs: &runs,
+        });
+
+        let text_glyphs = drawn_glyphs(&text_base);
+        assert_eq!(drawn_glyphs(&runs_base), text_glyphs);
+
+        // the pen ends where a single pen over the whole text ends
+        let variations = font.variations(&[]);
+        let (font_glyphs, font_metrics) = (variations.glyphs(), variations.metrics(16.0));
+        let mut pen = Pen::default();
+        text.chars().for_each(|ch| {
+            pen.place(ch, font_glyphs.glyph(ch), &font_metrics);
+        });
+        let (last_id, last_x, _) = *text_glyphs.last().unwrap();
+        let last_advance = font_metrics.glyph_width(font_glyphs.glyph(';'));
+        assert_eq!(font_glyphs.glyph(';').to_u32(), last_id);
+        assert_eq!(last_x + last_advance, pen.x);
     }
 }
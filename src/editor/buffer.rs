@@ -0,0 +1,1444 @@
+use std::{
+    fs,
+    io::Write,
+    mem,
+    ops::{Range, RangeInclusive},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::Sender,
+    },
+    thread,
+    time::SystemTime,
+};
+
+use anyhow::{anyhow, Result};
+use tempfile::NamedTempFile;
+
+use crate::base::{is_combining_mark, Position};
+
+use super::{
+    editorconfig::apply_editorconfig,
+    filetype::FileType,
+    history::{Edit, History, UndoStep},
+    indent::{detect_indent, IndentStyle},
+    marks::Marks,
+    options::{BufferOptions, LineEnding},
+    selection::{Selection, SelectionKind},
+    substitute::SubstituteJob,
+};
+
+/// Returns the leading spaces and tabs of `line`.
+pub fn leading_whitespace(line: &str) -> &str {
+    let end = line
+        .find(|ch: char| ch != ' ' && ch != '\t')
+        .unwrap_or(line.len());
+    &line[..end]
+}
+
+// `words` joined by spaces into lines starting with `indent`, which are at
+// most `width` characters unless a single word is longer
+fn wrap_words(words: &[&str], indent: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+
+    for word in words {
+        let line_len = line.chars().count();
+        if line_len > 0 && line_len + 1 + word.chars().count() > width {
+            lines.push(mem::take(&mut line));
+        }
+        if line.is_empty() {
+            line.push_str(indent);
+        } else {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+
+    lines
+}
+
+// converts a column (in chars) into a byte index of `line`, clamped to the line length
+fn byte_index(line: &str, col: usize) -> usize {
+    line.char_indices()
+        .nth(col)
+        .map_or(line.len(), |(index, _)| index)
+}
+
+// the columns of the grapheme (a character and the combining marks stacked on
+// it) that column `col` of `line` is part of
+fn grapheme_range(line: &str, col: usize) -> Option<Range<usize>> {
+    let chars: Vec<char> = line.chars().collect();
+    if col >= chars.len() {
+        return None;
+    }
+
+    let mut start = col;
+    while start > 0 && is_combining_mark(chars[start]) {
+        start -= 1;
+    }
+    let mut end = col + 1;
+    while end < chars.len() && is_combining_mark(chars[end]) {
+        end += 1;
+    }
+
+    Some(start..end)
+}
+
+/// Writes `content` to a temporary file next to `path` and then renames it
+/// over `path`, so that `path` never holds a partly written file.
+fn write_atomically(path: &Path, content: &[u8]) -> Result<()> {
+    // replace the file a symlink points to, not the symlink
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    let mut file = NamedTempFile::new_in(dir)?;
+    file.write_all(content)?;
+    file.as_file().sync_all()?;
+
+    // the temporary file is only readable by us, unlike the file it replaces
+    if let Ok(metadata) = fs::metadata(&path) {
+        fs::set_permissions(file.path(), metadata.permissions())?;
+    }
+
+    // renames with MoveFileEx on Windows, which replaces the existing file too
+    file.persist(&path)
+        .map_err(|error| anyhow!("Failed to replace {}: {}", path.display(), error.error))?;
+
+    Ok(())
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// How the file of a buffer has changed on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiskState {
+    Unchanged,
+    Modified,
+    Deleted,
+}
+
+/// Lines of a buffer replaced by others, for keeping what is known about each
+/// line in step with the buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineSplice {
+    pub start: usize,
+    /// the end of the replaced lines before and after the change
+    pub old_end: usize,
+    pub new_end: usize,
+}
+
+// every buffer gets its own id, even after others are closed
+static NEXT_BUFFER_ID: AtomicUsize = AtomicUsize::new(0);
+
+pub struct Buffer {
+    // identifies the buffer while it is open, as the path can change or be missing
+    id: usize,
+    // a buffer always has at least one line, even if it is empty
+    lines: Vec<String>,
+    // where the buffer is saved to, if it has been given a file
+    path: Option<PathBuf>,
+    // when the file was last modified on disk, as far as the buffer knows
+    disk_modified: Option<SystemTime>,
+    // the language of the buffer, like rust, which may be unknown
+    filetype: FileType,
+    options: BufferOptions,
+    history: History,
+    // the state of the history when the buffer was last saved or opened
+    saved_seq: usize,
+    marks: Marks,
+    // goes up with every change, but not with undo and redo
+    change_count: usize,
+    // the lines replaced since they were last taken, undo and redo included
+    splices: Vec<LineSplice>,
+}
+
+impl Buffer {
+    pub fn new() -> Self {
+        Self::from_lines(vec![String::new()])
+    }
+
+    fn from_lines(lines: Vec<String>) -> Self {
+        Self {
+            id: NEXT_BUFFER_ID.fetch_add(1, Ordering::Relaxed),
+            lines,
+            path: None,
+            disk_modified: None,
+            filetype: FileType::Unknown,
+            options: BufferOptions::default(),
+            history: History::new(),
+            saved_seq: 0,
+            marks: Marks::new(),
+            change_count: 0,
+            splices: vec![],
+        }
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let content = fs::read_to_string(&path)?;
+        let (content, bom) = match content.strip_prefix('\u{feff}') {
+            Some(content) => (content, true),
+            None => (content.as_str(), false),
+        };
+
+        let mut buffer: Self = content.into();
+        buffer.options.bom = bom;
+        // saving keeps the line endings the file already had
+        if content.contains("\r\n") {
+            buffer.options.line_ending = LineEnding::CrLf;
+        }
+        buffer.path = Some(path.as_ref().to_path_buf());
+        buffer.disk_modified = modified_time(path.as_ref());
+        buffer.filetype = FileType::detect(path.as_ref(), &buffer.lines[0]);
+        buffer.detect_indent();
+        // settings the project asks for win over what was detected
+        apply_editorconfig(path.as_ref(), &mut buffer.options);
+        Ok(buffer)
+    }
+
+    /// Opens the file of this buffer again, as a buffer with the same id.
+    pub fn reopen(&self) -> Result<Self> {
+        let path = self.path.as_ref().ok_or_else(|| anyhow!("No file name"))?;
+
+        let mut buffer = Self::open(path)?;
+        buffer.id = self.id;
+        Ok(buffer)
+    }
+
+    // follows the indentation style of the file, keeping the defaults if it is unclear
+    fn detect_indent(&mut self) {
+        // files without telling indentation get the usual one for their filetype
+        let style = detect_indent(&self.lines).or_else(|| self.filetype.indent_style());
+        match style {
+            Some(IndentStyle::Tabs) => self.options.expand_tab = false,
+            Some(IndentStyle::Spaces(width)) => {
+                self.options.expand_tab = true;
+                self.options.shift_width = width;
+            }
+            None => {}
+        }
+    }
+
+    /// Opens `path` on another thread, sending the buffer through `sender` once
+    /// it is loaded, so that large files don't block the caller.
+    pub fn load_async(path: String, sender: Sender<Result<Self>>) {
+        thread::spawn(move || {
+            // nobody is waiting for the buffer anymore if the receiver is gone
+            let _ = sender.send(Self::open(path));
+        });
+    }
+
+    pub fn save(&mut self) -> Result<()> {
+        let path = self.path.as_ref().ok_or_else(|| anyhow!("No file name"))?;
+
+        let content = self.encode(&self.lines);
+        if path.exists() {
+            if let Some(backup_path) = self.options.backup_path(path) {
+                if let Some(dir) = backup_path.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                fs::copy(path, &backup_path)
+                    .map_err(|error| anyhow!("Failed to write backup: {error}"))?;
+            }
+        }
+        write_atomically(path, content.as_bytes())?;
+        // our own write isn't a change on disk that has to be picked up
+        self.disk_modified = modified_time(path);
+        self.saved_seq = self.history.current_seq();
+
+        Ok(())
+    }
+
+    /// Writes `lines` of the buffer to the file at `path`, without making it
+    /// the file of the buffer.
+    pub fn write_lines(&self, lines: RangeInclusive<usize>, path: &Path) -> Result<()> {
+        let lines = self
+            .lines
+            .get(lines)
+            .ok_or_else(|| anyhow!("Invalid range"))?;
+        write_atomically(path, self.encode(lines).as_bytes())
+    }
+
+    // the file content for `lines`, following the options for line endings and BOM
+    fn encode(&self, lines: &[String]) -> String {
+        let line_ending = self.options.line_ending.as_str();
+        let mut content = if self.options.bom {
+            "\u{feff}".to_string()
+        } else {
+            String::new()
+        };
+        content.push_str(&lines.join(line_ending));
+        if self.options.insert_final_newline {
+            content.push_str(line_ending);
+        }
+        content
+    }
+
+    /// Inserts the lines of the file at `path` before `line`, returning how
+    /// many were inserted.
+    pub fn read_file(&mut self, line: usize, path: &Path) -> Result<usize> {
+        let content = fs::read_to_string(path)?;
+        let content = content.strip_prefix('\u{feff}').unwrap_or(&content);
+
+        let lines: Vec<String> = content.lines().map(String::from).collect();
+        let count = lines.len();
+        if count > 0 {
+            self.insert_lines_before(line, &lines);
+        }
+
+        Ok(count)
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Whether the buffer has changed since it was last saved. Undoing back
+    /// to the saved state makes it unmodified again.
+    pub fn is_modified(&self) -> bool {
+        self.history.current_seq() != self.saved_seq
+    }
+
+    /// Whether the file has been changed by something else since it was opened
+    /// or saved.
+    pub fn disk_state(&self) -> DiskState {
+        let Some(path) = &self.path else {
+            return DiskState::Unchanged;
+        };
+
+        match modified_time(path) {
+            None if path.exists() => DiskState::Unchanged,
+            None => DiskState::Deleted,
+            modified if modified == self.disk_modified => DiskState::Unchanged,
+            Some(_) => DiskState::Modified,
+        }
+    }
+
+    /// Treats the file on disk as if it had no changes, so that they are only
+    /// reported again if it changes after this.
+    pub fn ignore_disk_changes(&mut self) {
+        self.disk_modified = self.path.as_deref().and_then(modified_time);
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn line(&self, line: usize) -> Option<&str> {
+        self.lines.get(line).map(String::as_str)
+    }
+
+    pub fn line_len(&self, line: usize) -> usize {
+        self.line(line).map_or(0, |line| line.chars().count())
+    }
+
+    /// The grapheme that column `col` of `line` is part of, which is the
+    /// character there together with any combining marks on it.
+    pub fn grapheme_at(&self, line: usize, col: usize) -> Option<&str> {
+        let text = self.line(line)?;
+        let range = grapheme_range(text, col)?;
+        Some(&text[byte_index(text, range.start)..byte_index(text, range.end)])
+    }
+
+    /// The grapheme after the one at column `col` of `line`.
+    pub fn grapheme_after(&self, line: usize, col: usize) -> Option<&str> {
+        let range = grapheme_range(self.line(line)?, col)?;
+        self.grapheme_at(line, range.end)
+    }
+
+    /// The grapheme before the one at column `col` of `line`. `col` can be
+    /// the end of the line, for the last grapheme.
+    pub fn grapheme_before(&self, line: usize, col: usize) -> Option<&str> {
+        let text = self.line(line)?;
+        let start = grapheme_range(text, col).map_or(col, |range| range.start);
+        self.grapheme_at(line, start.min(text.chars().count()).checked_sub(1)?)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.len() == 1 && self.lines[0].is_empty()
+    }
+
+    pub fn filetype(&self) -> FileType {
+        self.filetype
+    }
+
+    /// What starts a line comment in the buffer's filetype, if it is known and
+    /// has line comments.
+    pub fn comment_prefix(&self) -> Option<&'static str> {
+        self.filetype.comment_prefix()
+    }
+
+    /// Overrides the detected filetype.
+    pub fn set_filetype(&mut self, filetype: FileType) {
+        self.filetype = filetype;
+    }
+
+    pub fn options(&self) -> &BufferOptions {
+        &self.options
+    }
+
+    pub fn options_mut(&mut self) -> &mut BufferOptions {
+        &mut self.options
+    }
+
+    pub fn marks(&self) -> &Marks {
+        &self.marks
+    }
+
+    pub fn marks_mut(&mut self) -> &mut Marks {
+        &mut self.marks
+    }
+
+    /// How many changes have been made to the buffer, not counting undo and
+    /// redo, for telling whether a command changed it.
+    pub fn change_count(&self) -> usize {
+        self.change_count
+    }
+
+    pub fn history_mut(&mut self) -> &mut History {
+        &mut self.history
+    }
+
+    /// Replaces the lines in `start..end` with `new_lines`, recording the change
+    /// in the undo history. `cursor` is where the cursor was before the change.
+    fn replace_lines(
+        &mut self,
+        start: usize,
+        end: usize,
+        new_lines: Vec<String>,
+        cursor: Position<usize>,
+    ) {
+        let old_lines = self
+            .lines
+            .splice(start..end, new_lines.iter().cloned())
+            .collect();
+
+        let new_end = start + new_lines.len();
+        self.marks.adjust(start, end, new_end);
+        self.splices.push(LineSplice {
+            start,
+            old_end: end,
+            new_end,
+        });
+        // '[ and '] are the first and last changed lines
+        let last_line = new_end
+            .saturating_sub(1)
+            .max(start)
+            .min(self.lines.len() - 1);
+        self.marks.set('[', Position { x: 0, y: start });
+        self.marks.set(
+            ']',
+            Position {
+                x: self.line_len(last_line).saturating_sub(1),
+                y: last_line,
+            },
+        );
+        // '. is where the change was made, which is where the cursor was
+        // unless the change is away from it, like lines put below
+        let last_change = if (start..=last_line).contains(&cursor.y) {
+            cursor
+        } else {
+            Position { x: 0, y: start }
+        };
+        self.marks.set('.', last_change);
+
+        self.change_count += 1;
+        self.history.record(
+            Edit {
+                line: start,
+                old_lines,
+                new_lines,
+            },
+            cursor,
+        );
+    }
+
+    /// Inserts `ch` before column `col`, returning the position after it.
+    pub fn insert_char(&mut self, line: usize, col: usize, ch: char) -> Position<usize> {
+        let mut text = self.lines[line].clone();
+        text.insert(byte_index(&text, col), ch);
+        self.replace_lines(line, line + 1, vec![text], Position { x: col, y: line });
+
+        Position {
+            x: col + 1,
+            y: line,
+        }
+    }
+
+    /// Overwrites the character at column `col` with `ch`, or appends `ch` if
+    /// `col` is the end of the line. Returns the character that was overwritten.
+    pub fn replace_char(&mut self, line: usize, col: usize, ch: char) -> Option<char> {
+        self.set_char(line, col, Some(ch))
+    }
+
+    /// Puts `old` back at column `col` after `replace_char` returned it,
+    /// removing the character there if nothing was overwritten.
+    pub fn restore_char(&mut self, line: usize, col: usize, old: Option<char>) {
+        self.set_char(line, col, old);
+    }
+
+    // replaces the character at `col` with `ch`, or with nothing if `ch` is None
+    fn set_char(&mut self, line: usize, col: usize, ch: Option<char>) -> Option<char> {
+        let mut text = self.lines[line].clone();
+        let start = byte_index(&text, col);
+        let old = text[start..].chars().next();
+        let end = start + old.map_or(0, char::len_utf8);
+        text.replace_range(start..end, ch.map(String::from).as_deref().unwrap_or(""));
+        self.replace_lines(line, line + 1, vec![text], Position { x: col, y: line });
+        old
+    }
+
+    /// Inserts `text` (which must not contain newlines) before column `col`,
+    /// returning the position after it.
+    pub fn insert_text(&mut self, line: usize, col: usize, text: &str) -> Position<usize> {
+        let mut new_line = self.lines[line].clone();
+        new_line.insert_str(byte_index(&new_line, col), text);
+        self.replace_lines(line, line + 1, vec![new_line], Position { x: col, y: line });
+
+        Position {
+            x: col + text.chars().count(),
+            y: line,
+        }
+    }
+
+    /// Inserts `text` before column `col`, where each newline in `text` starts a
+    /// new line. Returns the position of the last inserted character.
+    pub fn insert_multiline_text(
+        &mut self,
+        line: usize,
+        col: usize,
+        text: &str,
+    ) -> Position<usize> {
+        let current = &self.lines[line];
+        let (before, after) = current.split_at(byte_index(current, col));
+
+        let mut new_lines: Vec<String> = text.split('\n').map(str::to_string).collect();
+        let last = new_lines.len() - 1;
+        let end = Position {
+            x: new_lines[last].chars().count().saturating_sub(1) + if last == 0 { col } else { 0 },
+            y: line + last,
+        };
+        new_lines[0].insert_str(0, before);
+        new_lines[last].push_str(after);
+
+        self.replace_lines(line, line + 1, new_lines, Position { x: col, y: line });
+        end
+    }
+
+    /// Inserts `lines` as whole lines after line `after_line`.
+    pub fn insert_lines(&mut self, after_line: usize, lines: &[String]) {
+        self.insert_lines_before(after_line + 1, lines);
+    }
+
+    /// Inserts `lines` as whole lines before line `line`, which may be the
+    /// line count to insert them at the end.
+    pub fn insert_lines_before(&mut self, line: usize, lines: &[String]) {
+        self.replace_lines(line, line, lines.to_vec(), Position { x: 0, y: line });
+    }
+
+    /// The text covered by `selection`. A linewise selection ends with a newline.
+    pub fn selected_text(&self, selection: &Selection) -> String {
+        // a line of text for each line of a block
+        if selection.kind == SelectionKind::Block {
+            let cols = selection.block_cols();
+            return self.lines[selection.first_line()..=selection.last_line()]
+                .iter()
+                .map(|line| &line[byte_index(line, cols.start)..byte_index(line, cols.end)])
+                .collect::<Vec<_>>()
+                .join("\n");
+        }
+
+        let mut text = String::new();
+
+        for line in selection.first_line()..=selection.last_line() {
+            let content = &self.lines[line];
+            let cols = selection.cols(line, self.line_len(line)).unwrap_or(0..0);
+            text.push_str(&content[byte_index(content, cols.start)..byte_index(content, cols.end)]);
+
+            // the column after the last character stands for the line break
+            if cols.end > self.line_len(line) {
+                text.push('\n');
+            }
+        }
+
+        text
+    }
+
+    /// Removes the text covered by `selection`, returning the position where it
+    /// started.
+    pub fn delete_selection(&mut self, selection: &Selection) -> Position<usize> {
+        let first = selection.first_line();
+        let last = selection.last_line();
+        let cursor = selection.cursor;
+
+        match selection.kind {
+            SelectionKind::Line => {
+                let remaining = if last - first + 1 == self.lines.len() {
+                    vec![String::new()]
+                } else {
+                    vec![]
+                };
+                self.replace_lines(first, last + 1, remaining, cursor);
+
+                Position {
+                    x: 0,
+                    y: first.min(self.lines.len() - 1),
+                }
+            }
+            SelectionKind::Char => {
+                let (start, end) = selection.ordered();
+                // a selection that includes the line break of its last line joins
+                // the next line onto it
+                let joins_next = end.x >= self.line_len(end.y) && end.y + 1 < self.lines.len();
+                let last = if joins_next { end.y + 1 } else { end.y };
+
+                let first_text = &self.lines[start.y];
+                let mut text = first_text[..byte_index(first_text, start.x)].to_string();
+                if !joins_next {
+                    let last_text = &self.lines[end.y];
+                    text.push_str(&last_text[byte_index(last_text, end.x + 1)..]);
+                } else {
+                    text.push_str(&self.lines[last]);
+                }
+                self.replace_lines(start.y, last + 1, vec![text], cursor);
+
+                start
+            }
+            SelectionKind::Block => {
+                let cols = selection.block_cols();
+                self.delete_columns(first..last + 1, cols.start, cols.end, cursor);
+
+                selection.start()
+            }
+        }
+    }
+
+    /// Inserts `text` at column `col` of each of `lines`, padding lines too
+    /// short to reach it with spaces.
+    pub fn insert_at_columns(
+        &mut self,
+        lines: Range<usize>,
+        col: usize,
+        text: &str,
+        cursor: Position<usize>,
+    ) {
+        if lines.is_empty() || text.is_empty() {
+            return;
+        }
+
+        let new_lines: Vec<String> = self.lines[lines.clone()]
+            .iter()
+            .map(|line| {
+                let padding = " ".repeat(col.saturating_sub(line.chars().count()));
+                let index = byte_index(line, col);
+                format!("{}{padding}{text}{}", &line[..index], &line[index..])
+            })
+            .collect();
+
+        self.replace_lines(lines.start, lines.end, new_lines, cursor);
+    }
+
+    /// Removes the columns from `start_col` to `end_col` of each of `lines`,
+    /// or as many of them as a short line has.
+    pub fn delete_columns(
+        &mut self,
+        lines: Range<usize>,
+        start_col: usize,
+        end_col: usize,
+        cursor: Position<usize>,
+    ) {
+        let new_lines: Vec<String> = self.lines[lines.clone()]
+            .iter()
+            .map(|line| {
+                let start = byte_index(line, start_col);
+                let end = byte_index(line, end_col);
+                format!("{}{}", &line[..start], &line[end..])
+            })
+            .collect();
+
+        if new_lines[..] != self.lines[lines.clone()] {
+            self.replace_lines(lines.start, lines.end, new_lines, cursor);
+        }
+    }
+
+    /// Replaces `lines` with a single line holding just the indentation of the
+    /// first of them, for changing whole lines. Returns the position after the
+    /// indentation.
+    pub fn clear_lines(
+        &mut self,
+        lines: RangeInclusive<usize>,
+        cursor: Position<usize>,
+    ) -> Position<usize> {
+        let indent = leading_whitespace(&self.lines[*lines.start()]).to_string();
+        let end = Position {
+            x: indent.chars().count(),
+            y: *lines.start(),
+        };
+        self.replace_lines(*lines.start(), lines.end() + 1, vec![indent], cursor);
+        end
+    }
+
+    /// Indents `lines` one level further with the buffer's indent unit, like
+    /// >>. Blank lines are left alone.
+    pub fn indent_lines(&mut self, lines: RangeInclusive<usize>, cursor: Position<usize>) {
+        self.shift_lines(lines, false, cursor);
+    }
+
+    /// Indents `lines` one level less, like <<, removing a tab or up to
+    /// `shift_width` spaces from each. Blank lines are left alone.
+    pub fn dedent_lines(&mut self, lines: RangeInclusive<usize>, cursor: Position<usize>) {
+        self.shift_lines(lines, true, cursor);
+    }
+
+    // indents `lines` one level further, or one level less if `dedent` is set
+    fn shift_lines(&mut self, lines: RangeInclusive<usize>, dedent: bool, cursor: Position<usize>) {
+        let indent_unit = self.options.indent_unit();
+        let shift_width = self.options.shift_width;
+
+        let new_lines: Vec<String> = self.lines[lines.clone()]
+            .iter()
+            .map(|line| {
+                if line.trim().is_empty() {
+                    line.clone()
+                } else if !dedent {
+                    format!("{indent_unit}{line}")
+                } else if let Some(rest) = line.strip_prefix('\t') {
+                    rest.to_string()
+                } else {
+                    let spaces = line.len() - line.trim_start_matches(' ').len();
+                    line[spaces.min(shift_width)..].to_string()
+                }
+            })
+            .collect();
+
+        if new_lines[..] != self.lines[lines.clone()] {
+            self.replace_lines(*lines.start(), lines.end() + 1, new_lines, cursor);
+        }
+    }
+
+    /// Comments out `lines` with `prefix`, or uncomments them if they all are
+    /// commented out already. The prefix is put at the indent of the least
+    /// indented line, followed by a space. Blank lines are left alone.
+    pub fn toggle_comment(
+        &mut self,
+        lines: RangeInclusive<usize>,
+        prefix: &str,
+        cursor: Position<usize>,
+    ) {
+        let non_blank = || {
+            self.lines[lines.clone()]
+                .iter()
+                .filter(|line| !line.trim().is_empty())
+        };
+        let commented = non_blank().all(|line| line.trim_start().starts_with(prefix));
+        let indent = non_blank()
+            .map(|line| leading_whitespace(line).len())
+            .min()
+            .unwrap_or(0);
+
+        let new_lines: Vec<String> = self.lines[lines.clone()]
+            .iter()
+            .map(|line| {
+                if line.trim().is_empty() {
+                    line.clone()
+                } else if commented {
+                    let start = leading_whitespace(line).len();
+                    let rest = &line[start + prefix.len()..];
+                    let rest = rest.strip_prefix(' ').unwrap_or(rest);
+                    format!("{}{rest}", &line[..start])
+                } else {
+                    format!("{}{prefix} {}", &line[..indent], &line[indent..])
+                }
+            })
+            .collect();
+
+        if new_lines[..] != self.lines[lines.clone()] {
+            self.replace_lines(*lines.start(), lines.end() + 1, new_lines, cursor);
+        }
+    }
+
+    /// Rewraps each paragraph of `lines` (separated by blank lines) so that
+    /// its lines are at most `width` characters where possible, keeping the
+    /// indentation of its first line. Returns the last line of the result.
+    pub fn format_lines(
+        &mut self,
+        lines: RangeInclusive<usize>,
+        width: usize,
+        cursor: Position<usize>,
+    ) -> usize {
+        let mut new_lines: Vec<String> = vec![];
+        let mut paragraph: Vec<&str> = vec![];
+        let mut indent = "";
+
+        for line in &self.lines[lines.clone()] {
+            if line.trim().is_empty() {
+                new_lines.extend(wrap_words(&mem::take(&mut paragraph), indent, width));
+                new_lines.push(String::new());
+            } else {
+                if paragraph.is_empty() {
+                    indent = leading_whitespace(line);
+                }
+                paragraph.extend(line.split_whitespace());
+            }
+        }
+        new_lines.extend(wrap_words(&paragraph, indent, width));
+
+        let last_line = lines.start() + new_lines.len() - 1;
+        if new_lines[..] != self.lines[lines.clone()] {
+            self.replace_lines(*lines.start(), lines.end() + 1, new_lines, cursor);
+        }
+        last_line
+    }
+
+    /// Joins `lines` into the first of them, like J. Unless `with_spaces` is
+    /// off (like gJ), the indent of each joined line is taken away and a space
+    /// put before it, except after whitespace or before a `)`. Returns the
+    /// column where the last line was joined.
+    pub fn join_lines(
+        &mut self,
+        lines: RangeInclusive<usize>,
+        with_spaces: bool,
+        cursor: Position<usize>,
+    ) -> usize {
+        let mut joined = self.lines[*lines.start()].clone();
+        let mut join_col = 0;
+
+        for line in &self.lines[lines.start() + 1..=*lines.end()] {
+            let next = if with_spaces {
+                line.trim_start()
+            } else {
+                line.as_str()
+            };
+            join_col = joined.chars().count();
+            if with_spaces
+                && !joined.is_empty()
+                && !joined.ends_with(char::is_whitespace)
+                && !next.is_empty()
+                && !next.starts_with(')')
+            {
+                joined.push(' ');
+            }
+            joined.push_str(next);
+        }
+
+        self.replace_lines(*lines.start(), lines.end() + 1, vec![joined], cursor);
+        join_col
+    }
+
+    /// Splits the line at column `col`, carrying over the leading whitespace of
+    /// the current line onto the new line. If the text before `col` ends with one
+    /// of the indent triggers, the new line is indented one level further.
+    ///
+    /// Returns the position right after the indentation of the new line.
+    pub fn insert_newline_with_indent(&mut self, line: usize, col: usize) -> Position<usize> {
+        let text = &self.lines[line];
+        let (before, rest) = text.split_at(byte_index(text, col));
+
+        let mut indent = leading_whitespace(before).to_string();
+        if before
+            .trim_end()
+            .ends_with(|ch| self.options.indent_triggers.contains(&ch))
+        {
+            indent.push_str(&self.options.indent_unit());
+        }
+
+        let new_col = indent.chars().count();
+        indent.push_str(rest.trim_start_matches([' ', '\t']));
+        let new_lines = vec![before.to_string(), indent];
+        self.replace_lines(line, line + 1, new_lines, Position { x: col, y: line });
+
+        Position {
+            x: new_col,
+            y: line + 1,
+        }
+    }
+
+    /// Removes the character before column `col`, joining with the previous
+    /// line if `col` is at the start of the line. Returns the new position of
+    /// the removed character.
+    ///
+    /// With smart backspace, spaces before `col` that are all indentation are
+    /// removed up to the previous indentation level instead.
+    pub fn remove_char_before(&mut self, line: usize, col: usize) -> Position<usize> {
+        let cursor = Position { x: col, y: line };
+
+        if col > 0 {
+            let mut text = self.lines[line].clone();
+            let remove_count = self.backspace_len(line, col);
+
+            let start = byte_index(&text, col - remove_count);
+            text.replace_range(start..byte_index(&text, col), "");
+            self.replace_lines(line, line + 1, vec![text], cursor);
+
+            Position {
+                x: col - remove_count,
+                y: line,
+            }
+        } else if line > 0 {
+            let prev_len = self.line_len(line - 1);
+            let joined = format!("{}{}", self.lines[line - 1], self.lines[line]);
+            self.replace_lines(line - 1, line + 1, vec![joined], cursor);
+
+            Position {
+                x: prev_len,
+                y: line - 1,
+            }
+        } else {
+            cursor
+        }
+    }
+
+    // number of characters a backspace at `col` of `line` removes
+    fn backspace_len(&self, line: usize, col: usize) -> usize {
+        let text = &self.lines[line];
+        let before = &text[..byte_index(text, col)];
+        let shift_width = self.options.shift_width.max(1);
+
+        if self.options.smart_backspace && before.chars().all(|ch| ch == ' ') {
+            // back to the previous multiple of shift_width
+            (col - 1) % shift_width + 1
+        } else {
+            // along with the combining marks on the character
+            self.grapheme_before(line, col)
+                .map_or(1, |grapheme| grapheme.chars().count())
+        }
+    }
+
+    /// Strips leading and trailing blank lines, and collapses runs of blank
+    /// lines longer than `max_blank_lines` in between, as a single undo entry.
+    ///
+    /// Returns the position `cursor` moves to.
+    pub fn trim_blank_lines(&mut self, cursor: Position<usize>) -> Position<usize> {
+        let is_blank = |line: &String| line.trim().is_empty();
+
+        let first = self.lines.iter().position(|line| !is_blank(line));
+        let last = self.lines.iter().rposition(|line| !is_blank(line));
+
+        let mut new_lines = vec![];
+        let mut kept_before_cursor = 0;
+
+        if let (Some(first), Some(last)) = (first, last) {
+            let mut blank_run = 0;
+
+            for (index, line) in self.lines.iter().enumerate().take(last + 1).skip(first) {
+                if is_blank(line) {
+                    blank_run += 1;
+                    if blank_run > self.options.max_blank_lines {
+                        continue;
+                    }
+                } else {
+                    blank_run = 0;
+                }
+
+                if index < cursor.y {
+                    kept_before_cursor += 1;
+                }
+                new_lines.push(line.clone());
+            }
+        } else {
+            new_lines.push(String::new());
+        }
+
+        if new_lines == self.lines {
+            return cursor;
+        }
+
+        let line_count = new_lines.len();
+        self.replace_lines(0, self.lines.len(), new_lines, cursor);
+
+        Position {
+            x: cursor.x,
+            y: kept_before_cursor.min(line_count - 1),
+        }
+    }
+
+    /// Removes whitespace at the end of every line, returning the new position
+    /// of `cursor`.
+    pub fn trim_trailing_whitespace(&mut self, cursor: Position<usize>) -> Position<usize> {
+        let new_lines: Vec<String> = self
+            .lines
+            .iter()
+            .map(|line| line.trim_end_matches([' ', '\t']).to_string())
+            .collect();
+
+        if new_lines == self.lines {
+            return cursor;
+        }
+
+        self.replace_lines(0, self.lines.len(), new_lines, cursor);
+
+        Position {
+            x: cursor.x.min(self.line_len(cursor.y)),
+            y: cursor.y,
+        }
+    }
+
+    /// Applies the lines of a finished substitution `job` as a single undo
+    /// entry, returning how many matches it replaced.
+    pub fn substitute(&mut self, job: SubstituteJob, cursor: Position<usize>) -> usize {
+        let lines = job.lines();
+        let (new_lines, count) = job.into_result();
+
+        if count > 0 && !lines.is_empty() {
+            self.replace_lines(*lines.start(), lines.end() + 1, new_lines, cursor);
+        }
+
+        count
+    }
+
+    /// Starts grouping the following changes into a single undo entry, until
+    /// `commit` or `rollback`.
+    pub fn begin_transaction(&mut self) {
+        self.history.begin_group();
+    }
+
+    /// Ends the transaction, keeping its changes as a single undo entry.
+    pub fn commit(&mut self) {
+        self.history.end_group();
+    }
+
+    /// Ends the transaction by reverting its changes, which leaves no trace of
+    /// them in the undo history. Returns where the cursor was before them, if
+    /// there were any.
+    pub fn rollback(&mut self) -> Option<Position<usize>> {
+        let node = self.history.group_node();
+        self.history.end_group();
+        let node = node?;
+
+        let steps = self.history.undo();
+        let cursor = self.apply_undo_steps(&steps);
+        self.history.forget(node);
+        cursor
+    }
+
+    /// Reverts the latest change, returning where the cursor should go.
+    pub fn undo(&mut self) -> Option<Position<usize>> {
+        let steps = self.history.undo();
+        self.apply_undo_steps(&steps)
+    }
+
+    /// Reapplies the most recently undone change, returning where the cursor
+    /// should go.
+    pub fn redo(&mut self) -> Option<Position<usize>> {
+        let steps = self.history.redo();
+        self.apply_undo_steps(&steps)
+    }
+
+    /// Moves to the state made right before (or after, if `forward`) the
+    /// current one, even if it is on another branch of the undo tree.
+    pub fn undo_chronologically(&mut self, forward: bool) -> Option<Position<usize>> {
+        let steps = self.history.travel_chronologically(forward);
+        self.apply_undo_steps(&steps)
+    }
+
+    /// The lines replaced since this was last called, in the order they were
+    /// replaced in.
+    pub fn take_splices(&mut self) -> Vec<LineSplice> {
+        mem::take(&mut self.splices)
+    }
+
+    pub fn describe_history(&self) -> String {
+        self.history.describe()
+    }
+
+    fn apply_undo_steps(&mut self, steps: &[UndoStep]) -> Option<Position<usize>> {
+        steps.iter().fold(None, |_, step| {
+            let (node, revert) = match step {
+                UndoStep::Revert(node) => (*node, true),
+                UndoStep::Apply(node) => (*node, false),
+            };
+            let entry = self.history.entry(node);
+
+            if revert {
+                entry.edits.iter().rev().for_each(|edit| {
+                    let end = edit.line + edit.new_lines.len();
+                    self.lines
+                        .splice(edit.line..end, edit.old_lines.iter().cloned());
+                    self.marks
+                        .adjust(edit.line, end, edit.line + edit.old_lines.len());
+                    self.splices.push(LineSplice {
+                        start: edit.line,
+                        old_end: end,
+                        new_end: edit.line + edit.old_lines.len(),
+                    });
+                });
+            } else {
+                entry.edits.iter().for_each(|edit| {
+                    let end = edit.line + edit.old_lines.len();
+                    self.lines
+                        .splice(edit.line..end, edit.new_lines.iter().cloned());
+                    self.marks
+                        .adjust(edit.line, end, edit.line + edit.new_lines.len());
+                    self.splices.push(LineSplice {
+                        start: edit.line,
+                        old_end: end,
+                        new_end: edit.line + edit.new_lines.len(),
+                    });
+                });
+            }
+
+            Some(entry.cursor)
+        })
+    }
+}
+
+impl Default for Buffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<&str> for Buffer {
+    fn from(value: &str) -> Self {
+        let lines: Vec<String> = value.lines().map(String::from).collect();
+
+        if lines.is_empty() {
+            Self::new()
+        } else {
+            Self::from_lines(lines)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::options::BackupMode;
+    use super::*;
+
+    const ORIGIN: Position<usize> = Position { x: 0, y: 0 };
+
+    #[test]
+    fn trim_blank_lines_strips_the_ends_and_collapses_runs() {
+        let mut buffer = Buffer::from("\n\na\n\n\n\nb\n\n");
+
+        let cursor = buffer.trim_blank_lines(Position { x: 0, y: 6 });
+
+        assert_eq!(buffer.lines, ["a", "", "b"]);
+        // still on `b`
+        assert_eq!(cursor, Position { x: 0, y: 2 });
+    }
+
+    #[test]
+    fn trim_blank_lines_keeps_up_to_max_blank_lines() {
+        let mut buffer = Buffer::from("a\n\n\n\nb");
+        buffer.options.max_blank_lines = 2;
+
+        buffer.trim_blank_lines(ORIGIN);
+
+        assert_eq!(buffer.lines, ["a", "", "", "b"]);
+    }
+
+    #[test]
+    fn trim_blank_lines_leaves_one_empty_line_of_a_blank_buffer() {
+        let mut buffer = Buffer::from(" \n\t\n");
+
+        buffer.trim_blank_lines(Position { x: 0, y: 1 });
+
+        assert_eq!(buffer.lines, [""]);
+    }
+
+    #[test]
+    fn trim_blank_lines_records_nothing_without_blank_lines() {
+        let mut buffer = Buffer::from("a\nb");
+
+        buffer.trim_blank_lines(ORIGIN);
+
+        assert!(buffer.undo().is_none());
+    }
+
+    #[test]
+    fn backspace_in_indentation_removes_an_indent_level() {
+        let mut buffer = Buffer::from("      x");
+
+        assert_eq!(buffer.remove_char_before(0, 6), Position { x: 4, y: 0 });
+        assert_eq!(buffer.lines, ["    x"]);
+        assert_eq!(buffer.remove_char_before(0, 4), Position { x: 0, y: 0 });
+        assert_eq!(buffer.lines, ["x"]);
+    }
+
+    #[test]
+    fn backspace_after_text_removes_one_character() {
+        let mut buffer = Buffer::from("  a    b");
+
+        assert_eq!(buffer.remove_char_before(0, 7), Position { x: 6, y: 0 });
+        assert_eq!(buffer.lines, ["  a   b"]);
+    }
+
+    #[test]
+    fn backspace_without_smart_backspace_removes_one_space() {
+        let mut buffer = Buffer::from("        x");
+        buffer.options_mut().smart_backspace = false;
+
+        assert_eq!(buffer.remove_char_before(0, 8), Position { x: 7, y: 0 });
+        assert_eq!(buffer.lines, ["       x"]);
+    }
+
+    #[test]
+    fn auto_indent_uses_shiftwidth_and_expandtab() {
+        let mut buffer = Buffer::from("if x {");
+        buffer.options_mut().shift_width = 2;
+
+        assert_eq!(
+            buffer.insert_newline_with_indent(0, 6),
+            Position { x: 2, y: 1 }
+        );
+        assert_eq!(buffer.lines, ["if x {", "  "]);
+
+        buffer.options_mut().expand_tab = false;
+        buffer.insert_newline_with_indent(0, 6);
+        assert_eq!(buffer.lines[1], "\t");
+    }
+
+    #[test]
+    fn save_keeps_the_bom_and_line_endings_of_the_file() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        fs::write(file.path(), "\u{feff}a\r\nb\r\n").unwrap();
+
+        let mut buffer = Buffer::open(file.path()).unwrap();
+        assert_eq!(buffer.lines, ["a", "b"]);
+        buffer.save().unwrap();
+
+        assert_eq!(
+            fs::read_to_string(file.path()).unwrap(),
+            "\u{feff}a\r\nb\r\n"
+        );
+    }
+
+    #[test]
+    fn write_atomically_replaces_the_whole_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        fs::write(&path, "a much longer old content").unwrap();
+
+        write_atomically(&path, b"new").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        // no temporary file is left behind
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_atomically_keeps_permissions_and_symlinks() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("target.sh");
+        let link = dir.path().join("link.sh");
+        fs::write(&target, "old").unwrap();
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o751)).unwrap();
+        symlink(&target, &link).unwrap();
+
+        write_atomically(&link, b"new").unwrap();
+
+        assert!(fs::symlink_metadata(&link).unwrap().is_symlink());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new");
+        let mode = fs::metadata(&target).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o751);
+    }
+
+    #[test]
+    fn save_backs_up_the_file_it_replaces() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        fs::write(&path, "old\n").unwrap();
+        let mut buffer = Buffer::open(&path).unwrap();
+        buffer.options_mut().backup = BackupMode::Tilde;
+        buffer.insert_text(0, 0, "new ");
+
+        buffer.save().unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new old\n");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("file.txt~")).unwrap(),
+            "old\n"
+        );
+    }
+
+    #[test]
+    fn write_lines_writes_only_the_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("part.txt");
+        let mut buffer = Buffer::from("a\nb\nc");
+        buffer.options_mut().line_ending = LineEnding::CrLf;
+
+        buffer.write_lines(1..=2, &path).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "b\r\nc\r\n");
+        // the buffer is still not of that file
+        assert_eq!(buffer.path(), None);
+        assert!(buffer.write_lines(2..=3, &path).is_err());
+    }
+
+    #[test]
+    fn read_file_inserts_its_lines() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        fs::write(file.path(), "\u{feff}x\r\ny\n").unwrap();
+        let mut buffer = Buffer::from("a\nb");
+
+        assert_eq!(buffer.read_file(1, file.path()).unwrap(), 2);
+
+        assert_eq!(buffer.lines, ["a", "x", "y", "b"]);
+    }
+
+    #[test]
+    fn insert_lines_puts_lines_after_the_given_one() {
+        let mut buffer = Buffer::from("a\nb");
+        let lines = ["x".to_string(), "y".to_string()];
+
+        buffer.insert_lines(0, &lines);
+        assert_eq!(buffer.lines, ["a", "x", "y", "b"]);
+
+        // after the last line too
+        buffer.insert_lines(3, &lines[..1]);
+        assert_eq!(buffer.lines, ["a", "x", "y", "b", "x"]);
+
+        buffer.insert_lines_before(0, &lines[1..]);
+        assert_eq!(buffer.lines, ["y", "a", "x", "y", "b", "x"]);
+    }
+
+    #[test]
+    fn graphemes_include_their_combining_marks() {
+        // a, e with a combining acute, then o with two marks
+        let buffer = Buffer::from("ae\u{0301}o\u{0302}\u{0323}");
+
+        assert_eq!(buffer.grapheme_at(0, 0), Some("a"));
+        assert_eq!(buffer.grapheme_at(0, 1), Some("e\u{0301}"));
+        // a column inside a grapheme gives all of it
+        assert_eq!(buffer.grapheme_at(0, 2), Some("e\u{0301}"));
+        assert_eq!(buffer.grapheme_at(0, 6), None);
+
+        assert_eq!(buffer.grapheme_after(0, 0), Some("e\u{0301}"));
+        assert_eq!(buffer.grapheme_after(0, 2), Some("o\u{0302}\u{0323}"));
+        assert_eq!(buffer.grapheme_after(0, 3), None);
+    }
+
+    #[test]
+    fn grapheme_before_works_from_the_end_of_the_line() {
+        let buffer = Buffer::from("ae\u{0301}");
+
+        assert_eq!(buffer.grapheme_before(0, 3), Some("e\u{0301}"));
+        assert_eq!(buffer.grapheme_before(0, 2), Some("a"));
+        assert_eq!(buffer.grapheme_before(0, 0), None);
+    }
+
+    #[test]
+    fn backspace_removes_the_marks_with_their_character() {
+        let mut buffer = Buffer::from("xe\u{0301}");
+
+        assert_eq!(buffer.remove_char_before(0, 3), Position { x: 1, y: 0 });
+        assert_eq!(buffer.lines, ["x"]);
+    }
+
+    #[test]
+    fn changes_set_the_last_change_mark() {
+        let mut buffer = Buffer::from("a\nb\nc");
+        assert_eq!(buffer.marks().get('.'), None);
+
+        buffer.insert_text(1, 1, "x");
+        assert_eq!(buffer.marks().get('.'), Some(Position { x: 1, y: 1 }));
+
+        // lines put away from the cursor are marked at their start
+        buffer.insert_lines(2, &["d".to_string()]);
+        assert_eq!(buffer.marks().get('.'), Some(Position { x: 0, y: 3 }));
+    }
+
+    #[test]
+    fn commit_undoes_the_transaction_as_one() {
+        let mut buffer = Buffer::from("a");
+        buffer.insert_text(0, 1, "b");
+
+        buffer.begin_transaction();
+        buffer.insert_text(0, 2, "c");
+        buffer.insert_lines(0, &["d".to_string()]);
+        buffer.commit();
+
+        assert_eq!(buffer.lines, ["abc", "d"]);
+        buffer.undo();
+        assert_eq!(buffer.lines, ["ab"]);
+        buffer.redo();
+        assert_eq!(buffer.lines, ["abc", "d"]);
+    }
+
+    #[test]
+    fn rollback_leaves_no_trace() {
+        let mut buffer = Buffer::from("a");
+        buffer.insert_text(0, 1, "b");
+        let history = buffer.describe_history();
+
+        buffer.begin_transaction();
+        buffer.insert_text(0, 2, "c");
+        buffer.insert_lines(0, &["d".to_string()]);
+        assert_eq!(buffer.rollback(), Some(Position { x: 2, y: 0 }));
+
+        assert_eq!(buffer.lines, ["ab"]);
+        assert_eq!(buffer.describe_history(), history);
+        // the change before the transaction is still there to undo
+        buffer.undo();
+        assert_eq!(buffer.lines, ["a"]);
+    }
+
+    #[test]
+    fn rollback_without_changes_does_nothing() {
+        let mut buffer = Buffer::from("a");
+        buffer.insert_text(0, 1, "b");
+
+        buffer.begin_transaction();
+        assert_eq!(buffer.rollback(), None);
+
+        assert_eq!(buffer.lines, ["ab"]);
+    }
+
+    #[test]
+    fn join_lines_puts_spaces_between_trimmed_lines() {
+        let mut buffer = Buffer::from("fn main(\n    a,\n    b\n)");
+
+        let join_col = buffer.join_lines(0..=3, true, ORIGIN);
+
+        assert_eq!(buffer.lines, ["fn main( a, b)"]);
+        // where the last line, `)`, was joined
+        assert_eq!(join_col, 13);
+    }
+
+    #[test]
+    fn join_lines_without_spaces_keeps_indent() {
+        let mut buffer = Buffer::from("a\n  b\nc");
+
+        buffer.join_lines(0..=1, false, ORIGIN);
+
+        assert_eq!(buffer.lines, ["a  b", "c"]);
+    }
+
+    #[test]
+    fn join_lines_adds_no_space_after_whitespace_or_empty_lines() {
+        let mut buffer = Buffer::from("a \n\n  b");
+
+        buffer.join_lines(0..=2, true, ORIGIN);
+
+        assert_eq!(buffer.lines, ["a b"]);
+    }
+
+    #[test]
+    fn join_lines_is_one_undo_step() {
+        let mut buffer = Buffer::from("a\nb");
+
+        buffer.join_lines(0..=1, true, ORIGIN);
+        buffer.undo();
+
+        assert_eq!(buffer.lines, ["a", "b"]);
+    }
+}
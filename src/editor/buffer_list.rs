@@ -0,0 +1,43 @@
+use std::path::Path;
+
+use super::Buffer;
+
+/// The buffers that are open but not shown in the view.
+pub struct BufferList {
+    buffers: Vec<Buffer>,
+}
+
+impl BufferList {
+    pub fn new() -> Self {
+        Self { buffers: vec![] }
+    }
+
+    pub fn push(&mut self, buffer: Buffer) {
+        self.buffers.push(buffer);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Buffer> {
+        self.buffers.iter()
+    }
+
+    /// Removes the buffer with `id` from the list, to be shown.
+    pub fn take(&mut self, id: usize) -> Option<Buffer> {
+        let index = self.buffers.iter().position(|buffer| buffer.id() == id)?;
+        Some(self.buffers.remove(index))
+    }
+
+    /// Removes the buffer of the file at `path` from the list, to be shown.
+    pub fn take_by_path(&mut self, path: &Path) -> Option<Buffer> {
+        let index = self
+            .buffers
+            .iter()
+            .position(|buffer| buffer.path() == Some(path))?;
+        Some(self.buffers.remove(index))
+    }
+}
+
+impl Default for BufferList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,523 @@
+use std::{ops::RangeInclusive, path::Path};
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::base::{AppFont, FontStyle, FontWeight, UnderlineStyle};
+
+use super::{
+    buffer_list::BufferList,
+    cursor_shape::CursorShapes,
+    filetype::FileType,
+    line_map::WrapMode,
+    location::FileLocation,
+    options::BackupMode,
+    registers::Registers,
+    selection::Selection,
+    substitute::{Substitute, SubstituteScope},
+    Buffer, DiskState, Theme, View,
+};
+
+/// The lines a command applies to, given before the command name.
+enum CommandRange {
+    Lines(RangeInclusive<usize>),
+    /// `'<,'>`, the last visual selection
+    Selection(Selection),
+}
+
+impl CommandRange {
+    fn lines(&self) -> RangeInclusive<usize> {
+        match self {
+            CommandRange::Lines(lines) => lines.clone(),
+            CommandRange::Selection(selection) => selection.first_line()..=selection.last_line(),
+        }
+    }
+}
+
+/// Executes an ex-style command (the text typed after `:`), returning any
+/// output it has to show.
+pub fn execute_command(
+    view: &mut View,
+    buffers: &mut BufferList,
+    registers: &mut Registers,
+    theme: &mut Theme,
+    font: &mut AppFont,
+    command: &str,
+) -> Result<Option<String>> {
+    let (range, command) = parse_range(view, command.trim())?;
+
+    let name_len = command
+        .find(|ch: char| !ch.is_ascii_alphabetic())
+        .unwrap_or(command.len());
+    let (name, args) = command.split_at(name_len);
+
+    let takes_range = matches!(name, "" | "s" | "substitute" | "w" | "write" | "r" | "read");
+    if range.is_some() && !takes_range {
+        bail!("No range allowed: {name}");
+    }
+
+    let result = match name {
+        // a range on its own goes to its last line
+        "" => {
+            if let Some(range) = range {
+                view.go_to_line(*range.lines().end());
+            }
+            Ok(())
+        }
+        "undolist" => return Ok(Some(view.buffer().describe_history())),
+        "reg" | "registers" | "di" | "display" => {
+            registers.sync_clipboard();
+            let filter: String = args.chars().filter(|ch| !ch.is_whitespace()).collect();
+            return Ok(Some(registers.describe(&filter)));
+        }
+        "s" | "substitute" => substitute(view, range, args),
+        "w" | "write" => write(view, range, args.trim()),
+        "r" | "read" => read(view, range, args.trim()),
+        "e" | "edit" => edit(view, buffers, args.trim()),
+        "TrimBlankLines" => {
+            view.trim_blank_lines();
+            Ok(())
+        }
+        "undoclear" => {
+            view.buffer_mut().history_mut().clear();
+            Ok(())
+        }
+        "set" => return set_option(view, font, args.trim()),
+        "colo" | "colorscheme" => return colorscheme(theme, args.trim()),
+        "fontaxes" => return Ok(Some(describe_font_axes(font))),
+        _ => bail!("Not an editor command: {name}"),
+    };
+
+    result.map(|()| None)
+}
+
+// switches to the built-in theme `name`, or the one in the themes directory
+// with that name, or shows the current one's name
+fn colorscheme(theme: &mut Theme, name: &str) -> Result<Option<String>> {
+    if name.is_empty() {
+        return Ok(Some(theme.name.clone()));
+    }
+
+    *theme = Theme::find(name)?;
+    Ok(None)
+}
+
+// the axes the font can be varied along with `:set fontvariation`
+fn describe_font_axes(font: &AppFont) -> String {
+    let axes = font.axes();
+    if axes.is_empty() {
+        return "The font has no variation axes".to_string();
+    }
+
+    let mut lines = vec!["--- Font axes ---".to_string()];
+    lines.extend(axes.iter().map(|axis| {
+        format!(
+            "{}   {} to {}, default {}",
+            axis.tag, axis.min, axis.max, axis.default
+        )
+    }));
+    lines.join("\n")
+}
+
+fn parse_range<'a>(view: &View, command: &'a str) -> Result<(Option<CommandRange>, &'a str)> {
+    if let Some(rest) = command.strip_prefix('%') {
+        let last_line = view.buffer().line_count() - 1;
+        return Ok((Some(CommandRange::Lines(0..=last_line)), rest));
+    }
+
+    if let Some(rest) = command.strip_prefix("'<,'>") {
+        let selection = view
+            .last_selection()
+            .ok_or_else(|| anyhow!("No previous selection"))?;
+        return Ok((Some(CommandRange::Selection(selection)), rest));
+    }
+
+    let (Some(start), rest) = parse_address(view, command)? else {
+        return Ok((None, command));
+    };
+
+    let Some(rest) = rest.strip_prefix(',') else {
+        return Ok((Some(CommandRange::Lines(start..=start)), rest));
+    };
+
+    match parse_address(view, rest)? {
+        (Some(end), rest) if start <= end => Ok((Some(CommandRange::Lines(start..=end)), rest)),
+        (Some(_), _) => bail!("Backwards range given"),
+        (None, _) => bail!("Expected a line after ','"),
+    }
+}
+
+// a single line number (counted from 1), `.` for the cursor line or `$` for the last line
+fn parse_address<'a>(view: &View, command: &'a str) -> Result<(Option<usize>, &'a str)> {
+    if let Some(rest) = command.strip_prefix('.') {
+        return Ok((Some(view.cursor().y), rest));
+    }
+
+    if let Some(rest) = command.strip_prefix('$') {
+        return Ok((Some(view.buffer().line_count() - 1), rest));
+    }
+
+    let digits_len = command
+        .find(|ch: char| !ch.is_ascii_digit())
+        .unwrap_or(command.len());
+    if digits_len == 0 {
+        return Ok((None, command));
+    }
+
+    let (digits, rest) = command.split_at(digits_len);
+    let line = digits.parse::<usize>()?.saturating_sub(1);
+
+    Ok((Some(line), rest))
+}
+
+fn substitute(view: &mut View, range: Option<CommandRange>, args: &str) -> Result<()> {
+    let substitute = Substitute::parse(args)?;
+
+    let cursor_line = view.cursor().y;
+    let lines = range.map_or(cursor_line..=cursor_line, |range| range.lines());
+    let scope = SubstituteScope { lines, cols: None };
+
+    view.substitute(substitute, scope)
+}
+
+// `:w` saves the buffer, `:w file` writes all of it or only the range to another file.
+// `:w!` saves over changes made to the file by something else
+fn write(view: &mut View, range: Option<CommandRange>, arg: &str) -> Result<()> {
+    let (force, arg) = match arg.strip_prefix('!') {
+        Some(arg) => (true, arg.trim()),
+        None => (false, arg),
+    };
+
+    if arg.is_empty() {
+        if range.is_some() {
+            bail!("No file name to write the range to");
+        }
+        // a change made while the buffer wasn't shown may not have been
+        // noticed yet, and saving would silently throw it away
+        if !force && view.buffer().disk_state() == DiskState::Modified {
+            bail!("File changed on disk since reading it (add ! to override)");
+        }
+        return view.save();
+    }
+
+    let last_line = view.buffer().line_count() - 1;
+    let lines = range.map_or(0..=last_line, |range| range.lines());
+    view.buffer().write_lines(lines, Path::new(arg))
+}
+
+// inserts a file below the cursor line, or below the line of the range
+fn read(view: &mut View, range: Option<CommandRange>, arg: &str) -> Result<()> {
+    if arg.is_empty() {
+        bail!("No file name");
+    }
+
+    let line = range.map_or(view.cursor().y, |range| *range.lines().end());
+    view.read_file(line, Path::new(arg))
+}
+
+// opens a file in place of the current buffer, like `:e src/main.rs:10`
+fn edit(view: &mut View, buffers: &mut BufferList, arg: &str) -> Result<()> {
+    if arg.is_empty() {
+        bail!("No file name");
+    }
+
+    let location = FileLocation::parse(arg);
+    let path = Path::new(&location.path);
+
+    if view.buffer().path() == Some(path) {
+        view.reload()?;
+    } else {
+        let buffer = match buffers.take_by_path(path) {
+            Some(buffer) => buffer,
+            None => Buffer::open(path)?,
+        };
+
+        let previous = view.set_buffer(buffer);
+        // an empty buffer without a file has nothing worth going back to
+        if previous.path().is_some() || !previous.is_empty() {
+            buffers.push(previous);
+        }
+    }
+
+    match &location.jump {
+        Some(jump) => view.jump_to(jump),
+        None => Ok(()),
+    }
+}
+
+// `option?` shows the value of an option instead of setting it
+fn set_option(view: &mut View, font: &mut AppFont, arg: &str) -> Result<Option<String>> {
+    if let Some(option) = arg.strip_suffix('?') {
+        return query_option(view, font, option).map(Some);
+    }
+
+    // `fontvariation wght=600` sets one axis, keeping the others
+    if let Some(setting) = arg.strip_prefix("fontvariation ") {
+        return set_font_variation(font, setting.trim()).map(|()| None);
+    }
+
+    let Some((option, value)) = arg.split_once('=') else {
+        return set_flag(view, font, arg).map(|()| None);
+    };
+
+    match option {
+        "undolevels" | "ul" => view
+            .buffer_mut()
+            .history_mut()
+            .set_undo_levels(value.parse()?),
+        // in KiB, like vim's maxmem
+        "undomemory" => view
+            .buffer_mut()
+            .history_mut()
+            .set_max_memory(value.parse::<usize>()? * 1024),
+        "tabstop" | "ts" => {
+            view.buffer_mut().options_mut().tab_width = value.parse::<usize>()?.max(1)
+        }
+        "shiftwidth" | "sw" => {
+            view.buffer_mut().options_mut().shift_width = value.parse::<usize>()?.max(1)
+        }
+        "maxblanklines" => view.buffer_mut().options_mut().max_blank_lines = value.parse()?,
+        "textwidth" | "tw" => {
+            view.buffer_mut().options_mut().text_width = value.parse::<usize>()?.max(1)
+        }
+        "filetype" | "ft" => {
+            let filetype =
+                FileType::from_name(value).ok_or_else(|| anyhow!("Unknown filetype: {value}"))?;
+            view.buffer_mut().set_filetype(filetype)
+        }
+        "guicursor" | "gcr" => view.options_mut().cursor_shapes = CursorShapes::parse(value)?,
+        "cursorblink" => view.options_mut().cursor_blink_ms = value.parse()?,
+        "showbreak" | "sbr" => view.options_mut().show_break = unescape_spaces(value),
+        "conceallevel" | "cole" => view.options_mut().conceal_level = value.parse::<u8>()?.min(3),
+        "scrolloff" | "so" => view.options_mut().scroll_off = value.parse()?,
+        "colorcolumn" | "cc" => {
+            view.options_mut().color_columns = value
+                .split(',')
+                .filter(|column| !column.is_empty())
+                .map(str::parse)
+                .collect::<Result<_, _>>()?
+        }
+        "sidescrolloff" | "siso" => view.options_mut().side_scroll_off = value.parse()?,
+        "virtualedit" | "ve" => {
+            view.options_mut().virtual_edit = match value {
+                "all" => true,
+                "" | "none" => false,
+                _ => bail!("Unsupported virtualedit: {value}"),
+            }
+        }
+        "linkunderline" => {
+            view.options_mut().link_underline = match value {
+                "straight" => Some(UnderlineStyle::Straight),
+                "wavy" => Some(UnderlineStyle::Wavy),
+                "dotted" => Some(UnderlineStyle::Dotted),
+                "" | "none" => None,
+                _ => bail!("Unsupported linkunderline: {value}"),
+            }
+        }
+        "backupdir" | "bdir" => {
+            view.buffer_mut().options_mut().backup = BackupMode::Directory(value.into())
+        }
+        "guifont" | "gfn" => *font = load_font(font, value)?,
+        _ => bail!("Unknown option: {option}"),
+    }
+
+    Ok(None)
+}
+
+fn set_font_variation(font: &mut AppFont, setting: &str) -> Result<()> {
+    let Some((tag, value)) = setting.split_once('=') else {
+        bail!("Expected an axis and a value, like wght=600");
+    };
+    // axis tags are always four characters, like `wght` or `opsz`
+    if tag.len() != 4 || !tag.chars().all(|ch| ch.is_ascii_graphic()) {
+        bail!("Invalid font axis: {tag}");
+    }
+
+    font.set_variation(tag, value.parse()?);
+    Ok(())
+}
+
+// a family name followed by `:b` for bold and `:i` for italic, like
+// `guifont=JetBrains\ Mono:b`. the settings of the current font carry over.
+fn load_font(current: &AppFont, value: &str) -> Result<AppFont> {
+    let mut parts = value.split(':');
+    let family = unescape_spaces(parts.next().unwrap_or_default());
+    if family.is_empty() {
+        bail!("No font family");
+    }
+
+    let mut weight = FontWeight::Regular;
+    let mut style = FontStyle::Normal;
+    for part in parts {
+        match part {
+            "b" => weight = FontWeight::Bold,
+            "i" => style = FontStyle::Italic,
+            _ => bail!("Unsupported guifont option: {part}"),
+        }
+    }
+
+    let mut font = AppFont::from_system_name(&family, weight, style)?;
+    font.inherit_settings(current);
+    Ok(font)
+}
+
+// spaces in option values are escaped with a backslash, like `showbreak=>\ `.
+// the command is trimmed, which leaves a trailing escaped space as just the backslash
+fn unescape_spaces(value: &str) -> String {
+    let value = value.replace("\\ ", " ");
+    match value.strip_suffix('\\') {
+        Some(value) => format!("{value} "),
+        None => value,
+    }
+}
+
+fn query_option(view: &View, font: &AppFont, option: &str) -> Result<String> {
+    let options = view.buffer().options();
+
+    let value = match option {
+        "tabstop" | "ts" => options.tab_width.to_string(),
+        "shiftwidth" | "sw" => options.shift_width.to_string(),
+        "textwidth" | "tw" => options.text_width.to_string(),
+        "expandtab" | "et" => {
+            let prefix = if options.expand_tab { "" } else { "no" };
+            return Ok(format!("{prefix}expandtab"));
+        }
+        "filetype" | "ft" => view.buffer().filetype().name().to_string(),
+        "colorcolumn" | "cc" => view
+            .options()
+            .color_columns
+            .iter()
+            .map(usize::to_string)
+            .collect::<Vec<_>>()
+            .join(","),
+        "guifont" | "gfn" => font.family().unwrap_or_default().to_string(),
+        "fontvariation" => {
+            let mut settings: Vec<String> = font
+                .variation_settings()
+                .iter()
+                .map(|(tag, value)| format!("{tag}={value}"))
+                .collect();
+            settings.sort_unstable();
+            settings.join(",")
+        }
+        _ => bail!("Unknown option: {option}"),
+    };
+
+    Ok(format!("{option}={value}"))
+}
+
+// boolean options are turned off by prefixing them with "no"
+fn set_flag(view: &mut View, font: &mut AppFont, arg: &str) -> Result<()> {
+    let (option, enabled) = match arg.strip_prefix("no") {
+        Some(option) => (option, false),
+        None => (arg, true),
+    };
+
+    match option {
+        "trimblanklines" => view.buffer_mut().options_mut().trim_blank_lines_on_save = enabled,
+        "expandtab" | "et" => view.buffer_mut().options_mut().expand_tab = enabled,
+        "backup" | "bk" => {
+            view.buffer_mut().options_mut().backup = if enabled {
+                BackupMode::Tilde
+            } else {
+                BackupMode::None
+            }
+        }
+        "smartbackspace" => view.buffer_mut().options_mut().smart_backspace = enabled,
+        "incsearch" | "is" => view.options_mut().incsearch = enabled,
+        "wrap" => {
+            view.options_mut().wrap = if enabled {
+                WrapMode::SoftWrap
+            } else {
+                WrapMode::Off
+            }
+        }
+        "breakindent" | "bri" => view.options_mut().break_indent = enabled,
+        "number" | "nu" => view.options_mut().number = enabled,
+        "debuggrid" => view.options_mut().debug_grid = enabled,
+        "diminactive" => view.options_mut().dim_inactive = enabled,
+        "scrollbar" => view.options_mut().scrollbar = enabled,
+        "showtabline" | "stal" => view.options_mut().show_tab_line = enabled,
+        "scrollpastend" => view.options_mut().scroll_past_end = enabled,
+        "smoothscroll" | "sms" => view.options_mut().smooth_scroll = enabled,
+        "ligatures" => font.set_ligatures_enabled(enabled),
+        _ => bail!("Unknown option: {arg}"),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, time::SystemTime};
+
+    use super::*;
+    use crate::editor::{Buffer, SelectionKind};
+
+    // the lines of the range at the start of `command`, and the rest of it
+    fn range_of(command: &str) -> Result<(Option<RangeInclusive<usize>>, String)> {
+        let mut view = View::new(Buffer::from("a\nb\nc\nd"));
+        view.move_cursor_down();
+
+        let (range, rest) = parse_range(&view, command)?;
+        Ok((range.map(|range| range.lines()), rest.to_string()))
+    }
+
+    #[test]
+    fn parse_range_reads_line_addresses() {
+        assert_eq!(range_of("s/a/b/").unwrap(), (None, "s/a/b/".to_string()));
+        assert_eq!(range_of("%s").unwrap(), (Some(0..=3), "s".to_string()));
+        assert_eq!(range_of("2,3s").unwrap(), (Some(1..=2), "s".to_string()));
+        assert_eq!(range_of(".,$s").unwrap(), (Some(1..=3), "s".to_string()));
+        assert_eq!(range_of("3").unwrap(), (Some(2..=2), String::new()));
+    }
+
+    #[test]
+    fn parse_range_rejects_bad_ranges() {
+        assert!(range_of("3,2s").is_err());
+        assert!(range_of("2,s").is_err());
+        // nothing has been selected yet
+        assert!(range_of("'<,'>s").is_err());
+    }
+
+    #[test]
+    fn parse_range_uses_the_last_selection() {
+        let mut view = View::new(Buffer::from("a\nb\nc\nd"));
+        view.move_cursor_down();
+        view.enter_visual_mode(SelectionKind::Line);
+        view.move_cursor_down();
+        view.enter_command_mode();
+
+        let (range, rest) = parse_range(&view, "'<,'>s").unwrap();
+
+        assert_eq!(range.map(|range| range.lines()), Some(1..=2));
+        assert_eq!(rest, "s");
+    }
+
+    #[test]
+    fn unescape_spaces_keeps_escaped_spaces() {
+        assert_eq!(unescape_spaces(">\\ "), "> ");
+        // a trailing escaped space is left as the backslash by trimming
+        assert_eq!(unescape_spaces("+\\"), "+ ");
+        assert_eq!(unescape_spaces("a\\ b"), "a b");
+        assert_eq!(unescape_spaces("..."), "...");
+    }
+
+    #[test]
+    fn write_refuses_to_save_over_changes_on_disk_without_a_bang() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        fs::write(&path, "old\n").unwrap();
+        let mut view = View::new(Buffer::open(&path).unwrap());
+        view.buffer_mut().insert_text(0, 0, "new ");
+
+        fs::write(&path, "theirs\n").unwrap();
+        let file = fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::UNIX_EPOCH).unwrap();
+
+        assert!(write(&mut view, None, "").is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "theirs\n");
+
+        write(&mut view, None, "!").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new old\n");
+    }
+}
@@ -0,0 +1,246 @@
+use std::ops::Range;
+
+use super::{
+    filetype::FileType,
+    width::{char_width, char_width_at},
+};
+
+// markers around markdown text, which are concealed in pairs
+const MARKDOWN_MARKERS: &[&str] = &["**", "__", "`"];
+
+// TeX commands and the symbols they are drawn as
+const TEX_SYMBOLS: &[(&str, char)] = &[
+    ("alpha", 'α'),
+    ("beta", 'β'),
+    ("gamma", 'γ'),
+    ("delta", 'δ'),
+    ("epsilon", 'ε'),
+    ("theta", 'θ'),
+    ("lambda", 'λ'),
+    ("mu", 'μ'),
+    ("pi", 'π'),
+    ("sigma", 'σ'),
+    ("omega", 'ω'),
+    ("infty", '∞'),
+    ("leq", '≤'),
+    ("geq", '≥'),
+    ("neq", '≠'),
+    ("to", '→'),
+    ("times", '×'),
+];
+
+/// Part of a line that is drawn differently from its text, while the text
+/// itself stays the same.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conceal {
+    /// columns (in chars) of the line
+    pub cols: Range<usize>,
+    /// drawn in place of the text, if anything
+    pub replacement: Option<char>,
+}
+
+impl Conceal {
+    // what is drawn in place of the text at `level`, like vim's conceallevel:
+    // 1 draws a space if there is no replacement, 2 only draws replacements
+    // and 3 draws nothing at all
+    fn shown_as(&self, level: u8) -> Option<char> {
+        match level {
+            1 => Some(self.replacement.unwrap_or(' ')),
+            2 => self.replacement,
+            _ => None,
+        }
+    }
+}
+
+/// The parts of `line` that are concealed for its filetype, in order.
+pub fn find_conceals(filetype: FileType, line: &str) -> Vec<Conceal> {
+    match filetype {
+        FileType::Markdown => markdown_conceals(line),
+        FileType::Tex => tex_conceals(line),
+        _ => vec![],
+    }
+}
+
+fn markdown_conceals(line: &str) -> Vec<Conceal> {
+    // fences of code blocks are shown as they are
+    if line.trim_start().starts_with("```") {
+        return vec![];
+    }
+
+    let mut conceals: Vec<Conceal> = vec![];
+
+    MARKDOWN_MARKERS.iter().for_each(|marker| {
+        let len = marker.chars().count();
+        let found: Vec<Range<usize>> = line
+            .match_indices(marker)
+            .map(|(index, _)| {
+                let start = line[..index].chars().count();
+                start..start + len
+            })
+            // `**` is not also two `*`, and so on
+            .filter(|cols| {
+                conceals
+                    .iter()
+                    .all(|conceal| conceal.cols.end <= cols.start || cols.end <= conceal.cols.start)
+            })
+            .collect();
+
+        // a marker without its other half is just text
+        found.chunks_exact(2).flatten().for_each(|cols| {
+            conceals.push(Conceal {
+                cols: cols.clone(),
+                replacement: None,
+            })
+        });
+    });
+
+    conceals.sort_by_key(|conceal| conceal.cols.start);
+    conceals
+}
+
+fn tex_conceals(line: &str) -> Vec<Conceal> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut conceals = vec![];
+
+    let mut col = 0;
+    while col < chars.len() {
+        if chars[col] != '\\' {
+            col += 1;
+            continue;
+        }
+
+        let name_len = chars[col + 1..]
+            .iter()
+            .take_while(|ch| ch.is_ascii_alphabetic())
+            .count();
+        let name: String = chars[col + 1..col + 1 + name_len].iter().collect();
+
+        if let Some((_, symbol)) = TEX_SYMBOLS.iter().find(|(command, _)| *command == name) {
+            conceals.push(Conceal {
+                cols: col..col + 1 + name_len,
+                replacement: Some(*symbol),
+            });
+        }
+        col += 1 + name_len;
+    }
+
+    conceals
+}
+
+/// The cells each character of `line` takes up, with `conceals` drawn at
+/// `level`. A concealed part takes up the cells of what is drawn in its place,
+/// which are all counted for its first character.
+pub fn cell_widths(line: &str, tab_width: usize, conceals: &[Conceal], level: u8) -> Vec<usize> {
+    let mut display_col = 0;
+
+    line.chars()
+        .enumerate()
+        .map(|(col, ch)| {
+            let width = match conceals.iter().find(|conceal| conceal.cols.contains(&col)) {
+                Some(conceal) if conceal.cols.start == col => {
+                    conceal.shown_as(level).map_or(0, char_width)
+                }
+                Some(_) => 0,
+                None => char_width_at(ch, display_col, tab_width),
+            };
+            display_col += width;
+            width
+        })
+        .collect()
+}
+
+/// The text drawn for columns `cols` of `line`, where `widths` are the cells
+/// of its characters from `cell_widths`. Tabs are drawn as spaces, and
+/// `conceals` as what is drawn in their place.
+pub fn display_text(
+    line: &str,
+    cols: Range<usize>,
+    widths: &[usize],
+    conceals: &[Conceal],
+    level: u8,
+) -> String {
+    let mut text = String::new();
+
+    line.chars()
+        .enumerate()
+        .take(cols.end)
+        .skip(cols.start)
+        .for_each(
+            |(col, ch)| match conceals.iter().find(|conceal| conceal.cols.contains(&col)) {
+                Some(conceal) if conceal.cols.start == col => {
+                    text.extend(conceal.shown_as(level));
+                }
+                Some(_) => {}
+                None if ch == '\t' => text.push_str(&" ".repeat(widths[col])),
+                None => text.push(ch),
+            },
+        );
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hidden(cols: Range<usize>) -> Conceal {
+        Conceal {
+            cols,
+            replacement: None,
+        }
+    }
+
+    // the line as drawn at `level`
+    fn drawn(filetype: FileType, line: &str, level: u8) -> String {
+        let conceals = find_conceals(filetype, line);
+        let widths = cell_widths(line, 8, &conceals, level);
+        display_text(line, 0..line.chars().count(), &widths, &conceals, level)
+    }
+
+    #[test]
+    fn markdown_markers_are_concealed_in_pairs() {
+        assert_eq!(
+            find_conceals(FileType::Markdown, "**a** `b` *c"),
+            [hidden(0..2), hidden(3..5), hidden(6..7), hidden(8..9)]
+        );
+        assert_eq!(find_conceals(FileType::Markdown, "a ** b"), []);
+        assert_eq!(find_conceals(FileType::Markdown, "```rust"), []);
+        assert_eq!(find_conceals(FileType::Rust, "**a**"), []);
+    }
+
+    #[test]
+    fn tex_commands_are_drawn_as_symbols() {
+        assert_eq!(
+            find_conceals(FileType::Tex, r"\alpha \to \foo"),
+            [
+                Conceal {
+                    cols: 0..6,
+                    replacement: Some('α'),
+                },
+                Conceal {
+                    cols: 7..10,
+                    replacement: Some('→'),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn conceal_level_decides_what_is_drawn() {
+        assert_eq!(drawn(FileType::Markdown, "**a**", 1), " a ");
+        assert_eq!(drawn(FileType::Markdown, "**a**", 2), "a");
+        assert_eq!(drawn(FileType::Tex, r"\pi r", 2), "π r");
+        assert_eq!(drawn(FileType::Tex, r"\pi r", 3), " r");
+    }
+
+    #[test]
+    fn concealed_text_takes_the_cells_of_its_replacement() {
+        let line = "\\pi\tx";
+        let conceals = find_conceals(FileType::Tex, line);
+
+        assert_eq!(cell_widths(line, 8, &conceals, 2), [1, 0, 0, 7, 1]);
+        assert_eq!(cell_widths(line, 8, &conceals, 3), [0, 0, 0, 8, 1]);
+        // without conceals, the tab is after three cells
+        assert_eq!(cell_widths(line, 8, &[], 2), [1, 1, 1, 5, 1]);
+    }
+}
@@ -0,0 +1,143 @@
+use std::marker::PhantomData;
+
+use vello::{
+    kurbo::Affine,
+    peniko::{Brush, Fill},
+};
+use winit::keyboard::{Key, NamedKey};
+
+use crate::base::{
+    AppFont, AppRenderer, DrawRoundedRectangleOptions, DrawTextOptions, Position, Size,
+};
+
+use super::{width::char_width, Theme};
+
+const HINT: &str = "[Y]es / [N]o";
+// in cells, between the edge of the dialog and its text
+const PADDING: f64 = 1.0;
+// in pixels
+const CORNER_RADIUS: f64 = 6.0;
+
+// called with what the answer acts on and whether it was yes
+type OnAnswer<T> = Box<dyn FnOnce(&mut T, bool)>;
+
+/// A yes/no question drawn over everything else, which takes every key until
+/// it is answered. The answer is passed to a callback, together with the `T`
+/// it acts on.
+pub struct Confirm<T> {
+    message: String,
+    on_answer: OnAnswer<T>,
+}
+
+impl<T> Confirm<T> {
+    pub fn new(message: impl Into<String>, on_answer: impl FnOnce(&mut T, bool) + 'static) -> Self {
+        Self {
+            message: message.into(),
+            on_answer: Box::new(on_answer),
+        }
+    }
+
+    /// The answer given by `key`, which is yes for Y and no for N or Escape.
+    /// Any other key leaves the question open.
+    pub fn answer(&self, key: &Key) -> Option<bool> {
+        match key {
+            Key::Character(text) if text.eq_ignore_ascii_case("y") => Some(true),
+            Key::Character(text) if text.eq_ignore_ascii_case("n") => Some(false),
+            Key::Named(NamedKey::Escape) => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Passes `answer` to the callback, which is the end of the question.
+    pub fn finish(self, target: &mut T, answer: bool) {
+        (self.on_answer)(target, answer);
+    }
+
+    /// Draws the dialog in the middle of a screen `screen_size` pixels big.
+    pub fn render(
+        &self,
+        renderer: &mut AppRenderer,
+        font: &AppFont,
+        font_size: f32,
+        cell_size: Size<f64>,
+        screen_size: Size<f64>,
+        theme: &Theme,
+    ) {
+        let lines = [self.message.as_str(), "", HINT];
+        let text_width = lines
+            .iter()
+            .map(|line| line.chars().map(char_width).sum::<usize>())
+            .max()
+            .unwrap_or(0);
+
+        let size = Size {
+            w: (text_width as f64 + PADDING * 2.0) * cell_size.w,
+            h: (lines.len() as f64 + PADDING * 2.0) * cell_size.h,
+        };
+        let pos = Position {
+            x: ((screen_size.w - size.w) / 2.0).max(0.0),
+            y: ((screen_size.h - size.h) / 2.0).max(0.0),
+        };
+
+        renderer.draw_rounded_rectangle(DrawRoundedRectangleOptions {
+            pos,
+            size,
+            radius: CORNER_RADIUS,
+            fill_color: theme.dialog_bg,
+            border_color: Some(theme.dialog_border),
+        });
+
+        renderer.draw_text(DrawTextOptions::<&Brush, _, _> {
+            font,
+            size: font_size,
+            transform: Affine::translate((
+                pos.x + PADDING * cell_size.w,
+                pos.y + PADDING * cell_size.h,
+            )),
+            glyph_transform: None,
+            brush: &Brush::Solid(theme.foreground),
+            style: Fill::NonZero,
+            text: lines.join("\n"),
+            _marker: PhantomData,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn confirm() -> Confirm<Vec<bool>> {
+        Confirm::new("Reload?", |answers: &mut Vec<bool>, answer| {
+            answers.push(answer)
+        })
+    }
+
+    #[test]
+    fn answer_takes_y_and_n_in_any_case() {
+        let confirm = confirm();
+
+        assert_eq!(confirm.answer(&Key::Character("y".into())), Some(true));
+        assert_eq!(confirm.answer(&Key::Character("Y".into())), Some(true));
+        assert_eq!(confirm.answer(&Key::Character("N".into())), Some(false));
+        assert_eq!(confirm.answer(&Key::Named(NamedKey::Escape)), Some(false));
+    }
+
+    #[test]
+    fn other_keys_leave_the_question_open() {
+        let confirm = confirm();
+
+        assert_eq!(confirm.answer(&Key::Character("x".into())), None);
+        assert_eq!(confirm.answer(&Key::Named(NamedKey::Enter)), None);
+    }
+
+    #[test]
+    fn finish_passes_the_answer_to_the_callback() {
+        let mut answers = vec![];
+
+        confirm().finish(&mut answers, true);
+        confirm().finish(&mut answers, false);
+
+        assert_eq!(answers, [true, false]);
+    }
+}
@@ -0,0 +1,100 @@
+use anyhow::{bail, Result};
+
+use crate::base::CursorShape;
+
+use super::Mode;
+
+impl CursorShape {
+    // written as block, ver<percent> or hor<percent>, like vim's guicursor
+    fn parse(text: &str) -> Result<Self> {
+        let parse_percent = |percent: &str| match percent.parse::<u8>() {
+            Ok(percent @ 1..=100) => Ok(percent),
+            _ => bail!("Invalid cursor size: {text}"),
+        };
+
+        if text == "block" {
+            Ok(CursorShape::Block)
+        } else if let Some(percent) = text.strip_prefix("ver") {
+            Ok(CursorShape::Bar {
+                percent: parse_percent(percent)?,
+            })
+        } else if let Some(percent) = text.strip_prefix("hor") {
+            Ok(CursorShape::Underline {
+                percent: parse_percent(percent)?,
+            })
+        } else {
+            bail!("Unknown cursor shape: {text}")
+        }
+    }
+}
+
+/// The cursor shape for each mode, set like vim's guicursor with a list of
+/// `modes:shape` parts such as `n-v-c:block,i:ver25,r:hor20`. The modes are
+/// `n` (normal), `v` (visual), `i` (insert), `r` (replace), `c` (command
+/// line and search) and `a` (all of them).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CursorShapes {
+    normal: CursorShape,
+    visual: CursorShape,
+    insert: CursorShape,
+    replace: CursorShape,
+    command: CursorShape,
+}
+
+const DEFAULT_CURSOR_SHAPES: &str = "n-v-c:block,i:ver25,r:hor20";
+
+impl CursorShapes {
+    pub fn parse(setting: &str) -> Result<Self> {
+        // modes that aren't mentioned keep a block cursor
+        let mut shapes = Self {
+            normal: CursorShape::Block,
+            visual: CursorShape::Block,
+            insert: CursorShape::Block,
+            replace: CursorShape::Block,
+            command: CursorShape::Block,
+        };
+
+        for part in setting.split(',').filter(|part| !part.is_empty()) {
+            let Some((modes, shape)) = part.split_once(':') else {
+                bail!("Expected modes:shape, got {part}");
+            };
+            let shape = CursorShape::parse(shape)?;
+
+            for mode in modes.split('-') {
+                match mode {
+                    "n" => shapes.normal = shape,
+                    "v" => shapes.visual = shape,
+                    "i" => shapes.insert = shape,
+                    "r" => shapes.replace = shape,
+                    "c" => shapes.command = shape,
+                    "a" => {
+                        shapes.normal = shape;
+                        shapes.visual = shape;
+                        shapes.insert = shape;
+                        shapes.replace = shape;
+                        shapes.command = shape;
+                    }
+                    _ => bail!("Unknown mode: {mode}"),
+                }
+            }
+        }
+
+        Ok(shapes)
+    }
+
+    pub fn shape_for(&self, mode: Mode) -> CursorShape {
+        match mode {
+            Mode::Normal | Mode::OperatorPending(_) => self.normal,
+            Mode::Visual | Mode::VisualLine | Mode::VisualBlock => self.visual,
+            Mode::Insert => self.insert,
+            Mode::Replace => self.replace,
+            Mode::Command | Mode::Search => self.command,
+        }
+    }
+}
+
+impl Default for CursorShapes {
+    fn default() -> Self {
+        Self::parse(DEFAULT_CURSOR_SHAPES).expect("default cursor shapes are valid")
+    }
+}
@@ -0,0 +1,301 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use super::options::{BufferOptions, LineEnding};
+
+const FILE_NAME: &str = ".editorconfig";
+
+/// The properties of an `.editorconfig` file that apply to files matching a
+/// glob pattern.
+#[derive(Debug, Default)]
+struct Section {
+    pattern: String,
+    properties: Vec<(String, String)>,
+}
+
+/// A parsed `.editorconfig` file.
+#[derive(Debug, Default)]
+struct EditorConfig {
+    /// whether files in parent directories are ignored
+    root: bool,
+    sections: Vec<Section>,
+}
+
+impl EditorConfig {
+    fn parse(content: &str) -> Self {
+        let mut config = Self::default();
+
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(['#', ';']) {
+                continue;
+            }
+
+            if let Some(pattern) = line
+                .strip_prefix('[')
+                .and_then(|rest| rest.strip_suffix(']'))
+            {
+                config.sections.push(Section {
+                    pattern: pattern.to_string(),
+                    properties: vec![],
+                });
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once(['=', ':']) else {
+                continue;
+            };
+            // keys and values are case insensitive
+            let key = key.trim().to_lowercase();
+            let value = value.trim().to_lowercase();
+
+            match config.sections.last_mut() {
+                Some(section) => section.properties.push((key, value)),
+                None if key == "root" => config.root = value == "true",
+                None => {}
+            }
+        }
+
+        config
+    }
+
+    /// The properties for the file at `path`, relative to the directory of
+    /// this config, in the order they are to be applied.
+    fn properties_for<'a>(&'a self, path: &'a str) -> impl Iterator<Item = &'a (String, String)> {
+        self.sections
+            .iter()
+            .filter(|section| section_matches(&section.pattern, path))
+            .flat_map(|section| &section.properties)
+    }
+}
+
+// patterns without a / match the file name in any directory below the config
+fn section_matches(pattern: &str, path: &str) -> bool {
+    let pattern = match pattern.strip_prefix('/') {
+        Some(pattern) => pattern.to_string(),
+        None if !pattern.contains('/') => format!("**/{pattern}"),
+        None => pattern.to_string(),
+    };
+
+    expand_braces(&pattern)
+        .iter()
+        .any(|pattern| glob_matches(&pattern.chars().collect::<Vec<_>>(), path))
+}
+
+/// Expands `{a,b}` alternatives in `pattern` into one pattern for each.
+fn expand_braces(pattern: &str) -> Vec<String> {
+    let Some(open) = pattern.find('{') else {
+        return vec![pattern.to_string()];
+    };
+    let Some(close) = pattern[open..].find('}').map(|close| open + close) else {
+        return vec![pattern.to_string()];
+    };
+
+    let (before, after) = (&pattern[..open], &pattern[close + 1..]);
+    pattern[open + 1..close]
+        .split(',')
+        .flat_map(|alternative| expand_braces(&format!("{before}{alternative}{after}")))
+        .collect()
+}
+
+/// Matches `path` against a glob pattern, where `*` matches anything but `/`,
+/// `**` matches anything, `?` matches one character and `[...]` matches one of
+/// the characters in it (or none of them when starting with `!`).
+fn glob_matches(pattern: &[char], path: &str) -> bool {
+    let Some((&first, rest)) = pattern.split_first() else {
+        return path.is_empty();
+    };
+
+    match first {
+        '*' if rest.first() == Some(&'*') => {
+            let rest = &rest[1..];
+            // **/ also matches no directories at all
+            if let Some(after_slash) = rest.strip_prefix(&['/']) {
+                if glob_matches(after_slash, path) {
+                    return true;
+                }
+            }
+            path.char_indices()
+                .map(|(index, _)| index)
+                .chain([path.len()])
+                .any(|index| glob_matches(rest, &path[index..]))
+        }
+        '*' => path
+            .char_indices()
+            .take_while(|&(_, ch)| ch != '/')
+            .map(|(index, _)| index)
+            .chain([path.find('/').unwrap_or(path.len())])
+            .any(|index| glob_matches(rest, &path[index..])),
+        '?' => {
+            let mut chars = path.chars();
+            matches!(chars.next(), Some(ch) if ch != '/') && glob_matches(rest, chars.as_str())
+        }
+        '[' => {
+            let Some(close) = rest.iter().position(|&ch| ch == ']') else {
+                return literal_matches('[', rest, path);
+            };
+            let (negated, set) = match rest[..close].split_first() {
+                Some(('!', set)) => (true, set),
+                _ => (false, &rest[..close]),
+            };
+
+            let mut chars = path.chars();
+            match chars.next() {
+                Some(ch) if ch != '/' && set.contains(&ch) != negated => {
+                    glob_matches(&rest[close + 1..], chars.as_str())
+                }
+                _ => false,
+            }
+        }
+        _ => literal_matches(first, rest, path),
+    }
+}
+
+fn literal_matches(ch: char, rest: &[char], path: &str) -> bool {
+    path.strip_prefix(ch)
+        .is_some_and(|path| glob_matches(rest, path))
+}
+
+/// Applies the `.editorconfig` files in the directories above `path` to
+/// `options`, where files closer to `path` take precedence.
+pub fn apply_editorconfig(path: &Path, options: &mut BufferOptions) {
+    let Ok(path) = fs::canonicalize(path) else {
+        return;
+    };
+
+    // from the closest config outwards, up to the first root config
+    let mut configs: Vec<(PathBuf, EditorConfig)> = vec![];
+    for dir in path.ancestors().skip(1) {
+        let Ok(content) = fs::read_to_string(dir.join(FILE_NAME)) else {
+            continue;
+        };
+
+        let config = EditorConfig::parse(&content);
+        let is_root = config.root;
+        configs.push((dir.to_path_buf(), config));
+        if is_root {
+            break;
+        }
+    }
+
+    for (dir, config) in configs.iter().rev() {
+        let Some(relative_path) = path.strip_prefix(dir).ok().and_then(|path| path.to_str()) else {
+            continue;
+        };
+        // globs are written with /, whatever the platform
+        let relative_path = relative_path.replace('\\', "/");
+
+        for (key, value) in config.properties_for(&relative_path) {
+            apply_property(key, value, options);
+        }
+    }
+}
+
+// unknown properties and values are ignored, as other editors may support more
+fn apply_property(key: &str, value: &str, options: &mut BufferOptions) {
+    match (key, value) {
+        ("indent_style", "space") => options.expand_tab = true,
+        ("indent_style", "tab") => options.expand_tab = false,
+        ("indent_size", "tab") => options.shift_width = options.tab_width,
+        ("indent_size", size) => {
+            if let Ok(size) = size.parse::<usize>() {
+                options.shift_width = size.max(1);
+            }
+        }
+        ("tab_width", width) => {
+            if let Ok(width) = width.parse::<usize>() {
+                options.tab_width = width.max(1);
+            }
+        }
+        ("end_of_line", "lf") => options.line_ending = LineEnding::Lf,
+        ("end_of_line", "crlf") => options.line_ending = LineEnding::CrLf,
+        ("end_of_line", "cr") => options.line_ending = LineEnding::Cr,
+        // only UTF-8 can be read and written, with or without a byte order mark
+        ("charset", "utf-8") => options.bom = false,
+        ("charset", "utf-8-bom") => options.bom = true,
+        ("trim_trailing_whitespace", value) => {
+            options.trim_trailing_whitespace_on_save = value == "true"
+        }
+        ("insert_final_newline", value) => options.insert_final_newline = value == "true",
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, path: &str) -> bool {
+        section_matches(pattern, path)
+    }
+
+    #[test]
+    fn parse_reads_root_and_sections() {
+        let config = EditorConfig::parse(
+            "# comment\nroot = true\n\n[*.rs]\nIndent_Style = Space\n; another\n[Makefile]\nindent_style: tab\n",
+        );
+
+        assert!(config.root);
+        assert_eq!(config.sections.len(), 2);
+        assert_eq!(config.sections[0].pattern, "*.rs");
+        assert_eq!(
+            config.sections[0].properties,
+            [("indent_style".to_string(), "space".to_string())]
+        );
+        assert_eq!(
+            config.sections[1].properties,
+            [("indent_style".to_string(), "tab".to_string())]
+        );
+    }
+
+    #[test]
+    fn patterns_without_a_slash_match_in_any_directory() {
+        assert!(matches("*.rs", "main.rs"));
+        assert!(matches("*.rs", "src/editor/main.rs"));
+        assert!(!matches("*.rs", "main.rsx"));
+        assert!(matches("/*.rs", "main.rs"));
+        assert!(!matches("/*.rs", "src/main.rs"));
+    }
+
+    #[test]
+    fn globs_match_stars_and_character_sets() {
+        assert!(matches("src/*.rs", "src/main.rs"));
+        assert!(!matches("src/*.rs", "src/editor/main.rs"));
+        assert!(matches("src/**.rs", "src/editor/main.rs"));
+        assert!(matches("src/**/main.rs", "src/main.rs"));
+        assert!(matches("file?.txt", "file1.txt"));
+        assert!(matches("[ab].txt", "a.txt"));
+        assert!(!matches("[!ab].txt", "a.txt"));
+        assert!(matches("[!ab].txt", "c.txt"));
+    }
+
+    #[test]
+    fn expand_braces_gives_each_alternative() {
+        assert_eq!(expand_braces("*.{js,ts}"), ["*.js", "*.ts"]);
+        assert_eq!(expand_braces("{a,b}/{c,d}"), ["a/c", "a/d", "b/c", "b/d"]);
+        assert!(matches("*.{js,ts}", "lib/index.ts"));
+    }
+
+    #[test]
+    fn closer_configs_take_precedence() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("nested");
+        fs::create_dir(&nested).unwrap();
+        fs::write(
+            dir.path().join(FILE_NAME),
+            "root = true\n[*]\nindent_size = 2\nend_of_line = crlf\n",
+        )
+        .unwrap();
+        fs::write(nested.join(FILE_NAME), "[*.rs]\nindent_size = 8\n").unwrap();
+        let file = nested.join("main.rs");
+        fs::write(&file, "").unwrap();
+
+        let mut options = BufferOptions::default();
+        apply_editorconfig(&file, &mut options);
+
+        assert_eq!(options.shift_width, 8);
+        assert_eq!(options.line_ending, LineEnding::CrLf);
+    }
+}
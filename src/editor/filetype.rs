@@ -0,0 +1,266 @@
+use std::path::Path;
+
+use super::indent::IndentStyle;
+
+/// The language of a buffer, which decides how its lines are commented,
+/// indented and highlighted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum FileType {
+    Rust,
+    C,
+    Cpp,
+    Go,
+    Python,
+    JavaScript,
+    TypeScript,
+    Java,
+    Ruby,
+    Lua,
+    Perl,
+    Shell,
+    Zsh,
+    Html,
+    Css,
+    Markdown,
+    Tex,
+    Toml,
+    Json,
+    Yaml,
+    Make,
+    Dockerfile,
+    #[default]
+    Unknown,
+}
+
+// the filetypes that have a name, for looking them up by it
+const NAMED_FILE_TYPES: &[FileType] = &[
+    FileType::Rust,
+    FileType::C,
+    FileType::Cpp,
+    FileType::Go,
+    FileType::Python,
+    FileType::JavaScript,
+    FileType::TypeScript,
+    FileType::Java,
+    FileType::Ruby,
+    FileType::Lua,
+    FileType::Perl,
+    FileType::Shell,
+    FileType::Zsh,
+    FileType::Html,
+    FileType::Css,
+    FileType::Markdown,
+    FileType::Tex,
+    FileType::Toml,
+    FileType::Json,
+    FileType::Yaml,
+    FileType::Make,
+    FileType::Dockerfile,
+];
+
+// file extensions and the filetype they indicate
+const EXTENSIONS: &[(&str, FileType)] = &[
+    ("rs", FileType::Rust),
+    ("c", FileType::C),
+    ("h", FileType::C),
+    ("cc", FileType::Cpp),
+    ("cpp", FileType::Cpp),
+    ("hpp", FileType::Cpp),
+    ("go", FileType::Go),
+    ("py", FileType::Python),
+    ("js", FileType::JavaScript),
+    ("ts", FileType::TypeScript),
+    ("java", FileType::Java),
+    ("rb", FileType::Ruby),
+    ("lua", FileType::Lua),
+    ("sh", FileType::Shell),
+    ("bash", FileType::Shell),
+    ("html", FileType::Html),
+    ("css", FileType::Css),
+    ("md", FileType::Markdown),
+    ("tex", FileType::Tex),
+    ("toml", FileType::Toml),
+    ("json", FileType::Json),
+    ("yaml", FileType::Yaml),
+    ("yml", FileType::Yaml),
+];
+
+// file names without a telling extension
+const FILE_NAMES: &[(&str, FileType)] = &[
+    ("Makefile", FileType::Make),
+    ("Dockerfile", FileType::Dockerfile),
+];
+
+// interpreters named in a #! line
+const INTERPRETERS: &[(&str, FileType)] = &[
+    ("sh", FileType::Shell),
+    ("bash", FileType::Shell),
+    ("dash", FileType::Shell),
+    ("zsh", FileType::Zsh),
+    ("python", FileType::Python),
+    ("node", FileType::JavaScript),
+    ("ruby", FileType::Ruby),
+    ("perl", FileType::Perl),
+    ("lua", FileType::Lua),
+];
+
+impl FileType {
+    /// Guesses the filetype of a file from its path, or failing that from a
+    /// `#!` on its first line.
+    pub fn detect(path: &Path, first_line: &str) -> Self {
+        path.extension()
+            .and_then(|extension| lookup(EXTENSIONS, extension.to_str()?))
+            .or_else(|| lookup(FILE_NAMES, path.file_name()?.to_str()?))
+            .or_else(|| detect_from_shebang(first_line))
+            .unwrap_or_default()
+    }
+
+    /// The filetype called `name`, like "rust", where an empty name is no
+    /// filetype at all.
+    pub fn from_name(name: &str) -> Option<Self> {
+        if name.is_empty() {
+            return Some(Self::Unknown);
+        }
+
+        NAMED_FILE_TYPES
+            .iter()
+            .copied()
+            .find(|file_type| file_type.name() == name)
+    }
+
+    /// The name of the filetype, as :set filetype takes it, which is empty
+    /// for an unknown one.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Rust => "rust",
+            Self::C => "c",
+            Self::Cpp => "cpp",
+            Self::Go => "go",
+            Self::Python => "python",
+            Self::JavaScript => "javascript",
+            Self::TypeScript => "typescript",
+            Self::Java => "java",
+            Self::Ruby => "ruby",
+            Self::Lua => "lua",
+            Self::Perl => "perl",
+            Self::Shell => "sh",
+            Self::Zsh => "zsh",
+            Self::Html => "html",
+            Self::Css => "css",
+            Self::Markdown => "markdown",
+            Self::Tex => "tex",
+            Self::Toml => "toml",
+            Self::Json => "json",
+            Self::Yaml => "yaml",
+            Self::Make => "make",
+            Self::Dockerfile => "dockerfile",
+            Self::Unknown => "",
+        }
+    }
+
+    /// What starts a line comment in the filetype, if it has line comments.
+    pub fn comment_prefix(self) -> Option<&'static str> {
+        match self {
+            Self::Rust
+            | Self::C
+            | Self::Cpp
+            | Self::Go
+            | Self::JavaScript
+            | Self::TypeScript
+            | Self::Java => Some("//"),
+            Self::Python
+            | Self::Ruby
+            | Self::Perl
+            | Self::Shell
+            | Self::Zsh
+            | Self::Toml
+            | Self::Yaml
+            | Self::Make
+            | Self::Dockerfile => Some("#"),
+            Self::Lua => Some("--"),
+            Self::Tex => Some("%"),
+            Self::Html | Self::Css | Self::Markdown | Self::Json | Self::Unknown => None,
+        }
+    }
+
+    /// How files of the filetype are usually indented, for files too short
+    /// to tell, if there is a convention.
+    pub fn indent_style(self) -> Option<IndentStyle> {
+        match self {
+            Self::Rust | Self::Python | Self::Java => Some(IndentStyle::Spaces(4)),
+            Self::JavaScript
+            | Self::TypeScript
+            | Self::Json
+            | Self::Yaml
+            | Self::Ruby
+            | Self::Lua => Some(IndentStyle::Spaces(2)),
+            Self::Go | Self::Make => Some(IndentStyle::Tabs),
+            _ => None,
+        }
+    }
+}
+
+fn detect_from_shebang(first_line: &str) -> Option<FileType> {
+    let mut words = first_line.strip_prefix("#!")?.split_whitespace();
+
+    let mut program = words.next()?.rsplit('/').next()?;
+    // the interpreter is looked up in PATH: #!/usr/bin/env python3
+    if program == "env" {
+        program = words.find(|word| !word.starts_with('-'))?;
+    }
+
+    // python3, python3.12 and so on are still python
+    lookup(
+        INTERPRETERS,
+        program.trim_end_matches(|ch: char| ch.is_ascii_digit() || ch == '.'),
+    )
+}
+
+fn lookup(table: &[(&str, FileType)], key: &str) -> Option<FileType> {
+    table
+        .iter()
+        .find(|(name, _)| *name == key)
+        .map(|(_, file_type)| *file_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(path: &str, first_line: &str) -> FileType {
+        FileType::detect(Path::new(path), first_line)
+    }
+
+    #[test]
+    fn detect_uses_the_extension_or_file_name() {
+        assert_eq!(detect("src/main.rs", ""), FileType::Rust);
+        assert_eq!(detect("config.yml", ""), FileType::Yaml);
+        assert_eq!(detect("project/Makefile", ""), FileType::Make);
+        assert_eq!(detect("notes.txt", ""), FileType::Unknown);
+    }
+
+    #[test]
+    fn detect_falls_back_to_the_shebang() {
+        assert_eq!(detect("build", "#!/bin/bash"), FileType::Shell);
+        assert_eq!(
+            detect("build", "#!/usr/bin/env python3.12"),
+            FileType::Python
+        );
+        assert_eq!(
+            detect("build", "#!/usr/bin/env -S node --flag"),
+            FileType::JavaScript
+        );
+        assert_eq!(detect("build", "# not a shebang"), FileType::Unknown);
+        // the extension wins over the shebang
+        assert_eq!(detect("build.rb", "#!/bin/sh"), FileType::Ruby);
+    }
+
+    #[test]
+    fn from_name_finds_every_named_filetype() {
+        for &file_type in NAMED_FILE_TYPES {
+            assert_eq!(FileType::from_name(file_type.name()), Some(file_type));
+        }
+        assert_eq!(FileType::from_name(""), Some(FileType::Unknown));
+        assert_eq!(FileType::from_name("cobol"), None);
+    }
+}
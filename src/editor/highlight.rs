@@ -0,0 +1,514 @@
+use std::ops::Range;
+
+use super::{buffer::LineSplice, filetype::FileType, treesitter::TreeSitterHighlighter, Buffer};
+use crate::base::{FontStyle, FontWeight};
+
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type",
+    "unsafe", "use", "where", "while",
+];
+
+const C_KEYWORDS: &[&str] = &[
+    "auto", "break", "case", "char", "const", "continue", "default", "do", "double", "else",
+    "enum", "extern", "float", "for", "goto", "if", "inline", "int", "long", "register", "return",
+    "short", "signed", "sizeof", "static", "struct", "switch", "typedef", "union", "unsigned",
+    "void", "volatile", "while",
+];
+
+const CPP_KEYWORDS: &[&str] = &[
+    "auto",
+    "bool",
+    "break",
+    "case",
+    "catch",
+    "char",
+    "class",
+    "const",
+    "constexpr",
+    "continue",
+    "default",
+    "delete",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "explicit",
+    "extern",
+    "false",
+    "float",
+    "for",
+    "friend",
+    "if",
+    "inline",
+    "int",
+    "long",
+    "namespace",
+    "new",
+    "nullptr",
+    "operator",
+    "private",
+    "protected",
+    "public",
+    "return",
+    "short",
+    "signed",
+    "sizeof",
+    "static",
+    "struct",
+    "switch",
+    "template",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typedef",
+    "typename",
+    "union",
+    "unsigned",
+    "using",
+    "virtual",
+    "void",
+    "volatile",
+    "while",
+];
+
+const GO_KEYWORDS: &[&str] = &[
+    "break",
+    "case",
+    "chan",
+    "const",
+    "continue",
+    "default",
+    "defer",
+    "else",
+    "fallthrough",
+    "false",
+    "for",
+    "func",
+    "go",
+    "goto",
+    "if",
+    "import",
+    "interface",
+    "map",
+    "nil",
+    "package",
+    "range",
+    "return",
+    "select",
+    "struct",
+    "switch",
+    "true",
+    "type",
+    "var",
+];
+
+const PYTHON_KEYWORDS: &[&str] = &[
+    "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del", "elif",
+    "else", "except", "False", "finally", "for", "from", "global", "if", "import", "in", "is",
+    "lambda", "None", "nonlocal", "not", "or", "pass", "raise", "return", "True", "try", "while",
+    "with", "yield",
+];
+
+const JAVASCRIPT_KEYWORDS: &[&str] = &[
+    "async",
+    "await",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "export",
+    "extends",
+    "false",
+    "finally",
+    "for",
+    "function",
+    "if",
+    "import",
+    "in",
+    "instanceof",
+    "let",
+    "new",
+    "null",
+    "of",
+    "return",
+    "static",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeof",
+    "undefined",
+    "var",
+    "void",
+    "while",
+    "yield",
+];
+
+const TYPESCRIPT_KEYWORDS: &[&str] = &[
+    "abstract",
+    "any",
+    "as",
+    "async",
+    "await",
+    "boolean",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "declare",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "enum",
+    "export",
+    "extends",
+    "false",
+    "finally",
+    "for",
+    "function",
+    "if",
+    "implements",
+    "import",
+    "in",
+    "instanceof",
+    "interface",
+    "let",
+    "namespace",
+    "never",
+    "new",
+    "null",
+    "number",
+    "of",
+    "private",
+    "protected",
+    "public",
+    "readonly",
+    "return",
+    "static",
+    "string",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "type",
+    "typeof",
+    "undefined",
+    "unknown",
+    "var",
+    "void",
+    "while",
+    "yield",
+];
+
+const JAVA_KEYWORDS: &[&str] = &[
+    "abstract",
+    "boolean",
+    "break",
+    "byte",
+    "case",
+    "catch",
+    "char",
+    "class",
+    "continue",
+    "default",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "extends",
+    "false",
+    "final",
+    "finally",
+    "float",
+    "for",
+    "if",
+    "implements",
+    "import",
+    "instanceof",
+    "int",
+    "interface",
+    "long",
+    "new",
+    "null",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "return",
+    "short",
+    "static",
+    "super",
+    "switch",
+    "synchronized",
+    "this",
+    "throw",
+    "throws",
+    "true",
+    "try",
+    "var",
+    "void",
+    "while",
+];
+
+const RUBY_KEYWORDS: &[&str] = &[
+    "begin", "break", "case", "class", "def", "do", "else", "elsif", "end", "ensure", "false",
+    "for", "if", "in", "module", "next", "nil", "not", "or", "and", "redo", "rescue", "retry",
+    "return", "self", "super", "then", "true", "unless", "until", "when", "while", "yield",
+];
+
+const LUA_KEYWORDS: &[&str] = &[
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "goto", "if", "in",
+    "local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+];
+
+const SH_KEYWORDS: &[&str] = &[
+    "case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if", "in",
+    "local", "return", "then", "until", "while",
+];
+
+// the keywords of each filetype and the quotes its strings are in. rust's
+// single quotes are left out, as they mostly start lifetimes
+const SYNTAXES: &[(FileType, Syntax)] = &[
+    (FileType::Rust, Syntax::new(RUST_KEYWORDS, &['"'])),
+    (FileType::C, Syntax::new(C_KEYWORDS, &['"', '\''])),
+    (FileType::Cpp, Syntax::new(CPP_KEYWORDS, &['"', '\''])),
+    (FileType::Go, Syntax::new(GO_KEYWORDS, &['"', '\'', '`'])),
+    (FileType::Python, Syntax::new(PYTHON_KEYWORDS, &['"', '\''])),
+    (
+        FileType::JavaScript,
+        Syntax::new(JAVASCRIPT_KEYWORDS, &['"', '\'', '`']),
+    ),
+    (
+        FileType::TypeScript,
+        Syntax::new(TYPESCRIPT_KEYWORDS, &['"', '\'', '`']),
+    ),
+    (FileType::Java, Syntax::new(JAVA_KEYWORDS, &['"', '\''])),
+    (FileType::Ruby, Syntax::new(RUBY_KEYWORDS, &['"', '\''])),
+    (FileType::Lua, Syntax::new(LUA_KEYWORDS, &['"', '\''])),
+    (FileType::Shell, Syntax::new(SH_KEYWORDS, &['"', '\''])),
+];
+
+/// What a part of the text is, which decides the color it is drawn in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HighlightGroup {
+    Comment,
+    String,
+    Number,
+    Keyword,
+}
+
+impl HighlightGroup {
+    /// How heavy the text of the group is drawn, unless said otherwise.
+    pub fn weight(self) -> FontWeight {
+        match self {
+            Self::Keyword => FontWeight::Bold,
+            Self::Comment | Self::String | Self::Number => FontWeight::Regular,
+        }
+    }
+
+    /// Whether the text of the group is drawn slanted, unless said otherwise.
+    pub fn style(self) -> FontStyle {
+        match self {
+            Self::Comment => FontStyle::Italic,
+            Self::String | Self::Number | Self::Keyword => FontStyle::Normal,
+        }
+    }
+}
+
+/// A highlighted part of a line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HighlightSpan {
+    /// columns (in chars) of the line
+    pub start: usize,
+    pub end: usize,
+    pub group: HighlightGroup,
+    pub weight: FontWeight,
+    pub style: FontStyle,
+}
+
+impl HighlightSpan {
+    /// A span drawn in the usual weight and style of `group`.
+    pub fn new(start: usize, end: usize, group: HighlightGroup) -> Self {
+        Self {
+            start,
+            end,
+            group,
+            weight: group.weight(),
+            style: group.style(),
+        }
+    }
+}
+
+/// Tells what the text of a filetype is made of, for drawing it in color.
+pub trait Highlighter {
+    /// Catches up with `buffer`, whose lines were replaced as in `splices`
+    /// since this was last called. Returns the lines whose highlighting
+    /// changed besides the replaced ones.
+    fn update(&mut self, _buffer: &Buffer, _splices: &[LineSplice]) -> Vec<Range<usize>> {
+        vec![]
+    }
+
+    /// The highlighted parts of `line` of `buffer`, in order.
+    fn highlight(&self, buffer: &Buffer, line: usize) -> Vec<HighlightSpan>;
+}
+
+/// The highlighter for `filetype`, if there is one.
+pub fn highlighter(filetype: FileType) -> Option<Box<dyn Highlighter>> {
+    // a grammar knows the language far better than a list of its keywords
+    if let Some(highlighter) = TreeSitterHighlighter::for_filetype(filetype) {
+        return Some(Box::new(highlighter));
+    }
+
+    let (_, syntax) = SYNTAXES.iter().find(|(name, _)| *name == filetype)?;
+
+    Some(Box::new(KeywordHighlighter {
+        syntax,
+        comment_prefix: filetype.comment_prefix(),
+    }))
+}
+
+struct Syntax {
+    keywords: &'static [&'static str],
+    quotes: &'static [char],
+}
+
+impl Syntax {
+    const fn new(keywords: &'static [&'static str], quotes: &'static [char]) -> Self {
+        Self { keywords, quotes }
+    }
+}
+
+// highlights keywords, numbers, strings and line comments. lines are looked
+// at on their own, so comments and strings spanning lines aren't found
+struct KeywordHighlighter {
+    syntax: &'static Syntax,
+    comment_prefix: Option<&'static str>,
+}
+
+impl Highlighter for KeywordHighlighter {
+    fn highlight(&self, buffer: &Buffer, line: usize) -> Vec<HighlightSpan> {
+        let chars: Vec<char> = buffer.line(line).unwrap_or_default().chars().collect();
+        let comment_prefix: Vec<char> = self.comment_prefix.unwrap_or_default().chars().collect();
+        let is_word_char = |ch: char| ch.is_alphanumeric() || ch == '_';
+
+        let mut spans = vec![];
+        let mut col = 0;
+        while col < chars.len() {
+            let start = col;
+            let ch = chars[col];
+
+            let group = if !comment_prefix.is_empty() && chars[col..].starts_with(&comment_prefix) {
+                col = chars.len();
+                Some(HighlightGroup::Comment)
+            } else if self.syntax.quotes.contains(&ch) {
+                col = string_end(&chars, col);
+                Some(HighlightGroup::String)
+            } else if is_word_char(ch) {
+                while col < chars.len() && is_word_char(chars[col]) {
+                    col += 1;
+                }
+                let word: String = chars[start..col].iter().collect();
+
+                if ch.is_ascii_digit() {
+                    Some(HighlightGroup::Number)
+                } else if self.syntax.keywords.contains(&word.as_str()) {
+                    Some(HighlightGroup::Keyword)
+                } else {
+                    None
+                }
+            } else {
+                col += 1;
+                None
+            };
+
+            if let Some(group) = group {
+                spans.push(HighlightSpan::new(start, col, group));
+            }
+        }
+
+        spans
+    }
+}
+
+// the column after the quote closing the string that starts at `start`,
+// skipping escaped quotes, or the end of the line if it isn't closed on it
+fn string_end(chars: &[char], start: usize) -> usize {
+    let quote = chars[start];
+    let mut col = start + 1;
+    while col < chars.len() {
+        match chars[col] {
+            '\\' => col += 2,
+            ch if ch == quote => return col + 1,
+            _ => col += 1,
+        }
+    }
+    chars.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn highlight_c(text: &str) -> Vec<HighlightSpan> {
+        highlighter(FileType::C)
+            .unwrap()
+            .highlight(&Buffer::from(text), 0)
+    }
+
+    #[test]
+    fn keywords_are_bold_and_comments_italic() {
+        assert_eq!(
+            highlight_c("int main() // entry"),
+            vec![
+                HighlightSpan {
+                    start: 0,
+                    end: 3,
+                    group: HighlightGroup::Keyword,
+                    weight: FontWeight::Bold,
+                    style: FontStyle::Normal,
+                },
+                HighlightSpan {
+                    start: 11,
+                    end: 19,
+                    group: HighlightGroup::Comment,
+                    weight: FontWeight::Regular,
+                    style: FontStyle::Italic,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn strings_skip_escaped_quotes() {
+        let spans = highlight_c(r#"char *s = "a\"b"; 42"#);
+        let groups: Vec<_> = spans
+            .iter()
+            .map(|span| (span.start, span.end, span.group))
+            .collect();
+
+        assert_eq!(
+            groups,
+            vec![
+                (0, 4, HighlightGroup::Keyword),
+                (10, 16, HighlightGroup::String),
+                (18, 20, HighlightGroup::Number),
+            ]
+        );
+    }
+}
@@ -0,0 +1,517 @@
+use crate::base::Position;
+
+const DEFAULT_UNDO_LEVELS: usize = 1000;
+const DEFAULT_MAX_MEMORY: usize = 64 * 1024 * 1024;
+
+/// Replacement of `old_lines` starting at `line` with `new_lines`.
+pub struct Edit {
+    pub line: usize,
+    pub old_lines: Vec<String>,
+    pub new_lines: Vec<String>,
+}
+
+impl Edit {
+    // approximate, only the text itself is counted
+    fn memory(&self) -> usize {
+        self.old_lines
+            .iter()
+            .chain(self.new_lines.iter())
+            .map(String::len)
+            .sum()
+    }
+
+    fn is_single_line(&self) -> bool {
+        self.old_lines.len() == 1 && self.new_lines.len() == 1
+    }
+}
+
+/// A group of edits that is undone and redone as a single unit.
+pub struct UndoEntry {
+    pub edits: Vec<Edit>,
+    // where the cursor was before the first edit
+    pub cursor: Position<usize>,
+}
+
+impl UndoEntry {
+    fn memory(&self) -> usize {
+        self.edits.iter().map(Edit::memory).sum()
+    }
+}
+
+/// A state of the buffer in the undo tree, reached from its parent by
+/// applying `entry`.
+struct UndoNode {
+    // None for the root, which is the oldest state that can be returned to
+    entry: Option<UndoEntry>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    // the child that was most recently visited, which redo goes to
+    current_child: Option<usize>,
+    // increases with every new node, for walking the tree in the order of edits
+    seq: usize,
+}
+
+/// A single move through the undo tree, as applied to the buffer.
+#[derive(Debug, Clone, Copy)]
+pub enum UndoStep {
+    /// revert the entry of this node, moving to its parent
+    Revert(usize),
+    /// apply the entry of this node, moving to it from its parent
+    Apply(usize),
+}
+
+/// Undo history kept as a tree, so that undoing and then making a different
+/// change does not lose the undone changes.
+pub struct History {
+    // evicted nodes leave a None behind, so that indices stay valid
+    nodes: Vec<Option<UndoNode>>,
+    root: usize,
+    // the node for the current state of the buffer
+    current: usize,
+    next_seq: usize,
+    // while a group is open, edits are recorded as a single entry
+    group_open: bool,
+    // whether the open group already has a node to add edits to
+    group_started: bool,
+    undo_levels: usize,
+    max_memory: usize,
+    // approximate memory used by all entries
+    memory: usize,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![Some(UndoNode {
+                entry: None,
+                parent: None,
+                children: vec![],
+                current_child: None,
+                seq: 0,
+            })],
+            root: 0,
+            current: 0,
+            next_seq: 1,
+            group_open: false,
+            group_started: false,
+            undo_levels: DEFAULT_UNDO_LEVELS,
+            max_memory: DEFAULT_MAX_MEMORY,
+            memory: 0,
+        }
+    }
+
+    fn node(&self, index: usize) -> &UndoNode {
+        self.nodes[index].as_ref().expect("undo node was evicted")
+    }
+
+    fn node_mut(&mut self, index: usize) -> &mut UndoNode {
+        self.nodes[index].as_mut().expect("undo node was evicted")
+    }
+
+    /// The entry that moves from the parent of `node` to `node`.
+    pub fn entry(&self, node: usize) -> &UndoEntry {
+        self.node(node)
+            .entry
+            .as_ref()
+            .expect("root has no undo entry")
+    }
+
+    pub fn set_undo_levels(&mut self, undo_levels: usize) {
+        self.undo_levels = undo_levels;
+        self.evict();
+    }
+
+    pub fn set_max_memory(&mut self, max_memory: usize) {
+        self.max_memory = max_memory;
+        self.evict();
+    }
+
+    pub fn begin_group(&mut self) {
+        self.group_open = true;
+        self.group_started = false;
+    }
+
+    pub fn end_group(&mut self) {
+        self.group_open = false;
+        self.group_started = false;
+    }
+
+    /// The node the open group records into, once it has recorded anything.
+    pub fn group_node(&self) -> Option<usize> {
+        self.group_started.then_some(self.current)
+    }
+
+    /// Drops `node` and every state after it, which must not include the
+    /// current state.
+    pub fn forget(&mut self, node: usize) {
+        if let Some(parent) = self.node(node).parent {
+            let parent = self.node_mut(parent);
+            parent.children.retain(|child| *child != node);
+            if parent.current_child == Some(node) {
+                parent.current_child = parent.children.last().copied();
+            }
+        }
+
+        self.remove_subtree(node);
+    }
+
+    /// Identifies the current state, so that it can be told apart from any
+    /// other state the buffer was in.
+    pub fn current_seq(&self) -> usize {
+        self.node(self.current).seq
+    }
+
+    /// Forgets everything, keeping only the current state.
+    pub fn clear(&mut self) {
+        let group_open = self.group_open;
+        let undo_levels = self.undo_levels;
+        let max_memory = self.max_memory;
+        let current_seq = self.current_seq();
+        let next_seq = self.next_seq;
+
+        *self = Self::new();
+        self.group_open = group_open;
+        self.undo_levels = undo_levels;
+        self.max_memory = max_memory;
+        // the current state is still the same one
+        self.node_mut(0).seq = current_seq;
+        self.next_seq = next_seq;
+    }
+
+    pub fn record(&mut self, edit: Edit, cursor: Position<usize>) {
+        if self.group_started {
+            let current = self.current;
+            let entry = self.nodes[current]
+                .as_mut()
+                .and_then(|node| node.entry.as_mut())
+                .expect("started group has an entry");
+            self.memory -= entry.memory();
+
+            let last = entry.edits.last_mut().expect("entry is not empty");
+            // consecutive changes to the same line (e.g. typing) only need
+            // to remember the line before the first change
+            if last.is_single_line() && edit.is_single_line() && last.line == edit.line {
+                last.new_lines = edit.new_lines;
+            } else {
+                entry.edits.push(edit);
+            }
+
+            self.memory += entry.memory();
+        } else {
+            let entry = UndoEntry {
+                edits: vec![edit],
+                cursor,
+            };
+            self.memory += entry.memory();
+
+            let index = self.nodes.len();
+            self.nodes.push(Some(UndoNode {
+                entry: Some(entry),
+                parent: Some(self.current),
+                children: vec![],
+                current_child: None,
+                seq: self.next_seq,
+            }));
+            self.next_seq += 1;
+
+            let parent = self.node_mut(self.current);
+            parent.children.push(index);
+            parent.current_child = Some(index);
+
+            self.current = index;
+            self.group_started = self.group_open;
+        }
+
+        self.evict();
+    }
+
+    /// Moves to the parent of the current state.
+    pub fn undo(&mut self) -> Vec<UndoStep> {
+        match self.node(self.current).parent {
+            Some(parent) => self.travel_to(parent),
+            None => vec![],
+        }
+    }
+
+    /// Moves to the most recently visited child of the current state.
+    pub fn redo(&mut self) -> Vec<UndoStep> {
+        match self.node(self.current).current_child {
+            Some(child) => self.travel_to(child),
+            None => vec![],
+        }
+    }
+
+    /// Moves to the state made right before (or after, if `forward`) the
+    /// current one, regardless of which branch it is on.
+    pub fn travel_chronologically(&mut self, forward: bool) -> Vec<UndoStep> {
+        let current_seq = self.node(self.current).seq;
+
+        let live_nodes = self
+            .nodes
+            .iter()
+            .enumerate()
+            .filter_map(|(index, node)| Some((index, node.as_ref()?.seq)));
+
+        let target = if forward {
+            live_nodes
+                .filter(|(_, seq)| *seq > current_seq)
+                .min_by_key(|(_, seq)| *seq)
+        } else {
+            live_nodes
+                .filter(|(_, seq)| *seq < current_seq)
+                .max_by_key(|(_, seq)| *seq)
+        };
+
+        match target {
+            Some((target, _)) => self.travel_to(target),
+            None => vec![],
+        }
+    }
+
+    // the path from `node` up to the root, starting with `node` itself
+    fn ancestors(&self, node: usize) -> Vec<usize> {
+        let mut ancestors = vec![node];
+        while let Some(parent) = self.node(*ancestors.last().unwrap()).parent {
+            ancestors.push(parent);
+        }
+        ancestors
+    }
+
+    fn travel_to(&mut self, target: usize) -> Vec<UndoStep> {
+        self.end_group();
+
+        let from_path = self.ancestors(self.current);
+        let to_path = self.ancestors(target);
+        let common = *from_path
+            .iter()
+            .find(|node| to_path.contains(node))
+            .expect("nodes share the root");
+
+        let mut steps: Vec<UndoStep> = from_path
+            .iter()
+            .take_while(|node| **node != common)
+            .map(|node| UndoStep::Revert(*node))
+            .collect();
+
+        let downwards: Vec<usize> = to_path
+            .iter()
+            .take_while(|node| **node != common)
+            .copied()
+            .collect();
+
+        downwards.iter().rev().for_each(|node| {
+            if let Some(parent) = self.node(*node).parent {
+                self.node_mut(parent).current_child = Some(*node);
+            }
+            steps.push(UndoStep::Apply(*node));
+        });
+
+        self.current = target;
+        steps
+    }
+
+    /// Describes the shape of the tree, e.g. `0 (1 (2, 3*))`, where each
+    /// number is the order a state was made in and `*` marks the current one.
+    pub fn describe(&self) -> String {
+        self.describe_node(self.root)
+    }
+
+    fn describe_node(&self, index: usize) -> String {
+        let node = self.node(index);
+        let mut description = node.seq.to_string();
+
+        if index == self.current {
+            description.push('*');
+        }
+
+        if !node.children.is_empty() {
+            let children: Vec<String> = node
+                .children
+                .iter()
+                .map(|child| self.describe_node(*child))
+                .collect();
+            description.push_str(&format!(" ({})", children.join(", ")));
+        }
+
+        description
+    }
+
+    fn remove_subtree(&mut self, index: usize) {
+        if let Some(node) = self.nodes[index].take() {
+            if let Some(entry) = &node.entry {
+                self.memory -= entry.memory();
+            }
+            node.children
+                .iter()
+                .for_each(|child| self.remove_subtree(*child));
+        }
+    }
+
+    // drops the oldest states until both the level and memory limits are met
+    fn evict(&mut self) {
+        loop {
+            let node_count = self.nodes.iter().flatten().count() - 1;
+            if node_count <= self.undo_levels && self.memory <= self.max_memory {
+                break;
+            }
+
+            let root = self.root;
+            let Some(oldest) = self
+                .node(root)
+                .children
+                .iter()
+                .copied()
+                .min_by_key(|child| self.node(*child).seq)
+            else {
+                break;
+            };
+
+            if self.ancestors(self.current).contains(&oldest) {
+                // the oldest state is still on the way to the current one, so
+                // it becomes the new root, and the other branches are dropped
+                let siblings: Vec<usize> = self
+                    .node(root)
+                    .children
+                    .iter()
+                    .copied()
+                    .filter(|child| *child != oldest)
+                    .collect();
+                siblings
+                    .into_iter()
+                    .for_each(|sibling| self.remove_subtree(sibling));
+
+                self.nodes[root] = None;
+                let new_root = self.node_mut(oldest);
+                new_root.parent = None;
+                if let Some(entry) = new_root.entry.take() {
+                    self.memory -= entry.memory();
+                }
+                self.root = oldest;
+
+                // the entry of the open group might have been evicted
+                if self.current == oldest {
+                    self.group_started = false;
+                }
+            } else {
+                self.remove_subtree(oldest);
+                self.node_mut(root)
+                    .children
+                    .retain(|child| *child != oldest);
+                let root_node = self.node_mut(root);
+                if root_node.current_child == Some(oldest) {
+                    root_node.current_child = root_node.children.last().copied();
+                }
+            }
+        }
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORIGIN: Position<usize> = Position { x: 0, y: 0 };
+
+    fn edit(old: &str, new: &str) -> Edit {
+        Edit {
+            line: 0,
+            old_lines: vec![old.to_string()],
+            new_lines: vec![new.to_string()],
+        }
+    }
+
+    // records a change of every line in `lines` to the next
+    fn history_of(lines: &[&str]) -> History {
+        let mut history = History::new();
+        lines
+            .windows(2)
+            .for_each(|pair| history.record(edit(pair[0], pair[1]), ORIGIN));
+        history
+    }
+
+    #[test]
+    fn undo_goes_back_to_the_branch_point_and_redo_forward() {
+        let mut history = history_of(&["a", "b", "c"]);
+
+        assert!(matches!(history.undo()[..], [UndoStep::Revert(2)]));
+        assert!(matches!(history.redo()[..], [UndoStep::Apply(2)]));
+        assert!(history.redo().is_empty());
+    }
+
+    #[test]
+    fn changes_after_undo_start_a_new_branch() {
+        let mut history = history_of(&["a", "b"]);
+        history.undo();
+        history.record(edit("a", "c"), ORIGIN);
+
+        assert_eq!(history.describe(), "0 (1, 2*)");
+        // back in time is the undone branch, which is reached through the root
+        assert!(matches!(
+            history.travel_chronologically(false)[..],
+            [UndoStep::Revert(2), UndoStep::Apply(1)]
+        ));
+    }
+
+    #[test]
+    fn undo_levels_evict_the_oldest_states() {
+        let mut history = history_of(&["a", "b", "c", "d"]);
+
+        history.set_undo_levels(2);
+
+        assert_eq!(history.describe(), "1 (2 (3*))");
+        assert_eq!(history.undo().len(), 1);
+        assert_eq!(history.undo().len(), 1);
+        assert!(history.undo().is_empty());
+    }
+
+    #[test]
+    fn max_memory_evicts_the_oldest_states() {
+        let mut history = History::new();
+        history.set_max_memory(10);
+
+        history.record(edit("aaaa", "bbbb"), ORIGIN);
+        history.record(edit("bbbb", "cccc"), ORIGIN);
+
+        assert_eq!(history.memory, 8);
+        assert_eq!(history.undo().len(), 1);
+        assert!(history.undo().is_empty());
+    }
+
+    #[test]
+    fn evicting_drops_undone_branches_if_they_are_older() {
+        let mut history = history_of(&["a", "b"]);
+        history.undo();
+        history.record(edit("a", "c"), ORIGIN);
+        history.record(edit("c", "d"), ORIGIN);
+
+        history.set_undo_levels(2);
+
+        // the root stays, as the states left are within the levels
+        assert_eq!(history.describe(), "0 (2 (3*))");
+    }
+
+    #[test]
+    fn group_records_edits_as_one_entry() {
+        let mut history = History::new();
+        history.begin_group();
+        history.record(edit("a", "ab"), ORIGIN);
+        history.record(edit("ab", "abc"), ORIGIN);
+        history.end_group();
+
+        let [UndoStep::Revert(node)] = history.undo()[..] else {
+            panic!("expected a single step");
+        };
+        let edits = &history.entry(node).edits;
+        assert_eq!(edits.len(), 1);
+        assert_eq!(
+            (&edits[0].old_lines, &edits[0].new_lines),
+            (&vec!["a".to_string()], &vec!["abc".to_string()])
+        );
+    }
+}
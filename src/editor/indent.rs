@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+
+use super::buffer::leading_whitespace;
+
+/// How a file indents its lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndentStyle {
+    Tabs,
+    /// the number of spaces for each level
+    Spaces(usize),
+}
+
+// indentation steps wider than this are probably alignment, not indentation
+const MAX_INDENT_STEP: usize = 8;
+
+/// Guesses the indentation style of `lines` from their leading whitespace,
+/// returning `None` if there is too little indentation to tell.
+pub fn detect_indent(lines: &[String]) -> Option<IndentStyle> {
+    let mut tab_lines = 0;
+    let mut space_lines = 0;
+    // how often each increase in indentation between lines is seen
+    let mut steps: HashMap<usize, usize> = HashMap::new();
+    let mut previous_width = 0;
+
+    for line in lines {
+        let indent = leading_whitespace(line);
+        let rest = &line[indent.len()..];
+        // blank lines and the * of block comments don't show the indent style
+        if rest.is_empty() || rest.starts_with('*') {
+            continue;
+        }
+
+        if indent.starts_with('\t') {
+            tab_lines += 1;
+            continue;
+        }
+
+        let width = indent.len();
+        if width > 0 {
+            space_lines += 1;
+        }
+        if width > previous_width && width - previous_width <= MAX_INDENT_STEP {
+            *steps.entry(width - previous_width).or_default() += 1;
+        }
+        previous_width = width;
+    }
+
+    if tab_lines > space_lines {
+        return Some(IndentStyle::Tabs);
+    }
+    if space_lines == 0 || space_lines == tab_lines {
+        return None;
+    }
+
+    // the most common step, preferring the smaller one on ties
+    steps
+        .into_iter()
+        .max_by_key(|&(step, count)| (count, usize::MAX - step))
+        .map(|(step, _)| IndentStyle::Spaces(step))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(text: &str) -> Option<IndentStyle> {
+        let lines: Vec<String> = text.lines().map(String::from).collect();
+        detect_indent(&lines)
+    }
+
+    #[test]
+    fn detects_the_most_common_step_of_spaces() {
+        let text = "fn a() {\n  if b {\n    c();\n  }\n  d();\n}";
+
+        assert_eq!(detect(text), Some(IndentStyle::Spaces(2)));
+    }
+
+    #[test]
+    fn detects_tabs() {
+        assert_eq!(detect("a {\n\tb\n\tc\n}"), Some(IndentStyle::Tabs));
+    }
+
+    #[test]
+    fn ignores_alignment_and_block_comments() {
+        let text = "/*\n * doc\n */\nlet x = f(a,\n                b);\nif x {\n    y\n}";
+
+        assert_eq!(detect(text), Some(IndentStyle::Spaces(4)));
+    }
+
+    #[test]
+    fn cannot_tell_without_indentation() {
+        assert_eq!(detect("a\nb\n\nc"), None);
+        assert_eq!(detect("a\n\tb\n  c"), None);
+    }
+}
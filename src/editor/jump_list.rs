@@ -0,0 +1,77 @@
+// older jumps are forgotten past this
+const MAX_JUMPS: usize = 100;
+
+/// A cursor position that was jumped away from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JumpEntry {
+    pub buffer_id: usize,
+    pub line: usize,
+    pub col: usize,
+}
+
+/// The positions that big cursor movements were made from, to go back and
+/// forth between like vim's jump list.
+pub struct JumpList {
+    entries: Vec<JumpEntry>,
+    // where `prev` and `next` move from, which is past the end when not
+    // moving through the list
+    current: usize,
+}
+
+impl JumpList {
+    pub fn new() -> Self {
+        Self {
+            entries: vec![],
+            current: 0,
+        }
+    }
+
+    /// Records a jump from `entry`, forgetting the jumps after the current one.
+    pub fn record(&mut self, entry: JumpEntry) {
+        self.entries.truncate(self.current);
+        // a line is only kept once, at its latest jump
+        self.entries
+            .retain(|old| (old.buffer_id, old.line) != (entry.buffer_id, entry.line));
+        self.entries.push(entry);
+
+        if self.entries.len() > MAX_JUMPS {
+            self.entries.remove(0);
+        }
+        self.current = self.entries.len();
+    }
+
+    /// Goes back to the previous jump, where `from` is the current position,
+    /// which `next` can return to.
+    pub fn prev(&mut self, from: JumpEntry) -> Option<JumpEntry> {
+        if self.current == 0 {
+            return None;
+        }
+
+        if self.current == self.entries.len() {
+            self.record(from);
+            self.current = self.entries.len() - 1;
+        }
+        if self.current == 0 {
+            return None;
+        }
+
+        self.current -= 1;
+        Some(self.entries[self.current])
+    }
+
+    /// Goes forward to the jump after the current one.
+    pub fn next(&mut self) -> Option<JumpEntry> {
+        if self.current + 1 >= self.entries.len() {
+            return None;
+        }
+
+        self.current += 1;
+        Some(self.entries[self.current])
+    }
+}
+
+impl Default for JumpList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
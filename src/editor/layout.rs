@@ -0,0 +1,413 @@
+use std::mem;
+
+use crate::base::{Position, Size};
+
+use super::View;
+
+/// How the two sides of a split are placed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitDirection {
+    /// side by side, split by a vertical line
+    Horizontal,
+    /// above each other, split by a horizontal line
+    Vertical,
+}
+
+/// A step from a split into one of its sides.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+impl Step {
+    fn first(direction: SplitDirection) -> Self {
+        match direction {
+            SplitDirection::Horizontal => Step::Left,
+            SplitDirection::Vertical => Step::Top,
+        }
+    }
+
+    fn second(direction: SplitDirection) -> Self {
+        match direction {
+            SplitDirection::Horizontal => Step::Right,
+            SplitDirection::Vertical => Step::Bottom,
+        }
+    }
+
+    fn is_first(self) -> bool {
+        matches!(self, Step::Left | Step::Top)
+    }
+}
+
+/// Identifies a pane of a `Layout`, by the sides taken from the root to it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FocusPath(Vec<Step>);
+
+impl FocusPath {
+    fn child(&self, step: Step) -> Self {
+        let mut steps = self.0.clone();
+        steps.push(step);
+        Self(steps)
+    }
+
+    fn parent(&self) -> Option<Self> {
+        let (_, steps) = self.0.split_last()?;
+        Some(Self(steps.to_vec()))
+    }
+}
+
+/// Where a pane is on screen, in cells.
+#[derive(Debug, Clone, Copy)]
+pub struct PaneArea {
+    pub pos: Position<usize>,
+    pub size: Size<usize>,
+}
+
+impl PaneArea {
+    fn right(&self) -> usize {
+        self.pos.x + self.size.w
+    }
+
+    fn bottom(&self) -> usize {
+        self.pos.y + self.size.h
+    }
+
+    pub fn contains(&self, cell: Position<usize>) -> bool {
+        (self.pos.x..self.right()).contains(&cell.x)
+            && (self.pos.y..self.bottom()).contains(&cell.y)
+    }
+
+    // how many cells the two areas share along the edge between them
+    fn overlap(&self, other: &PaneArea, direction: SplitDirection) -> usize {
+        let (start, end) = match direction {
+            SplitDirection::Horizontal => (
+                self.pos.y.max(other.pos.y),
+                self.bottom().min(other.bottom()),
+            ),
+            SplitDirection::Vertical => {
+                (self.pos.x.max(other.pos.x), self.right().min(other.right()))
+            }
+        };
+        end.saturating_sub(start)
+    }
+}
+
+/// The panes the window is split into, each showing a view.
+///
+/// The view of the focused pane is kept outside of the layout, the same way
+/// the buffer of the view is kept outside of the buffer list, so its pane
+/// holds `None`.
+pub enum Layout {
+    Pane(Option<Box<View>>),
+    Split {
+        direction: SplitDirection,
+        /// the share of the space given to the first side
+        ratio: f64,
+        first: Box<Layout>,
+        second: Box<Layout>,
+    },
+}
+
+impl Layout {
+    /// A layout of a single pane, for the focused view.
+    pub fn new() -> Self {
+        Layout::Pane(None)
+    }
+
+    fn node(&self, path: &FocusPath) -> Option<&Layout> {
+        path.0.iter().try_fold(self, |node, step| match node {
+            Layout::Split { first, .. } if step.is_first() => Some(first.as_ref()),
+            Layout::Split { second, .. } => Some(second.as_ref()),
+            Layout::Pane(_) => None,
+        })
+    }
+
+    fn node_mut(&mut self, path: &FocusPath) -> Option<&mut Layout> {
+        path.0.iter().try_fold(self, |node, step| match node {
+            Layout::Split { first, .. } if step.is_first() => Some(first.as_mut()),
+            Layout::Split { second, .. } => Some(second.as_mut()),
+            Layout::Pane(_) => None,
+        })
+    }
+
+    /// The view of the pane at `path`, which is `None` for the focused pane.
+    pub fn view(&self, path: &FocusPath) -> Option<&View> {
+        match self.node(path)? {
+            Layout::Pane(view) => view.as_deref(),
+            Layout::Split { .. } => None,
+        }
+    }
+
+    /// The slot of the pane at `path`, to move its view in or out.
+    pub fn pane_mut(&mut self, path: &FocusPath) -> Option<&mut Option<Box<View>>> {
+        match self.node_mut(path)? {
+            Layout::Pane(view) => Some(view),
+            Layout::Split { .. } => None,
+        }
+    }
+
+    /// The views of the panes other than the focused one.
+    pub fn views(&self) -> Vec<&View> {
+        match self {
+            Layout::Pane(view) => view.as_deref().into_iter().collect(),
+            Layout::Split { first, second, .. } => {
+                let mut views = first.views();
+                views.extend(second.views());
+                views
+            }
+        }
+    }
+
+    pub fn views_mut(&mut self) -> Vec<&mut View> {
+        match self {
+            Layout::Pane(view) => view.as_deref_mut().into_iter().collect(),
+            Layout::Split { first, second, .. } => {
+                let mut views = first.views_mut();
+                views.extend(second.views_mut());
+                views
+            }
+        }
+    }
+
+    /// The pane showing the buffer with `id`, if it isn't the focused one.
+    pub fn find_buffer(&self, id: usize) -> Option<FocusPath> {
+        self.find_buffer_from(FocusPath::default(), id)
+    }
+
+    fn find_buffer_from(&self, path: FocusPath, id: usize) -> Option<FocusPath> {
+        match self {
+            Layout::Pane(Some(view)) if view.buffer().id() == id => Some(path),
+            Layout::Pane(_) => None,
+            Layout::Split {
+                direction,
+                first,
+                second,
+                ..
+            } => first
+                .find_buffer_from(path.child(Step::first(*direction)), id)
+                .or_else(|| second.find_buffer_from(path.child(Step::second(*direction)), id)),
+        }
+    }
+
+    /// Every pane and where it is drawn when the layout fills `area`, in
+    /// order from the top left.
+    pub fn panes(&self, area: PaneArea) -> Vec<(FocusPath, PaneArea)> {
+        let mut panes = vec![];
+        self.collect_panes(FocusPath::default(), area, &mut panes);
+        panes
+    }
+
+    fn collect_panes(
+        &self,
+        path: FocusPath,
+        area: PaneArea,
+        panes: &mut Vec<(FocusPath, PaneArea)>,
+    ) {
+        match self {
+            Layout::Pane(_) => panes.push((path, area)),
+            Layout::Split {
+                direction,
+                ratio,
+                first,
+                second,
+            } => {
+                let (first_area, second_area) = split_area(area, *direction, *ratio);
+                first.collect_panes(path.child(Step::first(*direction)), first_area, panes);
+                second.collect_panes(path.child(Step::second(*direction)), second_area, panes);
+            }
+        }
+    }
+
+    /// Splits the pane at `path` in two, moving its view to the second side.
+    /// Returns the path of the first side, which is the new focused pane.
+    pub fn split(&mut self, path: &FocusPath, direction: SplitDirection, view: View) -> FocusPath {
+        if let Some(node) = self.node_mut(path) {
+            *node = Layout::Split {
+                direction,
+                ratio: 0.5,
+                first: Box::new(Layout::Pane(None)),
+                second: Box::new(Layout::Pane(Some(Box::new(view)))),
+            };
+        }
+
+        path.child(Step::first(direction))
+    }
+
+    /// Removes the pane at `path`, giving its space to the other side of its
+    /// split. Returns the path of the pane to focus instead, or `None` if it is
+    /// the only pane.
+    pub fn close(&mut self, path: &FocusPath) -> Option<FocusPath> {
+        let parent = path.parent()?;
+        let closed_first = path.0.last()?.is_first();
+
+        let node = self.node_mut(&parent)?;
+        let Layout::Split { first, second, .. } = node else {
+            return None;
+        };
+        let remaining = if closed_first { second } else { first };
+        *node = mem::replace(remaining.as_mut(), Layout::Pane(None));
+
+        Some(node.first_pane(parent))
+    }
+
+    // the top left pane inside this node, which is at `path`
+    fn first_pane(&self, path: FocusPath) -> FocusPath {
+        match self {
+            Layout::Pane(_) => path,
+            Layout::Split {
+                direction, first, ..
+            } => first.first_pane(path.child(Step::first(*direction))),
+        }
+    }
+
+    /// Shares the space evenly between all panes, like vim's CTRL-W =.
+    pub fn equalize(&mut self) {
+        if let Layout::Split {
+            direction,
+            ratio,
+            first,
+            second,
+        } = self
+        {
+            let first_count = first.panes_across(*direction);
+            let second_count = second.panes_across(*direction);
+            *ratio = first_count as f64 / (first_count + second_count) as f64;
+
+            first.equalize();
+            second.equalize();
+        }
+    }
+
+    // how many panes are next to each other in `direction`
+    fn panes_across(&self, direction: SplitDirection) -> usize {
+        match self {
+            Layout::Pane(_) => 1,
+            Layout::Split {
+                direction: split_direction,
+                first,
+                second,
+                ..
+            } => {
+                let first_count = first.panes_across(direction);
+                let second_count = second.panes_across(direction);
+                if *split_direction == direction {
+                    first_count + second_count
+                } else {
+                    first_count.max(second_count)
+                }
+            }
+        }
+    }
+
+    /// Gives the pane at `path` as much space as possible in `direction`,
+    /// leaving the other panes in the way as small as they can be.
+    pub fn maximize(&mut self, path: &FocusPath, direction: SplitDirection) {
+        let mut node = self;
+        for step in &path.0 {
+            let Layout::Split {
+                direction: split_direction,
+                ratio,
+                first,
+                second,
+            } = node
+            else {
+                return;
+            };
+
+            if *split_direction == direction {
+                *ratio = if step.is_first() { 1.0 } else { 0.0 };
+            }
+            node = if step.is_first() { first } else { second };
+        }
+    }
+
+    /// The pane next to the one at `path` in the direction of `step`, picking
+    /// the one sharing the longest edge with it.
+    pub fn neighbor(&self, area: PaneArea, path: &FocusPath, step: Step) -> Option<FocusPath> {
+        let panes = self.panes(area);
+        let (_, from) = panes.iter().find(|(pane, _)| pane == path)?;
+
+        panes
+            .iter()
+            .filter(|(_, to)| match step {
+                Step::Left => to.right() == from.pos.x,
+                Step::Right => to.pos.x == from.right(),
+                Step::Top => to.bottom() == from.pos.y,
+                Step::Bottom => to.pos.y == from.bottom(),
+            })
+            .map(|(pane, to)| {
+                let direction = match step {
+                    Step::Left | Step::Right => SplitDirection::Horizontal,
+                    Step::Top | Step::Bottom => SplitDirection::Vertical,
+                };
+                (pane, from.overlap(to, direction))
+            })
+            .filter(|(_, overlap)| *overlap > 0)
+            .max_by_key(|(_, overlap)| *overlap)
+            .map(|(pane, _)| pane.clone())
+    }
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// divides `area` between the two sides of a split, leaving each at least one cell
+fn split_area(area: PaneArea, direction: SplitDirection, ratio: f64) -> (PaneArea, PaneArea) {
+    let total = match direction {
+        SplitDirection::Horizontal => area.size.w,
+        SplitDirection::Vertical => area.size.h,
+    };
+    let first_len = if total < 2 {
+        total
+    } else {
+        ((total as f64 * ratio).round() as usize).clamp(1, total - 1)
+    };
+    let second_len = total - first_len;
+
+    match direction {
+        SplitDirection::Horizontal => (
+            PaneArea {
+                pos: area.pos,
+                size: Size {
+                    w: first_len,
+                    h: area.size.h,
+                },
+            },
+            PaneArea {
+                pos: Position {
+                    x: area.pos.x + first_len,
+                    y: area.pos.y,
+                },
+                size: Size {
+                    w: second_len,
+                    h: area.size.h,
+                },
+            },
+        ),
+        SplitDirection::Vertical => (
+            PaneArea {
+                pos: area.pos,
+                size: Size {
+                    w: area.size.w,
+                    h: first_len,
+                },
+            },
+            PaneArea {
+                pos: Position {
+                    x: area.pos.x,
+                    y: area.pos.y + first_len,
+                },
+                size: Size {
+                    w: area.size.w,
+                    h: second_len,
+                },
+            },
+        ),
+    }
+}
@@ -0,0 +1,234 @@
+use std::ops::Range;
+
+use super::{
+    buffer::leading_whitespace,
+    width::{char_width_at, display_col},
+};
+
+// break indent never leaves less than this many cells for the text of a row
+const MIN_BREAK_INDENT_TEXT_WIDTH: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WrapMode {
+    Off,
+    SoftWrap,
+}
+
+/// Maps logical buffer lines to the visual rows they are displayed in.
+pub struct LineMap {
+    // None if lines are never wrapped
+    wrap_width: Option<usize>,
+    tab_width: usize,
+    // whether continuation rows are indented like the start of their line
+    break_indent: bool,
+    // cells taken by the showbreak marker at the start of continuation rows
+    show_break_width: usize,
+}
+
+impl LineMap {
+    pub fn new(
+        wrap_mode: WrapMode,
+        width: usize,
+        tab_width: usize,
+        break_indent: bool,
+        show_break_width: usize,
+    ) -> Self {
+        let wrap_width = match wrap_mode {
+            WrapMode::Off => None,
+            // a zero width would never make progress
+            WrapMode::SoftWrap => Some(width.max(1)),
+        };
+
+        Self {
+            wrap_width,
+            tab_width,
+            break_indent,
+            show_break_width,
+        }
+    }
+
+    /// Number of cells the continuation rows of `line` are indented by, which
+    /// is as wide as the leading whitespace of the line with break indent.
+    pub fn continuation_indent(&self, line: &str) -> usize {
+        let Some(width) = self.wrap_width.filter(|_| self.break_indent) else {
+            return 0;
+        };
+
+        let indent = leading_whitespace(line);
+        let indent_width = display_col(indent, indent.chars().count(), self.tab_width);
+        // keep some room for the text, or the rows would only be indentation
+        indent_width.min(width.saturating_sub(MIN_BREAK_INDENT_TEXT_WIDTH))
+    }
+
+    /// Number of cells before the text of the continuation rows of `line`,
+    /// for break indent and the showbreak marker. At least one cell is always
+    /// left for the text.
+    pub fn continuation_offset(&self, line: &str) -> usize {
+        let Some(width) = self.wrap_width else {
+            return 0;
+        };
+
+        (self.continuation_indent(line) + self.show_break_width).min(width - 1)
+    }
+
+    /// Splits `line` into the column ranges (in chars) of each of its visual rows.
+    ///
+    /// There is always at least one row, even for an empty line. Rows are broken
+    /// after whitespace where possible, otherwise in the middle of a word. The
+    /// width of a row is measured in cells, so wide characters and tabs count
+    /// for more than one. Continuation rows are narrower by their offset.
+    pub fn rows(&self, line: &str) -> Vec<Range<usize>> {
+        let chars: Vec<char> = line.chars().collect();
+
+        let Some(width) = self.wrap_width else {
+            return vec![Range {
+                start: 0,
+                end: chars.len(),
+            }];
+        };
+
+        let mut display_col = 0;
+        let widths: Vec<usize> = chars
+            .iter()
+            .map(|ch| {
+                let width = char_width_at(*ch, display_col, self.tab_width);
+                display_col += width;
+                width
+            })
+            .collect();
+
+        let continuation_width = width - self.continuation_offset(line);
+
+        let mut rows = vec![];
+        let mut start = 0;
+        let mut row_width = 0;
+        // where the current row can be broken, right after whitespace
+        let mut last_break = None;
+
+        for (index, ch) in chars.iter().enumerate() {
+            let row_limit = if rows.is_empty() {
+                width
+            } else {
+                continuation_width
+            };
+
+            if row_width + widths[index] > row_limit && index > start {
+                let end = last_break.unwrap_or(index);
+                rows.push(start..end);
+
+                start = end;
+                row_width = widths[start..index].iter().sum();
+                last_break = None;
+            }
+
+            row_width += widths[index];
+            if ch.is_whitespace() {
+                last_break = Some(index + 1);
+            }
+        }
+
+        rows.push(start..chars.len());
+        rows
+    }
+
+    /// Index of the visual row of `line` that `col` is displayed in.
+    pub fn row_of(&self, line: &str, col: usize) -> usize {
+        let rows = self.rows(line);
+
+        rows.iter()
+            .position(|row| col < row.end)
+            .unwrap_or(rows.len() - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wrapped(width: usize) -> LineMap {
+        LineMap::new(WrapMode::SoftWrap, width, 8, false, 0)
+    }
+
+    #[test]
+    fn rows_keep_lines_whole_without_wrap() {
+        let line_map = LineMap::new(WrapMode::Off, 4, 8, false, 0);
+
+        assert_eq!(line_map.rows("a long line"), [Range { start: 0, end: 11 }]);
+        assert_eq!(line_map.rows(""), [Range { start: 0, end: 0 }]);
+    }
+
+    #[test]
+    fn rows_break_after_whitespace() {
+        assert_eq!(wrapped(8).rows("one two three"), [0..8, 8..13]);
+        assert_eq!(wrapped(4).rows("abcdefghij"), [0..4, 4..8, 8..10]);
+    }
+
+    #[test]
+    fn rows_measure_wide_chars_and_tabs_in_cells() {
+        // each of these takes two cells, so only two fit in a row of five
+        assert_eq!(wrapped(5).rows("漢字漢字"), [0..2, 2..4]);
+        // the tab takes the rest of the first eight cells, leaving no room
+        // for the word after it
+        assert_eq!(wrapped(10).rows("a\tbcdef"), [0..2, 2..7]);
+    }
+
+    #[test]
+    fn row_of_finds_the_row_of_a_column() {
+        let line_map = wrapped(4);
+
+        assert_eq!(line_map.row_of("abcdefghij", 3), 0);
+        assert_eq!(line_map.row_of("abcdefghij", 4), 1);
+        // past the end is on the last row
+        assert_eq!(line_map.row_of("abcdefghij", 10), 2);
+    }
+
+    #[test]
+    fn break_indent_narrows_continuation_rows() {
+        let line_map = LineMap::new(WrapMode::SoftWrap, 28, 8, true, 0);
+        let line = format!("    {}", "abcd ".repeat(10));
+
+        assert_eq!(line_map.continuation_indent(&line), 4);
+        // 28 cells for the first row, 24 after the indent for the others
+        assert_eq!(line_map.rows(&line), [0..24, 24..44, 44..54]);
+    }
+
+    #[test]
+    fn break_indent_leaves_room_for_the_text() {
+        let line_map = LineMap::new(WrapMode::SoftWrap, 30, 8, true, 0);
+        let line = format!("{}x", " ".repeat(16));
+
+        assert_eq!(line_map.continuation_indent(&line), 10);
+        // and tabs count for the cells they take
+        assert_eq!(line_map.continuation_indent("\tx"), 8);
+    }
+
+    #[test]
+    fn break_indent_does_nothing_without_wrap() {
+        let line_map = LineMap::new(WrapMode::Off, 28, 8, true, 0);
+
+        assert_eq!(line_map.continuation_indent("    x"), 0);
+        assert_eq!(
+            LineMap::new(WrapMode::SoftWrap, 28, 8, false, 0).continuation_indent("    x"),
+            0
+        );
+    }
+
+    #[test]
+    fn showbreak_narrows_continuation_rows_after_the_indent() {
+        let line_map = LineMap::new(WrapMode::SoftWrap, 30, 8, true, 2);
+
+        assert_eq!(line_map.continuation_offset("    x"), 6);
+        assert_eq!(
+            LineMap::new(WrapMode::SoftWrap, 6, 8, false, 2).rows("abcdefghij"),
+            [0..6, 6..10]
+        );
+    }
+
+    #[test]
+    fn continuation_rows_keep_a_cell_for_the_text() {
+        let line_map = LineMap::new(WrapMode::SoftWrap, 4, 8, false, 10);
+
+        assert_eq!(line_map.continuation_offset("abcdef"), 3);
+        assert_eq!(line_map.rows("abcdef"), [0..4, 4..5, 5..6]);
+    }
+}
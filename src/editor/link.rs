@@ -0,0 +1,41 @@
+use std::ops::Range;
+
+// what a web link starts with
+const LINK_SCHEMES: &[&str] = &["https://", "http://"];
+
+// left off the end of a link, as they more likely belong to the text around
+// it, like the `.` ending a sentence or the `)` closing a parenthesis
+const TRAILING_PUNCTUATION: &[char] =
+    &['.', ',', ';', ':', '!', '?', ')', ']', '}', '\'', '"', '>'];
+
+/// The columns of the web links in `line`, like `https://example.com`.
+pub fn find_links(line: &str) -> Vec<Range<usize>> {
+    let mut links = vec![];
+    // in bytes
+    let mut search_start = 0;
+
+    while let Some((start, scheme)) = LINK_SCHEMES
+        .iter()
+        .filter_map(|scheme| {
+            let start = line[search_start..].find(scheme)?;
+            Some((search_start + start, scheme))
+        })
+        .min_by_key(|(start, _)| *start)
+    {
+        let rest = &line[start..];
+        let len = rest
+            .find(|ch: char| ch.is_whitespace() || matches!(ch, '<' | '"' | '`'))
+            .unwrap_or(rest.len());
+        let link = rest[..len].trim_end_matches(TRAILING_PUNCTUATION);
+
+        // a scheme on its own isn't a link
+        if link.len() > scheme.len() {
+            let start_col = line[..start].chars().count();
+            links.push(start_col..start_col + link.chars().count());
+        }
+
+        search_start = start + len;
+    }
+
+    links
+}
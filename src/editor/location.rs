@@ -0,0 +1,120 @@
+use std::path::Path;
+
+/// Where to go in a file after opening it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Jump {
+    /// a line and column, counted from 0
+    Position { line: usize, col: usize },
+    /// the first match of a search
+    Search(String),
+}
+
+/// A file to open, written as `file`, `file:line`, `file:line:col` (lines and
+/// columns counted from 1, like in compiler errors) or `file:/pattern`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileLocation {
+    pub path: String,
+    pub jump: Option<Jump>,
+}
+
+impl FileLocation {
+    pub fn parse(arg: &str) -> Self {
+        // file names can contain colons, so an existing file is taken as it is
+        if Path::new(arg).exists() {
+            return Self::without_jump(arg);
+        }
+
+        if let Some((path, pattern)) = arg.split_once(":/") {
+            if !path.is_empty() && !pattern.is_empty() {
+                return Self {
+                    path: path.to_string(),
+                    jump: Some(Jump::Search(pattern.to_string())),
+                };
+            }
+        }
+
+        let mut parts = arg.rsplitn(3, ':');
+        let numbers: Vec<usize> = parts
+            .by_ref()
+            .take(2)
+            .map_while(|part| part.parse().ok())
+            .collect();
+
+        let (path, line, col) = match numbers[..] {
+            [col, line] => (parts.next(), line, col),
+            [line] => (arg.rsplit_once(':').map(|(path, _)| path), line, 1),
+            _ => (None, 0, 0),
+        };
+
+        match path {
+            Some(path) if !path.is_empty() => Self {
+                path: path.to_string(),
+                jump: Some(Jump::Position {
+                    line: line.saturating_sub(1),
+                    col: col.saturating_sub(1),
+                }),
+            },
+            _ => Self::without_jump(arg),
+        }
+    }
+
+    fn without_jump(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            jump: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn position(path: &str, line: usize, col: usize) -> FileLocation {
+        FileLocation {
+            path: path.to_string(),
+            jump: Some(Jump::Position { line, col }),
+        }
+    }
+
+    #[test]
+    fn parse_reads_lines_and_columns_from_1() {
+        assert_eq!(FileLocation::parse("main.rs:10"), position("main.rs", 9, 0));
+        assert_eq!(
+            FileLocation::parse("main.rs:10:5"),
+            position("main.rs", 9, 4)
+        );
+        // a colon in the file name is kept
+        assert_eq!(FileLocation::parse("a:b.rs:3"), position("a:b.rs", 2, 0));
+    }
+
+    #[test]
+    fn parse_reads_search_patterns() {
+        assert_eq!(
+            FileLocation::parse("main.rs:/fn main"),
+            FileLocation {
+                path: "main.rs".to_string(),
+                jump: Some(Jump::Search("fn main".to_string())),
+            }
+        );
+    }
+
+    #[test]
+    fn parse_keeps_anything_else_as_the_path() {
+        for arg in ["main.rs", "main.rs:", "main.rs:x", ":10", "main.rs:/"] {
+            assert_eq!(FileLocation::parse(arg), FileLocation::without_jump(arg));
+        }
+    }
+
+    #[test]
+    fn parse_keeps_existing_files_whole() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes:12");
+        fs::write(&path, "").unwrap();
+        let arg = path.to_str().unwrap();
+
+        assert_eq!(FileLocation::parse(arg), FileLocation::without_jump(arg));
+    }
+}
@@ -0,0 +1,43 @@
+use std::collections::HashMap;
+
+use crate::base::Position;
+
+/// Named positions in a buffer, set with `m` and jumped to with `'` and `` ` ``.
+pub struct Marks {
+    marks: HashMap<char, Position<usize>>,
+}
+
+impl Marks {
+    pub fn new() -> Self {
+        Self {
+            marks: HashMap::new(),
+        }
+    }
+
+    pub fn get(&self, mark: char) -> Option<Position<usize>> {
+        self.marks.get(&mark).copied()
+    }
+
+    pub fn set(&mut self, mark: char, position: Position<usize>) {
+        self.marks.insert(mark, position);
+    }
+
+    /// Keeps the marks on the same text after the lines in `start..old_end`
+    /// were replaced by the lines in `start..new_end`.
+    pub fn adjust(&mut self, start: usize, old_end: usize, new_end: usize) {
+        self.marks.values_mut().for_each(|position| {
+            if position.y >= old_end {
+                position.y = position.y + new_end - old_end;
+            } else if position.y >= new_end {
+                // the line was removed, so the mark moves to where it was
+                position.y = new_end.saturating_sub(1).max(start);
+            }
+        });
+    }
+}
+
+impl Default for Marks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
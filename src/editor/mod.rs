@@ -0,0 +1,54 @@
+mod buffer;
+mod buffer_list;
+mod command;
+mod conceal;
+mod confirm;
+mod cursor_shape;
+mod editorconfig;
+mod filetype;
+mod highlight;
+mod history;
+mod indent;
+mod jump_list;
+mod layout;
+mod line_map;
+mod link;
+mod location;
+mod marks;
+mod mode;
+mod notification;
+mod operator;
+mod options;
+mod pairs;
+mod registers;
+mod search;
+mod selection;
+mod substitute;
+mod tab_bar;
+mod text_object;
+mod theme;
+mod treesitter;
+mod view;
+mod watcher;
+mod width;
+
+pub use buffer::{Buffer, DiskState};
+pub use buffer_list::BufferList;
+pub use command::execute_command;
+pub use confirm::Confirm;
+pub use filetype::FileType;
+pub use jump_list::{JumpEntry, JumpList};
+pub use layout::{FocusPath, Layout, PaneArea, SplitDirection, Step};
+pub use location::{FileLocation, Jump};
+pub use mode::Mode;
+pub use notification::{Notification, NotificationLevel};
+pub use operator::{MotionKind, Operator};
+pub use registers::{RegisterText, Registers, CLIPBOARD_REGISTER, UNNAMED_REGISTER};
+pub use search::CharFind;
+pub use selection::SelectionKind;
+pub use tab_bar::{layout_tabs, Tab};
+pub use text_object::TextObject;
+pub use theme::Theme;
+pub use view::View;
+pub use watcher::FileWatcher;
+pub use width::char_width;
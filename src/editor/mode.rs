@@ -0,0 +1,17 @@
+use super::Operator;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Normal,
+    Insert,
+    /// typing over the text instead of before it, like after R
+    Replace,
+    Command,
+    Search,
+    Visual,
+    VisualLine,
+    /// selecting a rectangle of columns, like after CTRL-V
+    VisualBlock,
+    /// waiting for the motion of an operator, like after d
+    OperatorPending(Operator),
+}
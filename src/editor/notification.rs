@@ -0,0 +1,32 @@
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationLevel {
+    Info,
+    Warning,
+    Error,
+}
+
+/// A message shown above the command line for a while, which then goes away
+/// on its own.
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub message: String,
+    pub level: NotificationLevel,
+    pub expires_at: Instant,
+}
+
+impl Notification {
+    /// A notification shown for `duration` from now.
+    pub fn new(message: String, level: NotificationLevel, duration: Duration) -> Self {
+        Self {
+            message,
+            level,
+            expires_at: Instant::now() + duration,
+        }
+    }
+
+    pub fn is_expired(&self, now: Instant) -> bool {
+        now >= self.expires_at
+    }
+}
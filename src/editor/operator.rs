@@ -0,0 +1,69 @@
+/// What is done to the text a motion moves over, like vim's d, y and c.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Delete,
+    Yank,
+    /// deletes and starts insert mode in its place
+    Change,
+    /// rewraps lines to the text width, like gq
+    Format,
+    Indent,
+    Dedent,
+    /// comments lines out, or uncomments them if they all are, like gc
+    Comment,
+}
+
+impl Operator {
+    /// The operator typed as `key` in normal mode, where gq is typed as `q`
+    /// after a pending g.
+    pub fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "d" => Some(Self::Delete),
+            "y" => Some(Self::Yank),
+            "c" => Some(Self::Change),
+            ">" => Some(Self::Indent),
+            "<" => Some(Self::Dedent),
+            _ => None,
+        }
+    }
+
+    /// The key that, typed right after the operator, applies it to whole
+    /// lines, like dd and gqq.
+    pub fn line_key(&self) -> &'static str {
+        match self {
+            Self::Delete => "d",
+            Self::Yank => "y",
+            Self::Change => "c",
+            Self::Format => "q",
+            Self::Indent => ">",
+            Self::Dedent => "<",
+            Self::Comment => "c",
+        }
+    }
+
+    /// Whether the operator is typed after a g, like gq, so that it can also
+    /// be doubled with another g, like gqgq.
+    pub fn is_typed_after_g(&self) -> bool {
+        matches!(self, Self::Format | Self::Comment)
+    }
+
+    /// Whether the operator works on whole lines, whatever the motion.
+    pub fn is_linewise(&self) -> bool {
+        matches!(
+            self,
+            Self::Format | Self::Indent | Self::Dedent | Self::Comment
+        )
+    }
+}
+
+/// How much of the text between the cursor and where a motion moves it an
+/// operator applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MotionKind {
+    /// up to but not including the later end, like h and 0
+    Exclusive,
+    /// up to and including the later end, like $ and f
+    Inclusive,
+    /// every line between the ends, like j and G
+    Linewise,
+}
@@ -0,0 +1,227 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use crate::base::UnderlineStyle;
+
+use super::{cursor_shape::CursorShapes, line_map::WrapMode};
+
+const DEFAULT_SHIFT_WIDTH: usize = 4;
+const DEFAULT_INDENT_TRIGGERS: &[char] = &['{', '(', '[', ':'];
+const DEFAULT_MAX_BLANK_LINES: usize = 1;
+const DEFAULT_TAB_WIDTH: usize = 8;
+const DEFAULT_TEXT_WIDTH: usize = 79;
+
+/// What separates lines when a buffer is saved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+    Cr,
+}
+
+impl LineEnding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+            LineEnding::Cr => "\r",
+        }
+    }
+}
+
+/// Where the previous version of a file is kept when it is saved over.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BackupMode {
+    None,
+    /// next to the file, with `~` after its name
+    Tilde,
+    /// in the directory, named after the file and when it was last modified
+    Directory(PathBuf),
+}
+
+pub struct BufferOptions {
+    /// number of cells between tab stops
+    pub tab_width: usize,
+    /// indent with spaces instead of tabs
+    pub expand_tab: bool,
+    /// number of cells for each level of indentation
+    pub shift_width: usize,
+    /// backspace in leading indentation removes a whole level
+    pub smart_backspace: bool,
+    /// a new line is indented one level deeper after a line ending with these
+    pub indent_triggers: Vec<char>,
+    /// longer runs of blank lines are collapsed when trimming blank lines
+    pub max_blank_lines: usize,
+    /// the longest lines are made when formatting with gq
+    pub text_width: usize,
+    pub trim_blank_lines_on_save: bool,
+    pub trim_trailing_whitespace_on_save: bool,
+    pub line_ending: LineEnding,
+    /// end the last line with a line ending too
+    pub insert_final_newline: bool,
+    /// start the file with a UTF-8 byte order mark
+    pub bom: bool,
+    pub backup: BackupMode,
+}
+
+impl Default for BufferOptions {
+    fn default() -> Self {
+        Self {
+            tab_width: DEFAULT_TAB_WIDTH,
+            expand_tab: true,
+            shift_width: DEFAULT_SHIFT_WIDTH,
+            smart_backspace: true,
+            indent_triggers: DEFAULT_INDENT_TRIGGERS.to_vec(),
+            max_blank_lines: DEFAULT_MAX_BLANK_LINES,
+            text_width: DEFAULT_TEXT_WIDTH,
+            trim_blank_lines_on_save: false,
+            trim_trailing_whitespace_on_save: false,
+            line_ending: LineEnding::Lf,
+            insert_final_newline: true,
+            bom: false,
+            backup: BackupMode::None,
+        }
+    }
+}
+
+impl BufferOptions {
+    /// Text inserted for one level of indentation.
+    pub fn indent_unit(&self) -> String {
+        if self.expand_tab {
+            " ".repeat(self.shift_width)
+        } else {
+            "\t".to_string()
+        }
+    }
+
+    /// Where to copy `original` before saving over it, if anywhere.
+    pub fn backup_path(&self, original: &Path) -> Option<PathBuf> {
+        let file_name = original.file_name()?.to_string_lossy();
+
+        match &self.backup {
+            BackupMode::None => None,
+            BackupMode::Tilde => Some(original.with_file_name(format!("{file_name}~"))),
+            BackupMode::Directory(dir) => {
+                // every version gets its own backup, instead of replacing the last one
+                let modified = fs::metadata(original).and_then(|metadata| metadata.modified());
+                let timestamp = modified
+                    .ok()
+                    .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                    .map_or(0, |duration| duration.as_secs());
+
+                Some(dir.join(format!("{file_name}.{timestamp}~")))
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ViewOptions {
+    /// move to the match while typing a search, like vim's incsearch
+    pub incsearch: bool,
+    pub wrap: WrapMode,
+    /// indent wrapped rows like the start of their line, like vim's breakindent
+    pub break_indent: bool,
+    /// drawn at the start of wrapped rows, like vim's showbreak
+    pub show_break: String,
+    /// how markup like markdown's `**` is concealed outside of the cursor
+    /// line, like vim's conceallevel, where 0 shows it as it is
+    pub conceal_level: u8,
+    /// let the cursor move past the end of lines, padding them with spaces
+    /// when typing there, like vim's virtualedit=all
+    pub virtual_edit: bool,
+    /// cells from the start of the text where a ruler is drawn, like vim's
+    /// colorcolumn
+    pub color_columns: Vec<usize>,
+    /// show line numbers in a gutter
+    pub number: bool,
+    /// draw the cell grid, to debug font metrics and alignment
+    pub debug_grid: bool,
+    /// darken the view when it isn't focused
+    pub dim_inactive: bool,
+    /// show where the view is in the buffer on the right edge
+    pub scrollbar: bool,
+    /// list the open buffers in a bar above the view
+    pub show_tab_line: bool,
+    /// rows kept visible above and below the cursor, like vim's scrolloff
+    pub scroll_off: usize,
+    /// cells kept visible left and right of the cursor when lines aren't
+    /// wrapped, like vim's sidescrolloff
+    pub side_scroll_off: usize,
+    /// allow scrolling until the last line is at the top of the view
+    pub scroll_past_end: bool,
+    /// animate scrolling instead of jumping to the new position
+    pub smooth_scroll: bool,
+    pub cursor_shapes: CursorShapes,
+    /// how web links in the text are underlined, if they are
+    pub link_underline: Option<UnderlineStyle>,
+    /// how long the cursor is shown and then hidden when blinking, in
+    /// milliseconds, where 0 doesn't blink
+    pub cursor_blink_ms: u64,
+}
+
+impl Default for ViewOptions {
+    fn default() -> Self {
+        Self {
+            incsearch: true,
+            wrap: WrapMode::Off,
+            break_indent: false,
+            show_break: String::new(),
+            conceal_level: 0,
+            virtual_edit: false,
+            color_columns: vec![],
+            number: false,
+            debug_grid: false,
+            dim_inactive: true,
+            scrollbar: true,
+            show_tab_line: false,
+            scroll_off: 0,
+            side_scroll_off: 0,
+            scroll_past_end: false,
+            smooth_scroll: true,
+            cursor_shapes: CursorShapes::default(),
+            link_underline: Some(UnderlineStyle::Straight),
+            cursor_blink_ms: 530,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backup_path(backup: BackupMode, original: &Path) -> Option<PathBuf> {
+        let options = BufferOptions {
+            backup,
+            ..BufferOptions::default()
+        };
+        options.backup_path(original)
+    }
+
+    #[test]
+    fn backup_path_follows_the_backup_mode() {
+        let original = Path::new("/src/main.rs");
+
+        assert_eq!(backup_path(BackupMode::None, original), None);
+        assert_eq!(
+            backup_path(BackupMode::Tilde, original),
+            Some(PathBuf::from("/src/main.rs~"))
+        );
+    }
+
+    #[test]
+    fn backups_in_a_directory_are_named_after_the_modified_time() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let modified = fs::metadata(file.path()).unwrap().modified().unwrap();
+        let timestamp = modified.duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let name = file.path().file_name().unwrap().to_string_lossy();
+
+        assert_eq!(
+            backup_path(BackupMode::Directory("/backups".into()), file.path()),
+            Some(PathBuf::from(format!("/backups/{name}.{timestamp}~")))
+        );
+    }
+}
@@ -0,0 +1,72 @@
+const DEFAULT_PAIRS: &[(char, char)] =
+    &[('(', ')'), ('[', ']'), ('{', '}'), ('"', '"'), ('\'', '\'')];
+
+/// Characters that are automatically closed when typed in insert mode.
+pub struct PairTable {
+    pairs: Vec<(char, char)>,
+}
+
+impl PairTable {
+    pub fn new() -> Self {
+        Self {
+            pairs: DEFAULT_PAIRS.to_vec(),
+        }
+    }
+
+    pub fn closing(&self, opening: char) -> Option<char> {
+        self.pairs
+            .iter()
+            .find(|(open, _)| *open == opening)
+            .map(|(_, close)| *close)
+    }
+
+    /// What typing `opening` right after `before` should close it with, if
+    /// anything. A quote after a letter or digit is an apostrophe or ends a
+    /// word, like in don't, so it is left on its own.
+    pub fn closing_after(&self, opening: char, before: Option<char>) -> Option<char> {
+        let closing = self.closing(opening)?;
+        let is_quote = opening == closing;
+        if is_quote && before.is_some_and(char::is_alphanumeric) {
+            return None;
+        }
+        Some(closing)
+    }
+
+    pub fn is_closing(&self, ch: char) -> bool {
+        self.pairs.iter().any(|(_, close)| *close == ch)
+    }
+}
+
+impl Default for PairTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn closing_finds_the_other_half_of_a_pair() {
+        let pairs = PairTable::new();
+
+        assert_eq!(pairs.closing('('), Some(')'));
+        assert_eq!(pairs.closing('"'), Some('"'));
+        assert_eq!(pairs.closing(')'), None);
+        assert!(pairs.is_closing(']'));
+        assert!(!pairs.is_closing('['));
+    }
+
+    #[test]
+    fn quotes_after_a_word_character_are_not_closed() {
+        let pairs = PairTable::new();
+
+        assert_eq!(pairs.closing_after('\'', Some('n')), None);
+        assert_eq!(pairs.closing_after('"', Some('1')), None);
+        assert_eq!(pairs.closing_after('\'', Some(' ')), Some('\''));
+        assert_eq!(pairs.closing_after('"', None), Some('"'));
+        // brackets are closed after anything
+        assert_eq!(pairs.closing_after('(', Some('f')), Some(')'));
+    }
+}
@@ -0,0 +1,332 @@
+use std::collections::HashMap;
+
+use crate::base::AppEvent;
+
+/// The register that yanks and deletes go to when none is named.
+pub const UNNAMED_REGISTER: char = '"';
+/// The register holding the text of the last yank.
+const YANK_REGISTER: char = '0';
+/// The register backed by the system clipboard.
+pub const CLIPBOARD_REGISTER: char = '+';
+
+// contents are cut to this many characters when listed
+const MAX_LISTED_LEN: usize = 60;
+
+/// Whether the text in a register was taken from whole lines, which decides
+/// whether it is pasted as whole lines or inside the cursor line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegisterKind {
+    Charwise,
+    Linewise,
+}
+
+/// The text in a register. Linewise text ends with a newline, but text that
+/// ends with a newline isn't necessarily linewise, like a yank up to the end
+/// of a line in visual mode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegisterText {
+    pub text: String,
+    pub kind: RegisterKind,
+}
+
+impl RegisterText {
+    pub fn charwise(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            kind: RegisterKind::Charwise,
+        }
+    }
+
+    pub fn linewise(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            kind: RegisterKind::Linewise,
+        }
+    }
+}
+
+/// Named storage that outlives a single command, such as recorded macros and
+/// yanked text.
+///
+/// Text registers `a`-`z` are replaced when written to, while writing to
+/// `A`-`Z` appends to the lowercase register instead.
+pub struct Registers {
+    macros: HashMap<char, Vec<AppEvent>>,
+    texts: HashMap<char, RegisterText>,
+    // not every platform has a clipboard, in which case `+` is a normal register
+    clipboard: Option<arboard::Clipboard>,
+}
+
+impl Registers {
+    pub fn new() -> Self {
+        Self {
+            macros: HashMap::new(),
+            texts: HashMap::new(),
+            clipboard: arboard::Clipboard::new().ok(),
+        }
+    }
+
+    pub fn is_valid(register: char) -> bool {
+        register.is_ascii_alphabetic()
+            || [UNNAMED_REGISTER, YANK_REGISTER, CLIPBOARD_REGISTER].contains(&register)
+    }
+
+    pub fn get_macro(&self, register: char) -> Option<&[AppEvent]> {
+        self.macros.get(&register).map(Vec::as_slice)
+    }
+
+    pub fn set_macro(&mut self, register: char, events: Vec<AppEvent>) {
+        self.macros.insert(register, events);
+    }
+
+    pub fn get(&self, register: char) -> Option<&RegisterText> {
+        self.texts.get(&register.to_ascii_lowercase())
+    }
+
+    pub fn set(&mut self, register: char, text: RegisterText) {
+        if register.is_ascii_uppercase() {
+            let register = register.to_ascii_lowercase();
+            let Some(existing) = self.texts.get_mut(&register) else {
+                self.texts.insert(register, text);
+                return;
+            };
+
+            // appending lines to text within a line starts them on a line of
+            // their own, and makes all of it linewise
+            if text.kind == RegisterKind::Linewise {
+                if existing.kind == RegisterKind::Charwise {
+                    existing.text.push('\n');
+                }
+                existing.kind = RegisterKind::Linewise;
+            }
+            existing.text.push_str(&text.text);
+            return;
+        }
+
+        if register == CLIPBOARD_REGISTER {
+            if let Some(clipboard) = &mut self.clipboard {
+                // if the clipboard can't be written, the text is still kept here
+                let _ = clipboard.set_text(text.text.as_str());
+            }
+        }
+
+        self.texts.insert(register, text);
+    }
+
+    /// Picks up text copied to the clipboard by other programs, so that `get`
+    /// sees the current clipboard contents.
+    pub fn sync_clipboard(&mut self) {
+        if let Some(text) = self
+            .clipboard
+            .as_mut()
+            .and_then(|clipboard| clipboard.get_text().ok())
+        {
+            // other programs don't say how the text was taken, so whole lines
+            // are told apart by the newline they end with, like vim does
+            let text = if text.ends_with('\n') {
+                RegisterText::linewise(text)
+            } else {
+                RegisterText::charwise(text)
+            };
+            self.texts.insert(CLIPBOARD_REGISTER, text);
+        }
+    }
+
+    /// Lists the text registers and their contents, like vim's :registers.
+    /// Only the registers in `filter` are listed, unless it is empty.
+    pub fn describe(&self, filter: &str) -> String {
+        let mut registers: Vec<char> = self
+            .texts
+            .keys()
+            .copied()
+            .filter(|register| filter.is_empty() || filter.contains(*register))
+            .collect();
+        // the unnamed register first, then the rest in order
+        registers.sort_by_key(|&register| (register != UNNAMED_REGISTER, register));
+
+        let mut lines = vec!["--- Registers ---".to_string()];
+        lines.extend(registers.iter().map(|register| {
+            let text = &self.texts[register].text;
+            format!("\"{register}   {}", listed_contents(text))
+        }));
+        lines.join("\n")
+    }
+
+    /// Stores yanked `text` in `register` (if given), the unnamed register and
+    /// the yank register.
+    pub fn yank(&mut self, register: Option<char>, text: RegisterText) {
+        self.set(YANK_REGISTER, text.clone());
+        self.delete(register, text);
+    }
+
+    /// Stores deleted `text` in `register` (if given) and the unnamed register.
+    pub fn delete(&mut self, register: Option<char>, text: RegisterText) {
+        if let Some(register) = register.filter(|&register| register != UNNAMED_REGISTER) {
+            self.set(register, text.clone());
+        }
+        self.set(UNNAMED_REGISTER, text);
+    }
+}
+
+// `text` on a single line, with control characters like newlines shown as ^J
+// and anything past MAX_LISTED_LEN cut off
+fn listed_contents(text: &str) -> String {
+    let mut listed = String::new();
+
+    for (index, ch) in text.chars().enumerate() {
+        if index == MAX_LISTED_LEN {
+            listed.push_str("...");
+            break;
+        }
+
+        match ch {
+            '\x7f' => listed.push_str("^?"),
+            ch if ch.is_ascii_control() => {
+                listed.push('^');
+                listed.push((ch as u8 + b'@') as char);
+            }
+            ch => listed.push(ch),
+        }
+    }
+
+    listed
+}
+
+impl Default for Registers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resize() -> AppEvent {
+        AppEvent::ResizeEvent
+    }
+
+    #[test]
+    fn macros_are_kept_per_register() {
+        let mut registers = Registers::new();
+
+        registers.set_macro('q', vec![resize(), resize()]);
+        registers.set_macro('w', vec![resize()]);
+
+        assert_eq!(registers.get_macro('q').map(<[_]>::len), Some(2));
+        assert_eq!(registers.get_macro('w').map(<[_]>::len), Some(1));
+        assert!(registers.get_macro('e').is_none());
+    }
+
+    #[test]
+    fn recording_again_replaces_the_macro() {
+        let mut registers = Registers::new();
+
+        registers.set_macro('q', vec![resize(), resize()]);
+        registers.set_macro('q', vec![]);
+
+        assert_eq!(registers.get_macro('q').map(<[_]>::len), Some(0));
+    }
+
+    #[test]
+    fn macros_are_kept_apart_from_text() {
+        let mut registers = Registers::new();
+
+        registers.set_macro('q', vec![resize(), resize()]);
+        registers.set('q', RegisterText::charwise("text"));
+
+        assert_eq!(registers.get_macro('q').map(<[_]>::len), Some(2));
+        assert_eq!(registers.get('q'), Some(&RegisterText::charwise("text")));
+    }
+
+    #[test]
+    fn is_valid_takes_letters_and_the_special_registers() {
+        assert!(Registers::is_valid('a'));
+        assert!(Registers::is_valid('Z'));
+        assert!(Registers::is_valid(UNNAMED_REGISTER));
+        assert!(Registers::is_valid(CLIPBOARD_REGISTER));
+        assert!(!Registers::is_valid('1'));
+        assert!(!Registers::is_valid('@'));
+    }
+
+    #[test]
+    fn yank_fills_the_unnamed_and_yank_registers() {
+        let mut registers = Registers::new();
+
+        registers.yank(Some('a'), RegisterText::charwise("text"));
+        registers.delete(None, RegisterText::linewise("gone\n"));
+
+        assert_eq!(registers.get('a'), Some(&RegisterText::charwise("text")));
+        assert_eq!(
+            registers.get(YANK_REGISTER),
+            Some(&RegisterText::charwise("text"))
+        );
+        assert_eq!(
+            registers.get(UNNAMED_REGISTER),
+            Some(&RegisterText::linewise("gone\n"))
+        );
+    }
+
+    #[test]
+    fn uppercase_registers_append() {
+        let mut registers = Registers::new();
+
+        registers.set('a', RegisterText::charwise("one"));
+        registers.set('A', RegisterText::charwise(" two"));
+
+        assert_eq!(registers.get('a'), Some(&RegisterText::charwise("one two")));
+        assert_eq!(registers.get('A'), Some(&RegisterText::charwise("one two")));
+        // appending to an empty register takes the text as it is
+        registers.set('B', RegisterText::linewise("line\n"));
+        assert_eq!(registers.get('b'), Some(&RegisterText::linewise("line\n")));
+    }
+
+    #[test]
+    fn appending_lines_makes_the_register_linewise() {
+        let mut registers = Registers::new();
+
+        registers.set('a', RegisterText::charwise("one"));
+        registers.set('A', RegisterText::linewise("two\n"));
+
+        assert_eq!(
+            registers.get('a'),
+            Some(&RegisterText::linewise("one\ntwo\n"))
+        );
+    }
+
+    #[test]
+    fn text_ending_with_a_newline_keeps_its_kind() {
+        let mut registers = Registers::new();
+
+        registers.yank(None, RegisterText::charwise("end\n"));
+
+        assert_eq!(
+            registers.get(UNNAMED_REGISTER).map(|text| text.kind),
+            Some(RegisterKind::Charwise)
+        );
+    }
+
+    #[test]
+    fn describe_lists_the_unnamed_register_first() {
+        let mut registers = Registers::new();
+        registers.set('b', RegisterText::linewise("line\n"));
+        registers.delete(Some('a'), RegisterText::charwise("x\ty"));
+
+        assert_eq!(
+            registers.describe(""),
+            "--- Registers ---\n\"\"   x^Iy\n\"a   x^Iy\n\"b   line^J"
+        );
+        assert_eq!(registers.describe("b"), "--- Registers ---\n\"b   line^J");
+    }
+
+    #[test]
+    fn listed_contents_are_cut_short() {
+        let text = "a".repeat(MAX_LISTED_LEN + 1);
+
+        assert_eq!(
+            listed_contents(&text),
+            format!("{}...", "a".repeat(MAX_LISTED_LEN))
+        );
+        assert_eq!(listed_contents("\x7f"), "^?");
+    }
+}
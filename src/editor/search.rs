@@ -0,0 +1,204 @@
+use crate::base::Position;
+
+use super::Buffer;
+
+// brackets that % jumps between, opening first
+const BRACKETS: &[(char, char)] = &[('(', ')'), ('[', ']'), ('{', '}')];
+
+/// Returns the columns (in chars) where `query` starts in `line`.
+pub fn find_in_line(line: &str, query: &str) -> Vec<usize> {
+    if query.is_empty() {
+        return vec![];
+    }
+
+    line.match_indices(query)
+        .map(|(index, _)| line[..index].chars().count())
+        .collect()
+}
+
+/// Finds the first match of `query` after `from`, wrapping around to the
+/// start of the buffer if needed.
+pub fn find_next(buffer: &Buffer, query: &str, from: Position<usize>) -> Option<Position<usize>> {
+    let line_count = buffer.line_count();
+
+    (0..=line_count).find_map(|offset| {
+        let line = (from.y + offset) % line_count;
+        let cols = find_in_line(buffer.line(line)?, query);
+
+        let col = match offset {
+            // on the starting line, only matches after `from` count...
+            0 => cols.into_iter().find(|col| *col > from.x),
+            // ...unless we wrapped all the way around to it
+            _ if offset == line_count => cols.into_iter().find(|col| *col <= from.x),
+            _ => cols.into_iter().next(),
+        }?;
+
+        Some(Position { x: col, y: line })
+    })
+}
+
+/// Finds the bracket matching the one at `pos`, forward for an opening
+/// bracket and backward for a closing one, skipping over pairs nested between
+/// them.
+pub fn find_matching_bracket(buffer: &Buffer, pos: Position<usize>) -> Option<Position<usize>> {
+    let ch = buffer.line(pos.y)?.chars().nth(pos.x)?;
+    let (this, other, forward) = BRACKETS.iter().find_map(|&(open, close)| {
+        if ch == open {
+            Some((open, close, true))
+        } else if ch == close {
+            Some((close, open, false))
+        } else {
+            None
+        }
+    })?;
+
+    find_unmatched(chars_from(buffer, pos, forward).skip(1), other, this)
+}
+
+/// Finds the `open` and `close` brackets around `pos`, skipping over pairs
+/// nested inside them. A bracket at `pos` counts as being around it.
+pub fn find_enclosing_pair(
+    buffer: &Buffer,
+    pos: Position<usize>,
+    open: char,
+    close: char,
+) -> Option<(Position<usize>, Position<usize>)> {
+    let on_close = buffer.line(pos.y)?.chars().nth(pos.x) == Some(close);
+    let before = chars_from(buffer, pos, false).skip(usize::from(on_close));
+    let start = find_unmatched(before, open, close)?;
+    let end = find_unmatched(chars_from(buffer, start, true).skip(1), close, open)?;
+    Some((start, end))
+}
+
+// the first `target` in `chars` that isn't matched by a `nested` before it
+fn find_unmatched(
+    chars: impl Iterator<Item = (Position<usize>, char)>,
+    target: char,
+    nested: char,
+) -> Option<Position<usize>> {
+    let mut depth = 0;
+    for (pos, ch) in chars {
+        if ch == nested {
+            depth += 1;
+        } else if ch == target && depth == 0 {
+            return Some(pos);
+        } else if ch == target {
+            depth -= 1;
+        }
+    }
+    None
+}
+
+// every character from `pos` onward, in the direction of the search
+fn chars_from(
+    buffer: &Buffer,
+    pos: Position<usize>,
+    forward: bool,
+) -> impl Iterator<Item = (Position<usize>, char)> + '_ {
+    let chars = move |y: usize| -> Vec<(Position<usize>, char)> {
+        let line = buffer.line(y).unwrap_or_default();
+        let mut chars: Vec<_> = line
+            .chars()
+            .enumerate()
+            .map(|(x, ch)| (Position { x, y }, ch))
+            .collect();
+        if y == pos.y && forward {
+            chars.drain(..pos.x.min(chars.len()));
+        } else if y == pos.y {
+            chars.truncate(pos.x + 1);
+        }
+        if !forward {
+            chars.reverse();
+        }
+        chars
+    };
+    let lines: Box<dyn Iterator<Item = usize>> = if forward {
+        Box::new(pos.y..buffer.line_count())
+    } else {
+        Box::new((0..=pos.y).rev())
+    };
+
+    lines.flat_map(chars)
+}
+
+/// A search for a character in the cursor line, like vim's f, F, t and T.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CharFind {
+    pub ch: char,
+    pub forward: bool,
+    /// stops next to the character instead of on it, like t and T
+    pub till: bool,
+}
+
+impl CharFind {
+    /// The same search in the other direction, like , repeating a find.
+    pub fn reversed(self) -> Self {
+        Self {
+            forward: !self.forward,
+            ..self
+        }
+    }
+
+    /// The column of `line` the `count`th find from `col` stops at.
+    /// `repeated` finds don't stop next to a character they already stopped
+    /// next to, so that repeating t moves on.
+    pub fn find_in_line(
+        &self,
+        line: &str,
+        col: usize,
+        count: usize,
+        repeated: bool,
+    ) -> Option<usize> {
+        let cols: Vec<usize> = line
+            .chars()
+            .enumerate()
+            .filter(|&(_, ch)| ch == self.ch)
+            .map(|(found, _)| found)
+            .collect();
+        // a repeated till starts past the character right next to the cursor
+        let skip = usize::from(self.till && repeated);
+
+        if self.forward {
+            let found = cols
+                .into_iter()
+                .filter(|&found| found > col + skip)
+                .nth(count - 1)?;
+            Some(if self.till { found - 1 } else { found })
+        } else {
+            let found = cols
+                .into_iter()
+                .rev()
+                .filter(|&found| found + skip < col)
+                .nth(count - 1)?;
+            Some(if self.till { found + 1 } else { found })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pos(x: usize, y: usize) -> Position<usize> {
+        Position { x, y }
+    }
+
+    #[test]
+    fn find_in_line_counts_columns_in_chars() {
+        assert_eq!(find_in_line("ab ab", "ab"), [0, 3]);
+        assert_eq!(find_in_line("é ab", "ab"), [2]);
+        assert_eq!(find_in_line("ab", ""), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn find_next_starts_after_the_cursor_and_wraps() {
+        let buffer = Buffer::from("foo bar\nbar\nfoo");
+
+        assert_eq!(find_next(&buffer, "bar", pos(0, 0)), Some(pos(4, 0)));
+        assert_eq!(find_next(&buffer, "bar", pos(4, 0)), Some(pos(0, 1)));
+        assert_eq!(find_next(&buffer, "foo", pos(0, 2)), Some(pos(0, 0)));
+        // the only match is the one under the cursor
+        assert_eq!(find_next(&buffer, "foo bar", pos(0, 0)), Some(pos(0, 0)));
+        assert_eq!(find_next(&buffer, "baz", pos(0, 0)), None);
+    }
+}
@@ -0,0 +1,79 @@
+use std::ops::Range;
+
+use crate::base::Position;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionKind {
+    Char,
+    Line,
+    /// the same columns on every line, making a rectangle
+    Block,
+}
+
+/// A region selected in visual mode, between two positions in either order.
+#[derive(Debug, Clone, Copy)]
+pub struct Selection {
+    pub anchor: Position<usize>,
+    pub cursor: Position<usize>,
+    pub kind: SelectionKind,
+}
+
+impl Selection {
+    /// The earlier and later ends of the selection.
+    pub fn ordered(&self) -> (Position<usize>, Position<usize>) {
+        if (self.anchor.y, self.anchor.x) <= (self.cursor.y, self.cursor.x) {
+            (self.anchor, self.cursor)
+        } else {
+            (self.cursor, self.anchor)
+        }
+    }
+
+    /// Where the selection starts, which is the top left corner of a block.
+    pub fn start(&self) -> Position<usize> {
+        match self.kind {
+            SelectionKind::Block => Position {
+                x: self.block_cols().start,
+                y: self.first_line(),
+            },
+            SelectionKind::Char | SelectionKind::Line => self.ordered().0,
+        }
+    }
+
+    /// The columns a block selection covers on each of its lines, which go
+    /// past the end of lines too short to reach them.
+    pub fn block_cols(&self) -> Range<usize> {
+        self.anchor.x.min(self.cursor.x)..self.anchor.x.max(self.cursor.x) + 1
+    }
+
+    pub fn first_line(&self) -> usize {
+        self.anchor.y.min(self.cursor.y)
+    }
+
+    pub fn last_line(&self) -> usize {
+        self.anchor.y.max(self.cursor.y)
+    }
+
+    /// Columns (in chars) of `line` that are selected, where `line_len` is its
+    /// length. The end of the line is included in the last column of a
+    /// linewise selection, so that empty lines are still visibly selected.
+    pub fn cols(&self, line: usize, line_len: usize) -> Option<Range<usize>> {
+        if line < self.first_line() || line > self.last_line() {
+            return None;
+        }
+
+        match self.kind {
+            SelectionKind::Line => Some(0..line_len + 1),
+            SelectionKind::Block => Some(self.block_cols()),
+            SelectionKind::Char => {
+                let (start, end) = self.ordered();
+                let start_col = if line == start.y { start.x } else { 0 };
+                let end_col = if line == end.y {
+                    end.x + 1
+                } else {
+                    line_len + 1
+                };
+                Some(start_col..end_col)
+            }
+        }
+    }
+}
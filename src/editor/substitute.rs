@@ -0,0 +1,240 @@
+use std::ops::{Range, RangeInclusive};
+
+use anyhow::{bail, Result};
+
+use super::Buffer;
+
+/// A parsed `:s/pattern/replacement/flags` command. Patterns are matched
+/// literally.
+pub struct Substitute {
+    pub pattern: String,
+    pub replacement: String,
+    /// replace every match in a line, instead of only the first
+    pub global: bool,
+}
+
+/// The part of the buffer a substitution is restricted to.
+pub struct SubstituteScope {
+    pub lines: RangeInclusive<usize>,
+    /// if set, only matches entirely within these columns (in chars) are replaced
+    pub cols: Option<Range<usize>>,
+}
+
+impl Substitute {
+    /// Parses the arguments of `:s`, e.g. `/foo/bar/g`. Any character can be
+    /// used as the delimiter instead of `/`.
+    pub fn parse(args: &str) -> Result<Self> {
+        let mut chars = args.chars();
+        let Some(delimiter) = chars.next() else {
+            bail!("Expected a pattern");
+        };
+
+        let mut parts = chars.as_str().splitn(3, delimiter);
+        let pattern = parts.next().unwrap_or_default().to_string();
+        let replacement = parts.next().unwrap_or_default().to_string();
+        let flags = parts.next().unwrap_or_default();
+
+        if pattern.is_empty() {
+            bail!("Expected a pattern");
+        }
+
+        let global = match flags.trim() {
+            "" => false,
+            "g" => true,
+            flags => bail!("Unknown flags: {flags}"),
+        };
+
+        Ok(Self {
+            pattern,
+            replacement,
+            global,
+        })
+    }
+
+    /// Returns `line` with the matches replaced, and how many were replaced.
+    pub fn apply(&self, line: &str, cols: Option<&Range<usize>>) -> (String, usize) {
+        let mut result = String::with_capacity(line.len());
+        let mut last_end = 0;
+        let mut count = 0;
+
+        for (index, matched) in line.match_indices(&self.pattern) {
+            if count > 0 && !self.global {
+                break;
+            }
+
+            if let Some(cols) = cols {
+                let start_col = line[..index].chars().count();
+                let end_col = start_col + matched.chars().count();
+
+                if start_col < cols.start || end_col > cols.end {
+                    continue;
+                }
+            }
+
+            result.push_str(&line[last_end..index]);
+            result.push_str(&self.replacement);
+            last_end = index + matched.len();
+            count += 1;
+        }
+
+        result.push_str(&line[last_end..]);
+        (result, count)
+    }
+}
+
+/// A substitution done a chunk of lines at a time, so that a long one can
+/// show its progress and be cancelled. The buffer isn't changed until every
+/// line is done, which leaves nothing to roll back when cancelling.
+pub struct SubstituteJob {
+    substitute: Substitute,
+    scope: SubstituteScope,
+    // the lines are done up to here
+    next_line: usize,
+    new_lines: Vec<String>,
+    count: usize,
+}
+
+impl SubstituteJob {
+    /// A substitution within `scope` of a buffer with `line_count` lines.
+    pub fn new(substitute: Substitute, scope: SubstituteScope, line_count: usize) -> Self {
+        let first = *scope.lines.start();
+        let last = (*scope.lines.end()).min(line_count.saturating_sub(1));
+
+        Self {
+            substitute,
+            scope: SubstituteScope {
+                lines: first..=last,
+                cols: scope.cols,
+            },
+            next_line: first,
+            new_lines: vec![],
+            count: 0,
+        }
+    }
+
+    pub fn pattern(&self) -> &str {
+        &self.substitute.pattern
+    }
+
+    /// The lines substituted in, which is empty if the scope is past the end
+    /// of the buffer.
+    pub fn lines(&self) -> RangeInclusive<usize> {
+        self.scope.lines.clone()
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.scope.lines.clone().count()
+    }
+
+    /// How much of the lines are done, from 0 to 1.
+    pub fn progress(&self) -> f64 {
+        match self.line_count() {
+            0 => 1.0,
+            line_count => self.new_lines.len() as f64 / line_count as f64,
+        }
+    }
+
+    /// Substitutes in up to `max_lines` more lines of `buffer`, returning
+    /// whether every line is done.
+    pub fn step(&mut self, buffer: &Buffer, max_lines: usize) -> bool {
+        let end = (*self.scope.lines.end() + 1)
+            .min(self.next_line.saturating_add(max_lines))
+            .max(self.next_line);
+
+        (self.next_line..end).for_each(|line| {
+            let text = buffer.line(line).unwrap_or_default();
+            let (new_line, count) = self.substitute.apply(text, self.scope.cols.as_ref());
+            self.new_lines.push(new_line);
+            self.count += count;
+        });
+        self.next_line = end;
+
+        self.next_line > *self.scope.lines.end()
+    }
+
+    /// The substituted lines, and how many matches were replaced in them.
+    pub fn into_result(self) -> (Vec<String>, usize) {
+        (self.new_lines, self.count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(args: &str, line: &str, cols: Option<Range<usize>>) -> (String, usize) {
+        Substitute::parse(args).unwrap().apply(line, cols.as_ref())
+    }
+
+    #[test]
+    fn parse_splits_at_any_delimiter() {
+        let substitute = Substitute::parse("#a/b#c#g").unwrap();
+
+        assert_eq!(substitute.pattern, "a/b");
+        assert_eq!(substitute.replacement, "c");
+        assert!(substitute.global);
+    }
+
+    #[test]
+    fn parse_rejects_a_missing_pattern_or_unknown_flags() {
+        assert!(Substitute::parse("").is_err());
+        assert!(Substitute::parse("//x/").is_err());
+        assert!(Substitute::parse("/a/b/x").is_err());
+    }
+
+    #[test]
+    fn apply_replaces_the_first_match_unless_global() {
+        assert_eq!(apply("/a/x/", "banana", None), ("bxnana".to_string(), 1));
+        assert_eq!(apply("/a/x/g", "banana", None), ("bxnxnx".to_string(), 3));
+        // the replacement can be left out to delete the matches
+        assert_eq!(apply("/an", "banana", None), ("bana".to_string(), 1));
+    }
+
+    #[test]
+    fn apply_only_replaces_matches_within_the_columns() {
+        assert_eq!(
+            apply("/a/x/g", "banana", Some(2..5)),
+            ("banxna".to_string(), 1)
+        );
+        // a match that only starts inside them doesn't count
+        assert_eq!(
+            apply("/na/x/g", "banana", Some(3..5)),
+            ("banana".to_string(), 0)
+        );
+    }
+
+    fn job(args: &str, lines: RangeInclusive<usize>, line_count: usize) -> SubstituteJob {
+        let scope = SubstituteScope { lines, cols: None };
+        SubstituteJob::new(Substitute::parse(args).unwrap(), scope, line_count)
+    }
+
+    #[test]
+    fn job_substitutes_a_chunk_of_lines_at_a_time() {
+        let buffer = Buffer::from("a\na\nb\na\na");
+        let mut job = job("/a/x/", 0..=4, buffer.line_count());
+
+        assert!(!job.step(&buffer, 2));
+        assert_eq!(job.progress(), 0.4);
+        assert!(!job.step(&buffer, 2));
+        assert!(job.step(&buffer, 2));
+        assert_eq!(job.progress(), 1.0);
+
+        let (lines, count) = job.into_result();
+        assert_eq!(lines, ["x", "x", "b", "x", "x"]);
+        assert_eq!(count, 4);
+    }
+
+    #[test]
+    fn job_stays_within_the_buffer() {
+        let buffer = Buffer::from("a\na");
+
+        let mut past_end = job("/a/x/", 1..=10, buffer.line_count());
+        assert_eq!(past_end.lines(), 1..=1);
+        assert!(past_end.step(&buffer, usize::MAX));
+        assert_eq!(past_end.into_result(), (vec!["x".to_string()], 1));
+
+        let outside = job("/a/x/", 5..=6, buffer.line_count());
+        assert_eq!(outside.line_count(), 0);
+        assert_eq!(outside.progress(), 1.0);
+    }
+}
@@ -0,0 +1,187 @@
+use super::{width::char_width, Buffer};
+
+// longer labels are cut, so that a single long name can't fill the whole bar
+const MAX_TAB_WIDTH: usize = 24;
+
+/// An open buffer, as listed in the tab bar.
+pub struct Tab {
+    pub buffer_id: usize,
+    pub name: String,
+    pub modified: bool,
+}
+
+impl Tab {
+    pub fn for_buffer(buffer: &Buffer) -> Self {
+        let name = buffer.path().and_then(|path| path.file_name()).map_or_else(
+            || "[No Name]".to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+
+        Self {
+            buffer_id: buffer.id(),
+            name,
+            modified: buffer.is_modified(),
+        }
+    }
+
+    fn label(&self) -> String {
+        let modified = if self.modified { " +" } else { "" };
+        truncate(&format!(" {}{modified} ", self.name), MAX_TAB_WIDTH)
+    }
+}
+
+/// A tab as drawn in the tab bar, in cells from the left of the bar.
+#[derive(Debug)]
+pub struct PlacedTab {
+    pub buffer_id: usize,
+    pub col: usize,
+    pub width: usize,
+    pub label: String,
+    pub active: bool,
+}
+
+// cuts `text` down to `width` cells, marking that it was cut with a `>`
+fn truncate(text: &str, width: usize) -> String {
+    let text_width: usize = text.chars().map(char_width).sum();
+    if text_width <= width {
+        return text.to_string();
+    }
+    if width == 0 {
+        return String::new();
+    }
+
+    let mut truncated = String::new();
+    let mut used = 0;
+    for ch in text.chars() {
+        let ch_width = char_width(ch);
+        if used + ch_width > width - 1 {
+            break;
+        }
+        truncated.push(ch);
+        used += ch_width;
+    }
+    truncated.push('>');
+    truncated
+}
+
+/// Places `tabs` in a bar `width` cells wide. When they don't all fit, the
+/// tabs before the active one are scrolled out of view, and the last tab
+/// that only fits partly is cut.
+pub fn layout_tabs(tabs: &[Tab], active_id: usize, width: usize) -> Vec<PlacedTab> {
+    let labels: Vec<(String, usize)> = tabs
+        .iter()
+        .map(|tab| {
+            let label = tab.label();
+            let label_width = label.chars().map(char_width).sum();
+            (label, label_width)
+        })
+        .collect();
+
+    let active = tabs
+        .iter()
+        .position(|tab| tab.buffer_id == active_id)
+        .unwrap_or(0);
+
+    // scroll just far enough that the active tab ends inside the bar
+    let mut first = 0;
+    while first < active
+        && labels[first..=active]
+            .iter()
+            .map(|(_, label_width)| label_width)
+            .sum::<usize>()
+            > width
+    {
+        first += 1;
+    }
+
+    let mut placed = vec![];
+    let mut col = 0;
+    for (tab, (label, label_width)) in tabs.iter().zip(labels).skip(first) {
+        if col >= width {
+            break;
+        }
+
+        let tab_width = label_width.min(width - col);
+        let label = truncate(&label, tab_width);
+        placed.push(PlacedTab {
+            buffer_id: tab.buffer_id,
+            col,
+            width: tab_width,
+            label,
+            active: tab.buffer_id == active_id,
+        });
+        col += tab_width;
+    }
+
+    placed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tab(buffer_id: usize, name: &str) -> Tab {
+        Tab {
+            buffer_id,
+            name: name.to_string(),
+            modified: false,
+        }
+    }
+
+    // the buffer, column and label of each placed tab
+    fn layout(tabs: &[Tab], active_id: usize, width: usize) -> Vec<(usize, usize, String)> {
+        layout_tabs(tabs, active_id, width)
+            .into_iter()
+            .map(|tab| (tab.buffer_id, tab.col, tab.label))
+            .collect()
+    }
+
+    #[test]
+    fn labels_show_whether_the_buffer_is_modified() {
+        let mut modified = tab(1, "a.rs");
+        modified.modified = true;
+
+        assert_eq!(tab(1, "a.rs").label(), " a.rs ");
+        assert_eq!(modified.label(), " a.rs + ");
+        assert_eq!(
+            tab(1, "a_very_long_file_name_indeed.rs").label(),
+            " a_very_long_file_name_>"
+        );
+    }
+
+    #[test]
+    fn tabs_are_placed_one_after_another() {
+        let tabs = [tab(1, "a"), tab(2, "b")];
+
+        assert_eq!(
+            layout(&tabs, 2, 20),
+            [(1, 0, " a ".to_string()), (2, 3, " b ".to_string())]
+        );
+    }
+
+    #[test]
+    fn tabs_scroll_to_show_the_active_one() {
+        let tabs = [tab(1, "one"), tab(2, "two"), tab(3, "three")];
+
+        assert_eq!(
+            layout(&tabs, 3, 12),
+            [(2, 0, " two ".to_string()), (3, 5, " three ".to_string())]
+        );
+        // the last tab that only fits partly is cut
+        assert_eq!(
+            layout(&tabs, 1, 12),
+            [
+                (1, 0, " one ".to_string()),
+                (2, 5, " two ".to_string()),
+                (3, 10, " >".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn truncate_counts_wide_characters_as_two_cells() {
+        assert_eq!(truncate("漢字漢字", 5), "漢字>");
+        assert_eq!(truncate("abc", 3), "abc");
+        assert_eq!(truncate("abc", 0), "");
+    }
+}
@@ -0,0 +1,257 @@
+use crate::base::Position;
+
+use super::{search::find_enclosing_pair, Buffer, MotionKind};
+
+// characters that end a sentence, when followed by whitespace or the end of the line
+const SENTENCE_ENDS: &[char] = &['.', '!', '?'];
+
+// brackets that can be typed after i or a, opening first
+const BRACKET_PAIRS: &[(char, char)] = &[('(', ')'), ('[', ']'), ('{', '}'), ('<', '>')];
+
+// quotes that can be typed after i or a
+const QUOTES: &[char] = &['"', '\'', '`'];
+
+/// A piece of text around the cursor that an operator can act on, like vim's
+/// iw and ap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextObject {
+    Word,
+    Sentence,
+    Paragraph,
+    /// the text between an opening and closing bracket, which may span lines
+    Brackets(char, char),
+    /// the text between two of the same quote on the cursor line
+    Quotes(char),
+}
+
+impl TextObject {
+    /// The object typed as `key` after i or a.
+    pub fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "w" => Some(Self::Word),
+            "s" => Some(Self::Sentence),
+            "p" => Some(Self::Paragraph),
+            _ => {
+                let ch = key.chars().next()?;
+                if key.chars().count() != 1 {
+                    return None;
+                }
+                if QUOTES.contains(&ch) {
+                    return Some(Self::Quotes(ch));
+                }
+                BRACKET_PAIRS
+                    .iter()
+                    .find(|&&(open, close)| ch == open || ch == close)
+                    .map(|&(open, close)| Self::Brackets(open, close))
+            }
+        }
+    }
+
+    /// The first and last positions of the object at `cursor` (both included),
+    /// and how an operator applies to them. `around` also takes the whitespace
+    /// after the object, or before it if there is none after, like aw and ap.
+    /// For brackets and quotes, `around` takes the brackets or quotes too.
+    pub fn range(
+        &self,
+        buffer: &Buffer,
+        cursor: Position<usize>,
+        around: bool,
+    ) -> Option<(Position<usize>, Position<usize>, MotionKind)> {
+        match self {
+            Self::Word => {
+                let chars: Vec<char> = buffer.line(cursor.y)?.chars().collect();
+                let (start, end) = word_range(&chars, cursor.x, around)?;
+                Some((
+                    at(start, cursor.y),
+                    at(end, cursor.y),
+                    MotionKind::Inclusive,
+                ))
+            }
+            Self::Sentence => {
+                let chars: Vec<char> = buffer.line(cursor.y)?.chars().collect();
+                let (start, end) = sentence_range(&chars, cursor.x, around)?;
+                Some((
+                    at(start, cursor.y),
+                    at(end, cursor.y),
+                    MotionKind::Inclusive,
+                ))
+            }
+            Self::Paragraph => {
+                let (first, last) = paragraph_range(buffer, cursor.y, around);
+                Some((at(0, first), at(0, last), MotionKind::Linewise))
+            }
+            Self::Brackets(open, close) => {
+                let (start, end) = find_enclosing_pair(buffer, cursor, *open, *close)?;
+                if around {
+                    return Some((start, end, MotionKind::Inclusive));
+                }
+                // the closing bracket is left out by ending the range on it
+                Some((at(start.x + 1, start.y), end, MotionKind::Exclusive))
+            }
+            Self::Quotes(quote) => {
+                let chars: Vec<char> = buffer.line(cursor.y)?.chars().collect();
+                let (start, end) = quote_range(&chars, cursor.x, *quote)?;
+                if around {
+                    let (start, end) = with_whitespace(&chars, start, end);
+                    return Some((
+                        at(start, cursor.y),
+                        at(end, cursor.y),
+                        MotionKind::Inclusive,
+                    ));
+                }
+                Some((
+                    at(start + 1, cursor.y),
+                    at(end, cursor.y),
+                    MotionKind::Exclusive,
+                ))
+            }
+        }
+    }
+}
+
+fn at(x: usize, y: usize) -> Position<usize> {
+    Position { x, y }
+}
+
+// words are runs of letters, digits and underscores, runs of other
+// characters, or runs of whitespace, like vim's words
+fn char_class(ch: char) -> u8 {
+    if ch.is_whitespace() {
+        0
+    } else if ch.is_alphanumeric() || ch == '_' {
+        1
+    } else {
+        2
+    }
+}
+
+// the run of characters of the same class as the one at `col`
+fn run_at(chars: &[char], col: usize) -> (usize, usize) {
+    let class = char_class(chars[col]);
+    let mut start = col;
+    while start > 0 && char_class(chars[start - 1]) == class {
+        start -= 1;
+    }
+    let mut end = col;
+    while end + 1 < chars.len() && char_class(chars[end + 1]) == class {
+        end += 1;
+    }
+    (start, end)
+}
+
+fn word_range(chars: &[char], col: usize, around: bool) -> Option<(usize, usize)> {
+    if chars.is_empty() {
+        return None;
+    }
+    let col = col.min(chars.len() - 1);
+    let (start, end) = run_at(chars, col);
+    if !around {
+        return Some((start, end));
+    }
+
+    // whitespace takes the word after it along
+    if char_class(chars[col]) == 0 {
+        let end = if end + 1 < chars.len() {
+            run_at(chars, end + 1).1
+        } else {
+            end
+        };
+        return Some((start, end));
+    }
+
+    Some(with_whitespace(chars, start, end))
+}
+
+fn sentence_range(chars: &[char], col: usize, around: bool) -> Option<(usize, usize)> {
+    if chars.iter().all(|ch| ch.is_whitespace()) {
+        return None;
+    }
+    let col = col.min(chars.len() - 1);
+    let ends_sentence = |index: usize| {
+        SENTENCE_ENDS.contains(&chars[index])
+            && chars.get(index + 1).is_none_or(|next| next.is_whitespace())
+    };
+
+    // the sentence starts after the end of the one before, past its whitespace
+    let mut start = (0..col)
+        .rev()
+        .find(|&index| ends_sentence(index))
+        .map_or(0, |index| index + 1);
+    while start < col && chars[start].is_whitespace() {
+        start += 1;
+    }
+    let end = (col..chars.len())
+        .find(|&index| ends_sentence(index))
+        .unwrap_or(chars.len() - 1);
+
+    if !around {
+        return Some((start, end));
+    }
+    Some(with_whitespace(chars, start, end))
+}
+
+// extends `start..=end` over the whitespace after it, or the whitespace
+// before it if there is none after
+fn with_whitespace(chars: &[char], start: usize, end: usize) -> (usize, usize) {
+    if chars.get(end + 1).is_some_and(|ch| ch.is_whitespace()) {
+        return (start, run_at(chars, end + 1).1);
+    }
+    if start > 0 && chars[start - 1].is_whitespace() {
+        return (run_at(chars, start - 1).0, end);
+    }
+    (start, end)
+}
+
+// the columns of the quotes around `col`. Quotes pair up from the start of the
+// line, skipping ones escaped with a backslash, so a quote at `col` opens or
+// closes a string depending on how many come before it.
+fn quote_range(chars: &[char], col: usize, quote: char) -> Option<(usize, usize)> {
+    let quotes: Vec<usize> = (0..chars.len())
+        .filter(|&index| chars[index] == quote && (index == 0 || chars[index - 1] != '\\'))
+        .collect();
+
+    if let Some(nth) = quotes.iter().position(|&index| index == col) {
+        return if nth % 2 == 0 {
+            Some((col, *quotes.get(nth + 1)?))
+        } else {
+            Some((quotes[nth - 1], col))
+        };
+    }
+
+    let start = quotes.iter().rev().find(|&&index| index < col)?;
+    let end = quotes.iter().find(|&&index| index > col)?;
+    Some((*start, *end))
+}
+
+// the lines of the paragraph at `line`, which are the run of non-blank lines
+// around it, or the run of blank lines if it is blank
+fn paragraph_range(buffer: &Buffer, line: usize, around: bool) -> (usize, usize) {
+    let is_blank = |line: usize| buffer.line(line).unwrap_or_default().trim().is_empty();
+    let run = |line: usize| {
+        let blank = is_blank(line);
+        let mut first = line;
+        while first > 0 && is_blank(first - 1) == blank {
+            first -= 1;
+        }
+        let mut last = line;
+        while last + 1 < buffer.line_count() && is_blank(last + 1) == blank {
+            last += 1;
+        }
+        (first, last)
+    };
+
+    let (first, last) = run(line);
+    if !around {
+        return (first, last);
+    }
+
+    // the blank lines after the paragraph, or before it if there are none
+    // after. Blank lines take the paragraph after them.
+    if last + 1 < buffer.line_count() {
+        (first, run(last + 1).1)
+    } else if first > 0 && !is_blank(line) {
+        (run(first - 1).0, last)
+    } else {
+        (first, last)
+    }
+}
@@ -0,0 +1,373 @@
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Result};
+use serde::{de, Deserialize, Deserializer};
+
+use vello::peniko::Color;
+
+use super::{highlight::HighlightGroup, Mode, NotificationLevel};
+
+// what theme files end with
+const THEME_EXTENSION: &str = "toml";
+
+// the theme loaded on startup from the themes directory, if it is there
+const STARTUP_THEME: &str = "default";
+
+/// The built-in themes by name, for :colorscheme.
+pub type ThemeRegistry = HashMap<String, Theme>;
+
+// colors are written like in CSS, such as "#1e1e1e" or "rgb(30, 30, 30)"
+fn deserialize_color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    Color::parse(&text).ok_or_else(|| de::Error::custom(format!("invalid color: {text}")))
+}
+
+/// Where theme files are looked for by name, like
+/// `~/.config/wlte/themes` on Linux.
+fn themes_dir() -> Option<PathBuf> {
+    #[cfg(windows)]
+    let config_dir = PathBuf::from(env::var_os("APPDATA")?);
+    #[cfg(not(windows))]
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| Some(PathBuf::from(env::var_os("HOME")?).join(".config")))?;
+
+    Some(config_dir.join(env!("CARGO_PKG_NAME")).join("themes"))
+}
+
+/// The colors everything is drawn with. Themes can be loaded from TOML files,
+/// with colors like `background = "#1e1e1e"`, where the colors a file leaves
+/// out are those of the default theme.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    /// what :colorscheme picks the theme by, which is the file name of themes
+    /// loaded from a file
+    #[serde(skip)]
+    pub name: String,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub background: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub foreground: Color,
+    /// the cursor in normal mode, and in modes without a color of their own
+    #[serde(deserialize_with = "deserialize_color")]
+    pub cursor: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub insert_cursor: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub visual_cursor: Color,
+    /// the character under a block cursor
+    #[serde(deserialize_with = "deserialize_color")]
+    pub cursor_text: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub current_line_bg: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub search_match_bg: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub selection_bg: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub line_number: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub show_break_color: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub indent_guide_color: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub color_column_color: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub trailing_ws_color: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub link_underline: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub syntax_comment: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub syntax_string: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub syntax_number: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub syntax_keyword: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub debug_grid_color: Color,
+    /// drawn over views that aren't focused, so should be translucent
+    #[serde(deserialize_with = "deserialize_color")]
+    pub inactive_dim_color: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub scrollbar_track_color: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub scrollbar_thumb_color: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub tab_bar_bg: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub tab_active_bg: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub tab_inactive_text: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub pane_border: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub focused_pane_border: Color,
+    /// drawn over the character under the mouse, so should be translucent
+    #[serde(deserialize_with = "deserialize_color")]
+    pub hover_cell_bg: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub info_notification_bg: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub warning_notification_bg: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub error_notification_bg: Color,
+    /// behind messages of more than one line
+    #[serde(deserialize_with = "deserialize_color")]
+    pub message_bg: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub dialog_bg: Color,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub dialog_border: Color,
+}
+
+impl Theme {
+    /// Light text on black, which is the default.
+    pub fn dark_default() -> Self {
+        Self {
+            name: "dark".to_string(),
+            background: Color::BLACK,
+            foreground: Color::WHITE,
+            cursor: Color::rgb(0.0, 1.0, 0.0),
+            insert_cursor: Color::rgb(1.0, 1.0, 1.0),
+            visual_cursor: Color::rgb(1.0, 0.6, 0.0),
+            cursor_text: Color::BLACK,
+            current_line_bg: Color::rgb(0.12, 0.12, 0.12),
+            search_match_bg: Color::rgb(0.5, 0.4, 0.0),
+            selection_bg: Color::rgb(0.2, 0.3, 0.5),
+            line_number: Color::rgb(0.5, 0.5, 0.5),
+            show_break_color: Color::rgb(0.4, 0.4, 0.6),
+            indent_guide_color: Color::rgb(0.25, 0.25, 0.25),
+            color_column_color: Color::rgb(0.35, 0.2, 0.2),
+            trailing_ws_color: Color::rgb(0.4, 0.1, 0.1),
+            link_underline: Color::rgb(0.35, 0.6, 1.0),
+            syntax_comment: Color::rgb(0.5, 0.55, 0.5),
+            syntax_string: Color::rgb(0.6, 0.8, 0.5),
+            syntax_number: Color::rgb(0.85, 0.6, 0.4),
+            syntax_keyword: Color::rgb(0.75, 0.5, 0.85),
+            debug_grid_color: Color::rgba(1.0, 0.0, 1.0, 0.3),
+            inactive_dim_color: Color::rgba(0.0, 0.0, 0.0, 0.4),
+            scrollbar_track_color: Color::rgb(0.1, 0.1, 0.1),
+            scrollbar_thumb_color: Color::rgb(0.35, 0.35, 0.35),
+            tab_bar_bg: Color::rgb(0.15, 0.15, 0.15),
+            tab_active_bg: Color::rgb(0.3, 0.3, 0.3),
+            tab_inactive_text: Color::rgb(0.6, 0.6, 0.6),
+            pane_border: Color::rgb(0.3, 0.3, 0.3),
+            focused_pane_border: Color::rgb(0.3, 0.5, 0.8),
+            hover_cell_bg: Color::rgba(1.0, 1.0, 1.0, 0.1),
+            info_notification_bg: Color::rgb(0.15, 0.3, 0.5),
+            warning_notification_bg: Color::rgb(0.5, 0.4, 0.1),
+            error_notification_bg: Color::rgb(0.55, 0.15, 0.15),
+            message_bg: Color::rgb(0.08, 0.08, 0.08),
+            dialog_bg: Color::rgb(0.18, 0.18, 0.2),
+            dialog_border: Color::rgb(0.45, 0.45, 0.5),
+        }
+    }
+
+    /// Dark text on white.
+    pub fn light_default() -> Self {
+        Self {
+            name: "light".to_string(),
+            background: Color::WHITE,
+            foreground: Color::rgb(0.1, 0.1, 0.1),
+            cursor: Color::rgb(0.0, 0.55, 0.0),
+            insert_cursor: Color::rgb(0.1, 0.1, 0.1),
+            visual_cursor: Color::rgb(0.85, 0.45, 0.0),
+            cursor_text: Color::WHITE,
+            current_line_bg: Color::rgb(0.93, 0.93, 0.93),
+            search_match_bg: Color::rgb(1.0, 0.85, 0.3),
+            selection_bg: Color::rgb(0.7, 0.8, 0.95),
+            line_number: Color::rgb(0.55, 0.55, 0.55),
+            show_break_color: Color::rgb(0.5, 0.5, 0.75),
+            indent_guide_color: Color::rgb(0.85, 0.85, 0.85),
+            color_column_color: Color::rgb(0.97, 0.88, 0.88),
+            trailing_ws_color: Color::rgb(0.95, 0.7, 0.7),
+            link_underline: Color::rgb(0.1, 0.35, 0.8),
+            syntax_comment: Color::rgb(0.45, 0.5, 0.45),
+            syntax_string: Color::rgb(0.2, 0.5, 0.1),
+            syntax_number: Color::rgb(0.7, 0.35, 0.0),
+            syntax_keyword: Color::rgb(0.55, 0.15, 0.6),
+            debug_grid_color: Color::rgba(1.0, 0.0, 1.0, 0.3),
+            inactive_dim_color: Color::rgba(0.0, 0.0, 0.0, 0.1),
+            scrollbar_track_color: Color::rgb(0.92, 0.92, 0.92),
+            scrollbar_thumb_color: Color::rgb(0.7, 0.7, 0.7),
+            tab_bar_bg: Color::rgb(0.88, 0.88, 0.88),
+            tab_active_bg: Color::WHITE,
+            tab_inactive_text: Color::rgb(0.4, 0.4, 0.4),
+            pane_border: Color::rgb(0.75, 0.75, 0.75),
+            focused_pane_border: Color::rgb(0.3, 0.5, 0.8),
+            hover_cell_bg: Color::rgba(0.0, 0.0, 0.0, 0.08),
+            info_notification_bg: Color::rgb(0.75, 0.85, 0.95),
+            warning_notification_bg: Color::rgb(0.98, 0.9, 0.6),
+            error_notification_bg: Color::rgb(0.98, 0.75, 0.75),
+            message_bg: Color::rgb(0.95, 0.95, 0.95),
+            dialog_bg: Color::rgb(0.96, 0.96, 0.97),
+            dialog_border: Color::rgb(0.6, 0.6, 0.65),
+        }
+    }
+
+    pub fn built_in_themes() -> ThemeRegistry {
+        [Self::dark_default(), Self::light_default()]
+            .into_iter()
+            .map(|theme| (theme.name.clone(), theme))
+            .collect()
+    }
+
+    /// Loads the theme in the TOML file at `path`, which is named after the
+    /// file.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .map_err(|error| anyhow!("Cannot read {}: {error}", path.display()))?;
+        let mut theme: Self = toml::from_str(&text)
+            .map_err(|error| anyhow!("Cannot load {}: {}", path.display(), error.message()))?;
+
+        theme.name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Ok(theme)
+    }
+
+    /// The built-in theme called `name`, or else the one in the themes
+    /// directory with that file name.
+    pub fn find(name: &str) -> Result<Self> {
+        if let Some(theme) = Self::built_in_themes().remove(name) {
+            return Ok(theme);
+        }
+
+        let path = themes_dir().map(|dir| dir.join(name).with_extension(THEME_EXTENSION));
+        match path {
+            Some(path) if path.is_file() => Self::from_file(&path),
+            _ => bail!(
+                "Cannot find color scheme '{name}', available: {}",
+                Self::available_names().join(", ")
+            ),
+        }
+    }
+
+    /// The theme in the themes directory that is used on startup, if there is
+    /// one. The built-in default is used instead when it is missing.
+    pub fn startup_theme() -> Result<Option<Self>> {
+        let Some(path) = themes_dir()
+            .map(|dir| dir.join(STARTUP_THEME).with_extension(THEME_EXTENSION))
+            .filter(|path| path.is_file())
+        else {
+            return Ok(None);
+        };
+
+        Self::from_file(&path).map(Some)
+    }
+
+    // the names of the built-in themes and of the theme files, sorted
+    fn available_names() -> Vec<String> {
+        let mut names: Vec<String> = Self::built_in_themes().into_keys().collect();
+
+        let files = themes_dir().and_then(|dir| fs::read_dir(dir).ok());
+        names.extend(files.into_iter().flatten().filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != THEME_EXTENSION {
+                return None;
+            }
+            Some(path.file_stem()?.to_string_lossy().into_owned())
+        }));
+
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark_default()
+    }
+}
+
+impl Theme {
+    pub fn notification_bg(&self, level: NotificationLevel) -> Color {
+        match level {
+            NotificationLevel::Info => self.info_notification_bg,
+            NotificationLevel::Warning => self.warning_notification_bg,
+            NotificationLevel::Error => self.error_notification_bg,
+        }
+    }
+
+    pub fn cursor_color(&self, mode: Mode) -> Color {
+        match mode {
+            Mode::Insert | Mode::Replace => self.insert_cursor,
+            Mode::Visual | Mode::VisualLine | Mode::VisualBlock => self.visual_cursor,
+            Mode::Normal | Mode::OperatorPending(_) | Mode::Command | Mode::Search => self.cursor,
+        }
+    }
+
+    pub fn highlight_color(&self, group: HighlightGroup) -> Color {
+        match group {
+            HighlightGroup::Comment => self.syntax_comment,
+            HighlightGroup::String => self.syntax_string,
+            HighlightGroup::Number => self.syntax_number,
+            HighlightGroup::Keyword => self.syntax_keyword,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    fn theme_file(text: &str) -> tempfile::NamedTempFile {
+        let mut file = tempfile::Builder::new().suffix(".toml").tempfile().unwrap();
+        file.write_all(text.as_bytes()).unwrap();
+        file
+    }
+
+    #[test]
+    fn from_file_falls_back_to_default_colors() {
+        let file = theme_file("background = \"#102030\"\n");
+        let theme = Theme::from_file(file.path()).unwrap();
+
+        assert_eq!(theme.background, Color::rgb8(0x10, 0x20, 0x30));
+        assert_eq!(theme.foreground, Theme::default().foreground);
+        assert_eq!(
+            theme.name,
+            file.path().file_stem().unwrap().to_string_lossy()
+        );
+    }
+
+    #[test]
+    fn from_file_rejects_invalid_colors() {
+        let file = theme_file("background = \"not a color\"\n");
+        let error = Theme::from_file(file.path()).unwrap_err().to_string();
+
+        assert!(error.contains("invalid color: not a color"), "{error}");
+    }
+
+    #[test]
+    fn from_file_keeps_the_alpha_of_colors() {
+        let file = theme_file("inactive_dim_color = \"#00000066\"\n");
+        let theme = Theme::from_file(file.path()).unwrap();
+
+        assert_eq!(theme.inactive_dim_color, Color::rgba8(0, 0, 0, 0x66));
+    }
+
+    #[test]
+    fn find_returns_built_in_themes() {
+        assert_eq!(Theme::find("light").unwrap().name, "light");
+        assert!(Theme::find("no such theme").is_err());
+    }
+
+    #[test]
+    fn built_in_themes_dim_inactive_views_translucently() {
+        for name in ["dark", "light"] {
+            let alpha = Theme::find(name).unwrap().inactive_dim_color.a;
+            assert!(alpha > 0 && alpha < u8::MAX, "{name}: {alpha}");
+        }
+    }
+}
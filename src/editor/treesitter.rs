@@ -0,0 +1,284 @@
+use std::{iter, ops::Range};
+
+use anyhow::Result;
+use tree_sitter::{
+    InputEdit, Language, Parser, Point, Query, QueryCursor, StreamingIterator, Tree,
+};
+
+use super::{
+    buffer::LineSplice,
+    filetype::FileType,
+    highlight::{HighlightGroup, HighlightSpan, Highlighter},
+    Buffer,
+};
+
+/// Highlights a buffer from the syntax tree tree-sitter parses it into, which
+/// is only parsed again where it changed.
+pub struct TreeSitterHighlighter {
+    parser: Parser,
+    query: Query,
+    // the group each capture of the query is drawn in, if any
+    capture_groups: Vec<Option<HighlightGroup>>,
+    // the tree of `text`, or None if nothing has been parsed yet
+    tree: Option<Tree>,
+    // the lines the tree was parsed from, each ending with a newline
+    text: String,
+    // where each line of `text` starts, followed by where the last one ends
+    line_starts: Vec<usize>,
+}
+
+impl TreeSitterHighlighter {
+    /// A highlighter for text in `language`, drawing what `highlights_query`
+    /// captures in the group its capture is named after, like `@keyword`.
+    pub fn new(language: Language, highlights_query: &str) -> Result<Self> {
+        let mut parser = Parser::new();
+        parser.set_language(&language)?;
+        let query = Query::new(&language, highlights_query)?;
+        let capture_groups = query
+            .capture_names()
+            .iter()
+            .map(|name| capture_group(name))
+            .collect();
+
+        Ok(Self {
+            parser,
+            query,
+            capture_groups,
+            tree: None,
+            text: String::new(),
+            line_starts: vec![0],
+        })
+    }
+
+    /// The highlighter for `filetype`, if there is a grammar for it.
+    pub fn for_filetype(filetype: FileType) -> Option<Self> {
+        let (language, highlights_query) = match filetype {
+            FileType::Rust => (
+                tree_sitter_rust::LANGUAGE.into(),
+                tree_sitter_rust::HIGHLIGHTS_QUERY,
+            ),
+            _ => return None,
+        };
+
+        Self::new(language, highlights_query)
+            .inspect_err(|error| eprintln!("Cannot load {} grammar: {error}", filetype.name()))
+            .ok()
+    }
+
+    // the column of `byte` in the line starting at `line_start`
+    fn col(&self, line_start: usize, byte: usize) -> usize {
+        self.text[line_start..byte].chars().count()
+    }
+}
+
+impl Highlighter for TreeSitterHighlighter {
+    fn update(&mut self, buffer: &Buffer, splices: &[LineSplice]) -> Vec<Range<usize>> {
+        let Some(old_tree) = self.tree.as_mut() else {
+            self.text = lines_text(buffer, 0..buffer.line_count());
+            self.line_starts = line_starts(&self.text);
+            self.tree = self.parser.parse(&self.text, None);
+            return vec![];
+        };
+        let Some(splice) = merge_splices(splices) else {
+            return vec![];
+        };
+
+        let old_end = splice.old_end.min(self.line_starts.len() - 1);
+        let start = splice.start.min(old_end);
+        let start_byte = self.line_starts[start];
+        let old_end_byte = self.line_starts[old_end];
+        let new_text = lines_text(buffer, start..splice.new_end);
+
+        self.text.replace_range(start_byte..old_end_byte, &new_text);
+        self.line_starts = line_starts(&self.text);
+        old_tree.edit(&InputEdit {
+            start_byte,
+            old_end_byte,
+            new_end_byte: start_byte + new_text.len(),
+            start_position: Point::new(start, 0),
+            old_end_position: Point::new(old_end, 0),
+            new_end_position: Point::new(splice.new_end, 0),
+        });
+
+        let Some(new_tree) = self.parser.parse(&self.text, Some(old_tree)) else {
+            self.tree = None;
+            return vec![];
+        };
+        // where the meaning of the text changed without it being replaced,
+        // like the lines after an unclosed comment
+        let changed = old_tree
+            .changed_ranges(&new_tree)
+            .map(|range| range.start_point.row..range.end_point.row + 1)
+            .collect();
+        self.tree = Some(new_tree);
+        changed
+    }
+
+    fn highlight(&self, _buffer: &Buffer, line: usize) -> Vec<HighlightSpan> {
+        let (Some(tree), Some(&line_start), Some(&line_end)) = (
+            &self.tree,
+            self.line_starts.get(line),
+            self.line_starts.get(line + 1),
+        ) else {
+            return vec![];
+        };
+        // without the newline
+        let line_end = line_end - 1;
+
+        let mut cursor = QueryCursor::new();
+        cursor.set_byte_range(line_start..line_end);
+        let mut captures = cursor.captures(&self.query, tree.root_node(), self.text.as_bytes());
+
+        let mut spans = vec![];
+        // the text before this is in a span already, so that what is inside
+        // it, like an escape in a string, isn't highlighted again
+        let mut covered = line_start;
+        while let Some((query_match, index)) = captures.next() {
+            let capture = query_match.captures()[*index];
+            let Some(group) = self.capture_groups[capture.index as usize] else {
+                continue;
+            };
+
+            let range = capture.node.byte_range();
+            let start = range.start.max(line_start);
+            let end = range.end.min(line_end);
+            if start < covered || start >= end {
+                continue;
+            }
+
+            spans.push(HighlightSpan::new(
+                self.col(line_start, start),
+                self.col(line_start, end),
+                group,
+            ));
+            covered = end;
+        }
+
+        spans
+    }
+}
+
+// the group a capture of a highlights query is drawn in, by its name, which
+// can be made more specific with dots, like `comment.documentation`
+fn capture_group(name: &str) -> Option<HighlightGroup> {
+    match name {
+        "constant.builtin" | "number" | "float" | "boolean" => Some(HighlightGroup::Number),
+        _ => match name.split('.').next()? {
+            "comment" => Some(HighlightGroup::Comment),
+            "string" | "escape" | "character" => Some(HighlightGroup::String),
+            "keyword" => Some(HighlightGroup::Keyword),
+            _ => None,
+        },
+    }
+}
+
+// the splices one after another as a single one, from the lines before the
+// first of them to those after the last
+fn merge_splices(splices: &[LineSplice]) -> Option<LineSplice> {
+    let (first, rest) = splices.split_first()?;
+
+    Some(rest.iter().fold(*first, |merged, splice| {
+        // the end of both, in the lines from before `splice` was made
+        let end = merged.new_end.max(splice.old_end);
+        LineSplice {
+            start: merged.start.min(splice.start),
+            old_end: end - merged.new_end + merged.old_end,
+            new_end: end - splice.old_end + splice.new_end,
+        }
+    }))
+}
+
+// the lines of `buffer` in `lines`, each followed by a newline
+fn lines_text(buffer: &Buffer, lines: Range<usize>) -> String {
+    lines
+        .flat_map(|line| [buffer.line(line).unwrap_or_default(), "\n"])
+        .collect()
+}
+
+fn line_starts(text: &str) -> Vec<usize> {
+    iter::once(0)
+        .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn splice(start: usize, old_end: usize, new_end: usize) -> LineSplice {
+        LineSplice {
+            start,
+            old_end,
+            new_end,
+        }
+    }
+
+    fn groups(
+        highlighter: &TreeSitterHighlighter,
+        buffer: &Buffer,
+        line: usize,
+    ) -> Vec<(usize, usize, HighlightGroup)> {
+        highlighter
+            .highlight(buffer, line)
+            .iter()
+            .map(|span| (span.start, span.end, span.group))
+            .collect()
+    }
+
+    #[test]
+    fn merge_splices_covers_all_of_them() {
+        assert_eq!(merge_splices(&[]), None);
+        // a line inserted after line 2, then line 5 replaced by two
+        assert_eq!(
+            merge_splices(&[splice(2, 2, 3), splice(5, 6, 7)]),
+            Some(splice(2, 5, 7))
+        );
+        // two lines deleted, then a line inserted before them
+        assert_eq!(
+            merge_splices(&[splice(4, 6, 4), splice(1, 1, 2)]),
+            Some(splice(1, 6, 5))
+        );
+    }
+
+    #[test]
+    fn highlights_rust_lines() {
+        let buffer = Buffer::from("fn main() {\n    let s = \"a\\n\"; // 42\n}");
+        let mut highlighter = TreeSitterHighlighter::for_filetype(FileType::Rust).unwrap();
+        highlighter.update(&buffer, &[]);
+
+        assert_eq!(
+            groups(&highlighter, &buffer, 0),
+            vec![(0, 2, HighlightGroup::Keyword)]
+        );
+        assert_eq!(
+            groups(&highlighter, &buffer, 1),
+            vec![
+                (4, 7, HighlightGroup::Keyword),
+                (12, 17, HighlightGroup::String),
+                (19, 24, HighlightGroup::Comment),
+            ]
+        );
+    }
+
+    #[test]
+    fn reparses_changes_and_reports_lines_they_affect() {
+        let mut buffer = Buffer::from("let a = 1;\nlet b = 2;\nlet c = 3;");
+        let mut highlighter = TreeSitterHighlighter::for_filetype(FileType::Rust).unwrap();
+        highlighter.update(&buffer, &[]);
+
+        // opening a comment turns the lines after it into comment too
+        buffer.insert_text(0, 0, "/* ");
+        let splices = buffer.take_splices();
+        let changed = highlighter.update(&buffer, &splices);
+
+        assert_eq!(highlighter.text, "/* let a = 1;\nlet b = 2;\nlet c = 3;\n");
+        assert!(
+            changed.iter().any(|lines| lines.contains(&2)),
+            "{changed:?}"
+        );
+        assert_eq!(
+            groups(&highlighter, &buffer, 2),
+            vec![(0, 10, HighlightGroup::Comment)]
+        );
+    }
+}
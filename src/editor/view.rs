@@ -0,0 +1,2776 @@
+use std::{borrow::Cow, iter, marker::PhantomData, mem, ops::Range, path::Path};
+
+use anyhow::{anyhow, bail, Result};
+
+use vello::{
+    kurbo::Affine,
+    peniko::{Brush, Color, Fill},
+};
+
+use crate::base::{
+    AppFont, AppRenderer, CursorShape, DrawColoredRunsOptions, DrawCursorOptions,
+    DrawFillRectangleOptions, DrawLineOptions, DrawTextOptions, DrawUnderlineOptions, FontStyle,
+    FontWeight, Position, Size, TextRun,
+};
+
+use super::{
+    buffer::leading_whitespace,
+    conceal::{cell_widths, display_text, find_conceals, Conceal},
+    filetype::FileType,
+    highlight::{highlighter, HighlightSpan, Highlighter},
+    line_map::{LineMap, WrapMode},
+    link::find_links,
+    location::Jump,
+    operator::{MotionKind, Operator},
+    options::ViewOptions,
+    pairs::PairTable,
+    registers::{RegisterKind, RegisterText},
+    search::{find_in_line, find_matching_bracket, find_next, CharFind},
+    selection::{Selection, SelectionKind},
+    substitute::{Substitute, SubstituteJob, SubstituteScope},
+    width::{char_width, char_width_at, display_col},
+    Buffer, Mode, Theme,
+};
+
+// in cells, drawn over the last column of the view
+const SCROLLBAR_WIDTH: f64 = 0.5;
+
+// how quickly smooth scrolling catches up, as the share of the remaining
+// distance covered per second
+const SCROLL_SPEED: f64 = 15.0;
+// substitutions over more lines than this are done this many lines a frame
+const SUBSTITUTE_CHUNK_LINES: usize = 5000;
+
+// smooth scrolling stops once it is this close (in lines) to where it goes
+const SCROLL_SNAP: f64 = 0.01;
+
+/// A single screen row of the view, and the part of the buffer it displays.
+#[derive(Debug, Clone, Copy)]
+pub struct VisibleRow {
+    pub screen_y: usize,
+    pub buffer_line: usize,
+    /// whether this row continues the buffer line of the previous row,
+    /// which only happens when lines are wrapped
+    pub is_wrapped_continuation: bool,
+    /// columns (in chars) of the buffer line shown in this row
+    pub start_col: usize,
+    pub end_col: usize,
+    /// cells the text of the row is shifted right by, for break indent and
+    /// the showbreak marker
+    pub indent: usize,
+}
+
+// how a column of a row is drawn, for splitting the row into runs
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct RunLook {
+    color: Color,
+    weight: FontWeight,
+    style: FontStyle,
+}
+
+// where the view was before a search started, to return to if it is cancelled
+struct SearchOrigin {
+    cursor: Position<usize>,
+    scroll_offset: usize,
+}
+
+// text typed on the first line of a block after I or A, which is repeated on
+// the other lines of the block once insert mode is left
+struct BlockInsert {
+    line: usize,
+    col: usize,
+    other_lines: Range<usize>,
+    // to tell what was typed, from before anything was
+    line_len: usize,
+    line_count: usize,
+}
+
+pub struct View {
+    buffer: Buffer,
+    options: ViewOptions,
+    mode: Mode,
+    cursor: Position<usize>,
+    // the first buffer line shown at the top of the view
+    scroll_offset: usize,
+    // the first cell shown at the left of the view, which stays 0 while lines
+    // are wrapped
+    scroll_offset_x: usize,
+    // the line the view is drawn scrolled to, which eases toward
+    // scroll_offset when scrolling smoothly
+    scroll_position: f64,
+    // in number of cells, not pixels
+    size: Size<usize>,
+    pairs: PairTable,
+    search_origin: Option<SearchOrigin>,
+    // matches of this are highlighted
+    highlighted_query: Option<String>,
+    // where visual mode was started
+    visual_anchor: Position<usize>,
+    // the selection when visual mode was last left, for '<,'>
+    last_selection: Option<Selection>,
+    // a long substitution that is still going
+    substitute_job: Option<SubstituteJob>,
+    // the line, column and overwritten character of each character typed in
+    // replace mode, for backspace to put back
+    replaced: Vec<(usize, usize, Option<char>)>,
+    block_insert: Option<BlockInsert>,
+    // the highlighted parts of each line, or None for lines that haven't been
+    // highlighted since they were last changed
+    highlight_cache: Vec<Option<Vec<HighlightSpan>>>,
+    // the filetype the cached lines were highlighted as
+    highlight_filetype: FileType,
+    // highlights the buffer as its filetype, if it can be
+    highlighter: Option<Box<dyn Highlighter>>,
+}
+
+impl View {
+    pub fn new(buffer: Buffer) -> Self {
+        Self {
+            buffer,
+            options: ViewOptions::default(),
+            mode: Mode::Normal,
+            cursor: Position { x: 0, y: 0 },
+            scroll_offset: 0,
+            scroll_offset_x: 0,
+            scroll_position: 0.0,
+            size: Size { w: 0, h: 0 },
+            pairs: PairTable::new(),
+            search_origin: None,
+            highlighted_query: None,
+            visual_anchor: Position { x: 0, y: 0 },
+            last_selection: None,
+            substitute_job: None,
+            replaced: vec![],
+            block_insert: None,
+            highlight_cache: vec![],
+            highlight_filetype: FileType::Unknown,
+            highlighter: None,
+        }
+    }
+
+    pub fn into_buffer(self) -> Buffer {
+        self.buffer
+    }
+
+    pub fn buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+
+    /// Shows `buffer` in this view instead, starting from its last change or
+    /// its top, and returns the buffer that was shown before.
+    pub fn set_buffer(&mut self, buffer: Buffer) -> Buffer {
+        let previous = mem::replace(&mut self.buffer, buffer);
+        self.mode = Mode::Normal;
+        self.cursor = Position { x: 0, y: 0 };
+        self.scroll_offset = 0;
+        self.scroll_position = 0.0;
+        self.last_selection = None;
+        self.block_insert = None;
+        // the changes made before the buffer was shown are all in the new lines
+        self.buffer.take_splices();
+        self.highlight_cache.clear();
+        // the highlighter of the old buffer knows nothing of the new one
+        self.highlight_filetype = FileType::Unknown;
+        self.highlighter = None;
+
+        if let Some(last_change) = self.buffer.marks().get('.') {
+            self.set_cursor(last_change);
+        }
+        previous
+    }
+
+    /// Moves the cursor to `jump`, such as a location given with a file name.
+    pub fn jump_to(&mut self, jump: &Jump) -> Result<()> {
+        match jump {
+            Jump::Position { line, col } => self.set_cursor(Position { x: *col, y: *line }),
+            Jump::Search(query) => {
+                let found = find_next(&self.buffer, query, Position { x: 0, y: 0 })
+                    .ok_or_else(|| anyhow!("Pattern not found: {query}"))?;
+                self.set_cursor(found);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the buffer with its file on disk, discarding unsaved changes.
+    pub fn reload(&mut self) -> Result<()> {
+        let buffer = self.buffer.reopen()?;
+
+        let cursor = self.cursor;
+        self.set_buffer(buffer);
+        self.set_cursor(cursor);
+        Ok(())
+    }
+
+    pub fn cursor(&self) -> Position<usize> {
+        self.cursor
+    }
+
+    pub fn options(&self) -> &ViewOptions {
+        &self.options
+    }
+
+    pub fn options_mut(&mut self) -> &mut ViewOptions {
+        &mut self.options
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn buffer_mut(&mut self) -> &mut Buffer {
+        &mut self.buffer
+    }
+
+    pub fn enter_insert_mode(&mut self) {
+        self.mode = Mode::Insert;
+        // everything typed in a single insert is undone together
+        self.buffer.history_mut().begin_group();
+    }
+
+    pub fn enter_replace_mode(&mut self) {
+        self.mode = Mode::Replace;
+        self.replaced.clear();
+        // everything typed over is undone together, like an insert
+        self.buffer.history_mut().begin_group();
+    }
+
+    /// Enters insert mode with the cursor after the current character.
+    pub fn enter_append_mode(&mut self) {
+        self.enter_insert_mode();
+        self.cursor.x = (self.cursor.x + 1).min(self.max_cursor_x(self.cursor.y));
+        self.scroll_to_cursor_x();
+    }
+
+    pub fn enter_visual_mode(&mut self, kind: SelectionKind) {
+        self.mode = match kind {
+            SelectionKind::Char => Mode::Visual,
+            SelectionKind::Line => Mode::VisualLine,
+            SelectionKind::Block => Mode::VisualBlock,
+        };
+        self.visual_anchor = self.cursor;
+    }
+
+    /// Waits for the motion that `operator` applies to.
+    pub fn enter_operator_pending(&mut self, operator: Operator) {
+        self.mode = Mode::OperatorPending(operator);
+    }
+
+    pub fn enter_command_mode(&mut self) {
+        self.remember_selection();
+        self.mode = Mode::Command;
+    }
+
+    /// The current selection, if in visual mode.
+    pub fn selection(&self) -> Option<Selection> {
+        let kind = match self.mode {
+            Mode::Visual => SelectionKind::Char,
+            Mode::VisualLine => SelectionKind::Line,
+            Mode::VisualBlock => SelectionKind::Block,
+            _ => return None,
+        };
+
+        Some(Selection {
+            anchor: self.visual_anchor,
+            cursor: self.cursor,
+            kind,
+        })
+    }
+
+    /// The selection when visual mode was last left.
+    pub fn last_selection(&self) -> Option<Selection> {
+        self.last_selection
+    }
+
+    fn remember_selection(&mut self) {
+        if let Some(selection) = self.selection() {
+            self.last_selection = Some(selection);
+        }
+    }
+
+    pub fn enter_normal_mode(&mut self) {
+        self.remember_selection();
+
+        match self.mode {
+            Mode::Insert | Mode::Replace => {
+                self.mode = Mode::Normal;
+                self.replaced.clear();
+                // undone together with what was typed
+                self.repeat_block_insert();
+                self.buffer.history_mut().end_group();
+                self.move_cursor_left();
+            }
+            Mode::Command | Mode::Search | Mode::Visual | Mode::VisualLine | Mode::VisualBlock => {
+                self.mode = Mode::Normal;
+                // off the virtual space of a block
+                self.clamp_cursor_x();
+            }
+            // a motion that doesn't fit within the line can leave the cursor past it
+            Mode::OperatorPending(_) => {
+                self.mode = Mode::Normal;
+                self.clamp_cursor_x();
+            }
+            Mode::Normal => {}
+        }
+    }
+
+    /// Moves the drawn scroll position toward `scroll_offset`, for a frame
+    /// `delta_time` seconds after the last one. Returns whether it still has
+    /// to move further.
+    pub fn animate_scroll(&mut self, delta_time: f64) -> bool {
+        let target = self.scroll_offset as f64;
+        let distance = target - self.scroll_position;
+
+        if !self.options.smooth_scroll || distance.abs() < SCROLL_SNAP {
+            self.scroll_position = target;
+            return false;
+        }
+
+        self.scroll_position += distance * (delta_time * SCROLL_SPEED).min(1.0);
+        true
+    }
+
+    // the first buffer line drawn, which lags behind scroll_offset while
+    // scrolling smoothly
+    fn first_drawn_line(&self) -> usize {
+        (self.scroll_position.floor() as usize).min(self.buffer.line_count() - 1)
+    }
+
+    pub fn resize(&mut self, size: Size<usize>) {
+        self.size = size;
+        self.scroll_to_cursor();
+    }
+
+    /// Maps each screen row of the view to the buffer line it displays.
+    ///
+    /// Rows past the end of the buffer are not yielded.
+    pub fn visible_rows(&self) -> impl Iterator<Item = VisibleRow> + '_ {
+        let line_map = self.line_map();
+
+        (self.first_drawn_line()..self.buffer.line_count())
+            .flat_map(move |buffer_line| {
+                let line = self.buffer.line(buffer_line).unwrap_or_default();
+                let indent = line_map.continuation_offset(line);
+
+                line_map
+                    .rows(line)
+                    .into_iter()
+                    .enumerate()
+                    .map(move |(index, cols)| (buffer_line, index > 0, cols, indent))
+            })
+            .take(self.size.h)
+            .enumerate()
+            .map(
+                |(screen_y, (buffer_line, is_wrapped_continuation, cols, indent))| VisibleRow {
+                    screen_y,
+                    buffer_line,
+                    is_wrapped_continuation,
+                    start_col: cols.start,
+                    end_col: cols.end,
+                    indent: if is_wrapped_continuation { indent } else { 0 },
+                },
+            )
+            .map(|row| self.skip_scrolled_cols(row))
+    }
+
+    // leaves out the columns of `row` scrolled out of view to the left. A
+    // character cut by the edge is left out whole, and the text shifted so
+    // that the rest still lines up with the other rows
+    fn skip_scrolled_cols(&self, row: VisibleRow) -> VisibleRow {
+        // turning wrapping on leaves the offset until the cursor moves
+        if self.scroll_offset_x == 0 || self.options.wrap != WrapMode::Off {
+            return row;
+        }
+
+        let widths = self.cell_widths(row.buffer_line);
+        let mut start_col = row.start_col;
+        let mut cells = 0;
+        while start_col < row.end_col && cells < self.scroll_offset_x {
+            cells += widths[start_col];
+            start_col += 1;
+        }
+
+        VisibleRow {
+            start_col,
+            indent: row.indent + cells.saturating_sub(self.scroll_offset_x),
+            ..row
+        }
+    }
+
+    /// The width of the line number gutter, for cells `font_char_width`
+    /// wide. It has room for the digits of the line count and a cell on
+    /// either side, so it widens as soon as the line count gains a digit, and
+    /// is empty without :set number.
+    pub fn gutter_width(&self, font_char_width: f64) -> f64 {
+        self.gutter_cells() as f64 * font_char_width
+    }
+
+    // the cells taken by the line number gutter
+    fn gutter_cells(&self) -> usize {
+        if self.options.number {
+            self.buffer.line_count().to_string().len() + 2
+        } else {
+            0
+        }
+    }
+
+    fn text_width(&self) -> usize {
+        self.size.w.saturating_sub(self.gutter_cells())
+    }
+
+    fn line_map(&self) -> LineMap {
+        LineMap::new(
+            self.options.wrap,
+            self.text_width(),
+            self.buffer.options().tab_width,
+            self.options.break_indent,
+            self.show_break_width(),
+        )
+    }
+
+    fn show_break_width(&self) -> usize {
+        self.options.show_break.chars().map(char_width).sum()
+    }
+
+    /// Number of characters in `line`. Cursor columns are counted in these.
+    pub fn line_len_at(&self, line: usize) -> usize {
+        self.buffer.line_len(line)
+    }
+
+    /// Number of cells `line` takes up on screen, with tabs expanded and wide
+    /// characters taking up two cells. Anything drawn is positioned in these.
+    pub fn line_display_width(&self, line: usize) -> usize {
+        self.display_col_at(line, self.line_len_at(line))
+    }
+
+    fn display_col_at(&self, line: usize, col: usize) -> usize {
+        display_col(
+            self.buffer.line(line).unwrap_or_default(),
+            col,
+            self.buffer.options().tab_width,
+        )
+    }
+
+    // combining marks are moved over together with the character they are on
+    pub fn move_cursor_left(&mut self) {
+        let before = if self.cursor.x > self.line_len_at(self.cursor.y) {
+            1
+        } else {
+            self.buffer
+                .grapheme_before(self.cursor.y, self.cursor.x)
+                .map_or(0, |grapheme| grapheme.chars().count())
+        };
+        self.cursor.x = self.cursor.x.saturating_sub(before);
+        self.scroll_to_cursor_x();
+    }
+
+    pub fn move_cursor_right(&mut self) {
+        let (line, col) = (self.cursor.y, self.cursor.x);
+        // only insert mode, motions of operators and virtual editing can move
+        // past the last grapheme
+        if !matches!(
+            self.mode,
+            Mode::Insert | Mode::Replace | Mode::OperatorPending(_)
+        ) && !self.has_virtual_space()
+            && self.buffer.grapheme_after(line, col).is_none()
+        {
+            return;
+        }
+
+        let len = self
+            .buffer
+            .grapheme_at(line, col)
+            .map_or(1, |grapheme| grapheme.chars().count());
+        self.cursor.x = (col + len).min(self.max_cursor_x(line));
+        self.scroll_to_cursor_x();
+    }
+
+    pub fn move_to_line_start(&mut self) {
+        self.cursor.x = 0;
+        self.scroll_to_cursor_x();
+    }
+
+    // onto the last grapheme, with the combining marks that follow it
+    pub fn move_to_line_end(&mut self) {
+        let (line, len) = (self.cursor.y, self.line_len_at(self.cursor.y));
+        let last = self
+            .buffer
+            .grapheme_before(line, len)
+            .map_or(0, |grapheme| grapheme.chars().count());
+        self.cursor.x = len - last;
+        self.scroll_to_cursor_x();
+    }
+
+    /// Moves the cursor to where `find` stops in the cursor line, `count`
+    /// times over, returning whether it found that many. See
+    /// `CharFind::find_in_line` for `repeated`.
+    pub fn find_char(&mut self, find: CharFind, count: usize, repeated: bool) -> bool {
+        let line = self.buffer.line(self.cursor.y).unwrap_or_default();
+        let Some(col) = find.find_in_line(line, self.cursor.x, count, repeated) else {
+            return false;
+        };
+        self.cursor.x = col;
+        self.scroll_to_cursor_x();
+        true
+    }
+
+    /// Moves the cursor to the bracket matching the one under it, like vim's
+    /// %, returning whether there was one.
+    pub fn move_to_matching_bracket(&mut self) -> bool {
+        let Some(found) = find_matching_bracket(&self.buffer, self.cursor) else {
+            return false;
+        };
+        self.set_cursor(found);
+        true
+    }
+
+    /// Joins `count` lines from the cursor line, or two if `count` is less,
+    /// like J (or gJ without `with_spaces`). Returns false on the last line,
+    /// where there is nothing to join.
+    pub fn join_lines(&mut self, count: usize, with_spaces: bool) -> bool {
+        let first = self.cursor.y;
+        let last = (first + count.max(2) - 1).min(self.buffer.line_count() - 1);
+        if last == first {
+            return false;
+        }
+
+        let col = self
+            .buffer
+            .join_lines(first..=last, with_spaces, self.cursor);
+        self.set_cursor(Position { x: col, y: first });
+        true
+    }
+
+    /// Moves the cursor to the first non-blank of `line` (counted from 0), or
+    /// of the last line if there aren't that many, like vim's G.
+    pub fn go_to_line(&mut self, line: usize) {
+        self.set_cursor(Position {
+            x: 0,
+            y: line.min(self.buffer.line_count() - 1),
+        });
+        self.move_to_first_non_blank();
+    }
+
+    pub fn move_to_first_non_blank(&mut self) {
+        let indent = self
+            .buffer
+            .line(self.cursor.y)
+            .map_or(0, |line| leading_whitespace(line).chars().count());
+        self.cursor.x = indent;
+        self.clamp_cursor_x();
+        self.scroll_to_cursor_x();
+    }
+
+    pub fn move_cursor_up(&mut self) {
+        if self.options.wrap == WrapMode::SoftWrap {
+            self.move_cursor_visual_row(false);
+        } else {
+            self.cursor.y = self.cursor.y.saturating_sub(1);
+            self.clamp_cursor_x();
+        }
+        self.scroll_to_cursor();
+    }
+
+    pub fn move_cursor_down(&mut self) {
+        if self.options.wrap == WrapMode::SoftWrap {
+            self.move_cursor_visual_row(true);
+        } else {
+            self.cursor.y = (self.cursor.y + 1).min(self.buffer.line_count() - 1);
+            self.clamp_cursor_x();
+        }
+        self.scroll_to_cursor();
+    }
+
+    // moves the cursor to the visual row above or below, keeping the column
+    // within the row
+    fn move_cursor_visual_row(&mut self, down: bool) {
+        let line_map = self.line_map();
+        let line = self.buffer.line(self.cursor.y).unwrap_or_default();
+        let rows = line_map.rows(line);
+        let row_index = line_map.row_of(line, self.cursor.x);
+        let offset = self.cursor.x - rows[row_index].start;
+
+        let (target_line, target_row) = if down {
+            if row_index + 1 < rows.len() {
+                (self.cursor.y, rows[row_index + 1].clone())
+            } else if self.cursor.y + 1 < self.buffer.line_count() {
+                let next = self.buffer.line(self.cursor.y + 1).unwrap_or_default();
+                (self.cursor.y + 1, line_map.rows(next)[0].clone())
+            } else {
+                return;
+            }
+        } else if row_index > 0 {
+            (self.cursor.y, rows[row_index - 1].clone())
+        } else if self.cursor.y > 0 {
+            let prev = self.buffer.line(self.cursor.y - 1).unwrap_or_default();
+            let prev_rows = line_map.rows(prev);
+            (self.cursor.y - 1, prev_rows[prev_rows.len() - 1].clone())
+        } else {
+            return;
+        };
+
+        self.cursor.y = target_line;
+        self.cursor.x = (target_row.start + offset).min(target_row.end.saturating_sub(1));
+        self.cursor.x = self.cursor.x.max(target_row.start);
+        self.clamp_cursor_x();
+    }
+
+    // fills the virtual space between the end of the line and the cursor with
+    // spaces, so that text is inserted where the cursor is drawn
+    fn pad_to_cursor(&mut self) {
+        let len = self.line_len_at(self.cursor.y);
+        if self.cursor.x > len {
+            self.buffer
+                .insert_text(self.cursor.y, len, &" ".repeat(self.cursor.x - len));
+        }
+    }
+
+    pub fn insert_char(&mut self, ch: char) {
+        self.pad_to_cursor();
+        self.cursor = self.buffer.insert_char(self.cursor.y, self.cursor.x, ch);
+        self.scroll_to_cursor_x();
+    }
+
+    /// Inserts a character typed by the user, auto-closing pairs and typing
+    /// over closing characters that are already there.
+    pub fn insert_typed_char(&mut self, ch: char) {
+        let mut typed = [0; 4];
+        let typed = ch.encode_utf8(&mut typed);
+        self.pad_to_cursor();
+        let next = self.buffer.grapheme_at(self.cursor.y, self.cursor.x);
+        let before = self.cursor.x.checked_sub(1).and_then(|col| {
+            let line = self.buffer.line(self.cursor.y).unwrap_or_default();
+            line.chars().nth(col)
+        });
+
+        if next == Some(typed) && self.pairs.is_closing(ch) {
+            self.cursor.x += 1;
+        } else if let Some(closing) = self.pairs.closing_after(ch, before) {
+            self.buffer
+                .insert_text(self.cursor.y, self.cursor.x, &format!("{ch}{closing}"));
+            self.cursor.x += 1;
+        } else {
+            self.insert_char(ch);
+        }
+        self.scroll_to_cursor_x();
+    }
+
+    pub fn insert_newline(&mut self) {
+        self.cursor = self
+            .buffer
+            .insert_newline_with_indent(self.cursor.y, self.cursor.x);
+        // what was typed over before no longer is where it was
+        self.replaced.clear();
+        self.scroll_to_cursor();
+    }
+
+    /// Types `ch` over the character under the cursor in replace mode, or
+    /// after the end of the line.
+    pub fn replace_typed_char(&mut self, ch: char) {
+        self.pad_to_cursor();
+        let (line, col) = (self.cursor.y, self.cursor.x);
+        let old = self.buffer.replace_char(line, col, ch);
+        self.replaced.push((line, col, old));
+        self.cursor.x += 1;
+        self.scroll_to_cursor_x();
+    }
+
+    /// Backspace in replace mode, which puts back what the last typed
+    /// character overwrote, or only moves left over text that wasn't typed.
+    pub fn restore_replaced_char(&mut self) {
+        match self.replaced.pop() {
+            Some((line, col, old)) => {
+                self.buffer.restore_char(line, col, old);
+                self.cursor = Position { x: col, y: line };
+            }
+            None => self.cursor.x = self.cursor.x.saturating_sub(1),
+        }
+        self.scroll_to_cursor_x();
+    }
+
+    /// Overwrites `count` characters from the cursor with `ch` as a single
+    /// change, like r, leaving the cursor on the last of them. Returns false
+    /// without changing anything if the line doesn't have that many left.
+    pub fn replace_chars(&mut self, ch: char, count: usize) -> bool {
+        let (line, col) = (self.cursor.y, self.cursor.x);
+        if count == 0 || col + count > self.line_len_at(line) {
+            return false;
+        }
+
+        self.buffer.begin_transaction();
+        for offset in 0..count {
+            self.buffer.replace_char(line, col + offset, ch);
+        }
+        self.buffer.commit();
+        self.cursor.x = col + count - 1;
+        true
+    }
+
+    pub fn remove_char_before_cursor(&mut self) {
+        // there is nothing to remove in virtual space, only to move over
+        if self.cursor.x > self.line_len_at(self.cursor.y) {
+            self.cursor.x -= 1;
+            return;
+        }
+
+        self.cursor = self.buffer.remove_char_before(self.cursor.y, self.cursor.x);
+        self.scroll_to_cursor();
+    }
+
+    /// Leaves visual block mode to insert before the block, or after it if
+    /// `append` is set. What is typed on its first line is inserted on every
+    /// other line of the block when insert mode is left, like I and A.
+    pub fn insert_in_block(&mut self, append: bool) {
+        let Some(selection) = self.block_selection() else {
+            return;
+        };
+        let cols = selection.block_cols();
+
+        self.enter_normal_mode();
+        self.enter_insert_mode();
+        self.start_block_insert(&selection, if append { cols.end } else { cols.start });
+    }
+
+    /// Leaves visual block mode, deleting the block and inserting in its
+    /// place on every line like I. Returns the deleted text.
+    pub fn change_block(&mut self) -> Option<RegisterText> {
+        let selection = self.block_selection()?;
+        let text = self.register_text(&selection);
+        let cols = selection.block_cols();
+
+        self.enter_normal_mode();
+        // undone together with what is typed
+        self.enter_insert_mode();
+        self.buffer.delete_columns(
+            selection.first_line()..selection.last_line() + 1,
+            cols.start,
+            cols.end,
+            self.cursor,
+        );
+        self.start_block_insert(&selection, cols.start);
+        Some(text)
+    }
+
+    /// Leaves visual block mode, replacing every character of the block with
+    /// `ch`. Lines too short to reach the block are left as they are.
+    pub fn replace_block(&mut self, ch: char) {
+        let Some(selection) = self.block_selection() else {
+            return;
+        };
+        let cols = selection.block_cols();
+
+        self.enter_normal_mode();
+        self.buffer.begin_transaction();
+        for line in selection.first_line()..=selection.last_line() {
+            let end = cols.end.min(self.line_len_at(line));
+            for col in cols.start..end {
+                self.buffer.replace_char(line, col, ch);
+            }
+        }
+        self.buffer.commit();
+        self.set_cursor(selection.start());
+    }
+
+    fn block_selection(&self) -> Option<Selection> {
+        self.selection()
+            .filter(|selection| selection.kind == SelectionKind::Block)
+    }
+
+    // puts the cursor at `col` of the first line of the block, which can be
+    // past its end, to type what is repeated on the other lines
+    fn start_block_insert(&mut self, selection: &Selection, col: usize) {
+        let line = selection.first_line();
+        self.cursor = Position { x: col, y: line };
+        self.block_insert = Some(BlockInsert {
+            line,
+            col,
+            other_lines: line + 1..selection.last_line() + 1,
+            line_len: self.line_len_at(line),
+            line_count: self.buffer.line_count(),
+        });
+        self.scroll_to_cursor();
+    }
+
+    // inserts what was typed on the first line of a block on its other lines.
+    // only text typed within the line is, not line breaks
+    fn repeat_block_insert(&mut self) {
+        let Some(insert) = self.block_insert.take() else {
+            return;
+        };
+        if self.buffer.line_count() != insert.line_count {
+            return;
+        }
+
+        // spaces padding the line up to the block aren't part of what was typed
+        let typed_len = self
+            .line_len_at(insert.line)
+            .saturating_sub(insert.line_len.max(insert.col));
+        let text: String = self
+            .buffer
+            .line(insert.line)
+            .unwrap_or_default()
+            .chars()
+            .skip(insert.col)
+            .take(typed_len)
+            .collect();
+
+        self.buffer
+            .insert_at_columns(insert.other_lines, insert.col, &text, self.cursor);
+    }
+
+    /// Leaves visual mode, returning the selected text.
+    pub fn yank_selection(&mut self) -> Option<RegisterText> {
+        let selection = self.selection()?;
+        let text = self.register_text(&selection);
+
+        self.enter_normal_mode();
+        self.set_cursor(selection.start());
+        Some(text)
+    }
+
+    /// Leaves visual mode, indenting the selected lines `count` levels further,
+    /// or less if `dedent` is set, like > and < in visual mode.
+    pub fn shift_selection(&mut self, dedent: bool, count: usize) {
+        let Some(selection) = self.selection() else {
+            return;
+        };
+        let lines = selection.first_line()..=selection.last_line();
+
+        self.enter_normal_mode();
+        self.buffer.begin_transaction();
+        for _ in 0..count {
+            if dedent {
+                self.buffer.dedent_lines(lines.clone(), self.cursor);
+            } else {
+                self.buffer.indent_lines(lines.clone(), self.cursor);
+            }
+        }
+        self.buffer.commit();
+
+        self.set_cursor(Position {
+            x: 0,
+            y: *lines.start(),
+        });
+        self.move_to_first_non_blank();
+    }
+
+    /// Leaves visual mode, commenting out the selected lines, or uncommenting
+    /// them if they all are already, like gc in visual mode.
+    pub fn toggle_comment_selection(&mut self) {
+        let Some(selection) = self.selection() else {
+            return;
+        };
+        let lines = selection.first_line()..=selection.last_line();
+
+        self.enter_normal_mode();
+        if let Some(prefix) = self.buffer.comment_prefix() {
+            self.buffer
+                .toggle_comment(lines.clone(), prefix, self.cursor);
+        }
+        self.set_cursor(Position {
+            x: 0,
+            y: *lines.start(),
+        });
+        self.move_to_first_non_blank();
+    }
+
+    /// Leaves visual mode, deleting and returning the selected text.
+    pub fn delete_selection(&mut self) -> Option<RegisterText> {
+        let selection = self.selection()?;
+        let text = self.register_text(&selection);
+
+        self.enter_normal_mode();
+        let cursor = self.buffer.delete_selection(&selection);
+        self.set_cursor(cursor);
+        Some(text)
+    }
+
+    /// Applies `operator` to the text a motion of `kind` moved over, from
+    /// `from` to `to`, and leaves operator-pending mode. Returns the text that
+    /// was deleted or yanked, for the registers.
+    pub fn apply_operator(
+        &mut self,
+        operator: Operator,
+        from: Position<usize>,
+        to: Position<usize>,
+        kind: MotionKind,
+    ) -> Option<RegisterText> {
+        self.mode = Mode::Normal;
+
+        let Some(selection) = self.motion_selection(from, to, kind) else {
+            self.set_cursor(from);
+            // changing nothing still starts insert mode, like C on an empty line
+            if operator == Operator::Change {
+                self.enter_insert_mode();
+            }
+            return None;
+        };
+        let lines = selection.first_line()..=selection.last_line();
+
+        match operator {
+            Operator::Yank => {
+                let text = self.register_text(&selection);
+                // yanking whole lines keeps the column, like yj
+                let cursor = match selection.kind {
+                    SelectionKind::Line => Position {
+                        x: from.x,
+                        y: *lines.start(),
+                    },
+                    SelectionKind::Char | SelectionKind::Block => selection.ordered().0,
+                };
+                self.set_cursor(cursor);
+                Some(text)
+            }
+            Operator::Delete => {
+                let text = self.register_text(&selection);
+                let cursor = self.buffer.delete_selection(&selection);
+                self.set_cursor(cursor);
+                if selection.kind == SelectionKind::Line {
+                    self.move_to_first_non_blank();
+                }
+                Some(text)
+            }
+            Operator::Change => {
+                let text = self.register_text(&selection);
+                // the deletion is undone together with what is typed in its place
+                self.enter_insert_mode();
+                let cursor = match selection.kind {
+                    SelectionKind::Line => self.buffer.clear_lines(lines, from),
+                    SelectionKind::Char | SelectionKind::Block => {
+                        self.buffer.delete_selection(&selection)
+                    }
+                };
+                self.set_cursor(cursor);
+                Some(text)
+            }
+            Operator::Comment => {
+                if let Some(prefix) = self.buffer.comment_prefix() {
+                    self.buffer.toggle_comment(lines.clone(), prefix, from);
+                }
+                self.set_cursor(Position {
+                    x: from.x,
+                    y: *lines.start(),
+                });
+                None
+            }
+            Operator::Indent | Operator::Dedent => {
+                if operator == Operator::Dedent {
+                    self.buffer.dedent_lines(lines.clone(), from);
+                } else {
+                    self.buffer.indent_lines(lines.clone(), from);
+                }
+                self.set_cursor(Position {
+                    x: 0,
+                    y: *lines.start(),
+                });
+                self.move_to_first_non_blank();
+                None
+            }
+            Operator::Format => {
+                let width = self.buffer.options().text_width;
+                let last_line = self.buffer.format_lines(lines, width, from);
+                self.set_cursor(Position { x: 0, y: last_line });
+                self.move_to_first_non_blank();
+                None
+            }
+        }
+    }
+
+    // the text covered by `selection`, for the registers
+    fn register_text(&self, selection: &Selection) -> RegisterText {
+        let text = self.buffer.selected_text(selection);
+        match selection.kind {
+            SelectionKind::Char | SelectionKind::Block => RegisterText::charwise(text),
+            SelectionKind::Line => RegisterText::linewise(text),
+        }
+    }
+
+    // the text a motion of `kind` from `from` to `to` moves over, if any
+    fn motion_selection(
+        &self,
+        from: Position<usize>,
+        to: Position<usize>,
+        kind: MotionKind,
+    ) -> Option<Selection> {
+        if kind == MotionKind::Linewise {
+            return Some(Selection {
+                anchor: to,
+                cursor: from,
+                kind: SelectionKind::Line,
+            });
+        }
+
+        let (start, mut end) = if (from.y, from.x) <= (to.y, to.x) {
+            (from, to)
+        } else {
+            (to, from)
+        };
+        let is_empty = (start.y, start.x) == (end.y, end.x);
+
+        if kind == MotionKind::Exclusive {
+            if is_empty {
+                return None;
+            }
+            // the column past the end of the line stands for its line break
+            end = match end.x.checked_sub(1) {
+                Some(x) => Position { x, y: end.y },
+                None => Position {
+                    x: self.line_len_at(end.y - 1),
+                    y: end.y - 1,
+                },
+            };
+        } else {
+            // an inclusive motion stops on text, never on the line break
+            let len = self.line_len_at(end.y);
+            if len == 0 && is_empty {
+                return None;
+            }
+            end.x = end.x.min(len.saturating_sub(1));
+        }
+
+        Some(Selection {
+            anchor: end,
+            cursor: start,
+            kind: SelectionKind::Char,
+        })
+    }
+
+    /// Pastes `text` after the cursor, or before it if `before` is set.
+    /// Linewise text is pasted as whole lines below or above the current line.
+    pub fn paste(&mut self, text: &RegisterText, before: bool) {
+        // filling virtual space is undone together with the paste
+        self.buffer.begin_transaction();
+
+        if text.kind == RegisterKind::Linewise {
+            let text = text.text.strip_suffix('\n').unwrap_or(&text.text);
+            let lines: Vec<String> = text.split('\n').map(str::to_string).collect();
+            let line = if before {
+                self.buffer.insert_lines_before(self.cursor.y, &lines);
+                self.cursor.y
+            } else {
+                self.buffer.insert_lines(self.cursor.y, &lines);
+                self.cursor.y + 1
+            };
+
+            let indent = leading_whitespace(self.buffer.line(line).unwrap_or_default());
+            self.set_cursor(Position {
+                x: indent.chars().count(),
+                y: line,
+            });
+        } else {
+            self.pad_to_cursor();
+            let col = if before {
+                self.cursor.x
+            } else {
+                (self.cursor.x + 1).min(self.buffer.line_len(self.cursor.y))
+            };
+            let cursor = self
+                .buffer
+                .insert_multiline_text(self.cursor.y, col, &text.text);
+            self.set_cursor(cursor);
+        }
+
+        self.buffer.commit();
+    }
+
+    /// Inserts the lines of the file at `path` below `line`, moving the cursor
+    /// to the first of them.
+    pub fn read_file(&mut self, line: usize, path: &Path) -> Result<()> {
+        if self.buffer.read_file(line + 1, path)? > 0 {
+            self.set_cursor(Position { x: 0, y: line + 1 });
+            self.move_to_first_non_blank();
+        }
+        Ok(())
+    }
+
+    pub fn trim_blank_lines(&mut self) {
+        let cursor = self.buffer.trim_blank_lines(self.cursor);
+        self.set_cursor(cursor);
+    }
+
+    pub fn save(&mut self) -> Result<()> {
+        // the cleanups are undone together, and not kept if the file can't
+        // be written
+        self.buffer.begin_transaction();
+
+        if self.buffer.options().trim_blank_lines_on_save {
+            self.trim_blank_lines();
+        }
+        if self.buffer.options().trim_trailing_whitespace_on_save {
+            let cursor = self.buffer.trim_trailing_whitespace(self.cursor);
+            self.set_cursor(cursor);
+        }
+
+        let result = self.buffer.save();
+        if result.is_ok() {
+            self.buffer.commit();
+        } else if let Some(cursor) = self.buffer.rollback() {
+            self.set_cursor(cursor);
+        }
+        result
+    }
+
+    /// Replaces matches within `scope`. A substitution over many lines is
+    /// only started, and done over the following frames with
+    /// `continue_substitute`, so that it can be cancelled.
+    pub fn substitute(&mut self, substitute: Substitute, scope: SubstituteScope) -> Result<()> {
+        let mut job = SubstituteJob::new(substitute, scope, self.buffer.line_count());
+        if job.line_count() > SUBSTITUTE_CHUNK_LINES {
+            self.substitute_job = Some(job);
+            return Ok(());
+        }
+
+        job.step(&self.buffer, usize::MAX);
+        self.finish_substitute(job)
+    }
+
+    /// Does another chunk of the substitution that is still going, returning
+    /// its result once it is done.
+    pub fn continue_substitute(&mut self) -> Option<Result<()>> {
+        let mut job = self.substitute_job.take()?;
+        if !job.step(&self.buffer, SUBSTITUTE_CHUNK_LINES) {
+            self.substitute_job = Some(job);
+            return None;
+        }
+
+        Some(self.finish_substitute(job))
+    }
+
+    fn finish_substitute(&mut self, job: SubstituteJob) -> Result<()> {
+        let pattern = job.pattern().to_string();
+        if self.buffer.substitute(job, self.cursor) == 0 {
+            bail!("Pattern not found: {pattern}");
+        }
+
+        self.set_cursor(self.cursor);
+        Ok(())
+    }
+
+    /// How far the substitution that is still going is, from 0 to 1.
+    pub fn substitute_progress(&self) -> Option<f64> {
+        self.substitute_job.as_ref().map(SubstituteJob::progress)
+    }
+
+    /// Stops the substitution that is still going. Nothing was changed yet, so
+    /// the buffer stays as it was.
+    pub fn cancel_substitute(&mut self) {
+        self.substitute_job = None;
+    }
+
+    pub fn undo(&mut self) {
+        if let Some(cursor) = self.buffer.undo() {
+            self.set_cursor(cursor);
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if let Some(cursor) = self.buffer.redo() {
+            self.set_cursor(cursor);
+        }
+    }
+
+    /// Moves to the buffer state made before (or after, if `forward`) the
+    /// current one, across branches of the undo tree, like vim's g- and g+.
+    pub fn undo_chronologically(&mut self, forward: bool) {
+        if let Some(cursor) = self.buffer.undo_chronologically(forward) {
+            self.set_cursor(cursor);
+        }
+    }
+
+    pub fn begin_search(&mut self) {
+        self.mode = Mode::Search;
+        self.search_origin = Some(SearchOrigin {
+            cursor: self.cursor,
+            scroll_offset: self.scroll_offset,
+        });
+    }
+
+    /// Previews `query` while it is being typed, if incsearch is enabled.
+    pub fn update_search(&mut self, query: &str) {
+        if !self.options.incsearch {
+            return;
+        }
+
+        let Some(origin) = &self.search_origin else {
+            return;
+        };
+        let origin_cursor = origin.cursor;
+        self.restore_search_origin();
+
+        self.highlighted_query = (!query.is_empty()).then(|| query.to_string());
+        if let Some(found) = find_next(&self.buffer, query, origin_cursor) {
+            self.set_cursor(found);
+        }
+    }
+
+    /// Moves to the first match of `query` after where the search started.
+    pub fn finish_search(&mut self, query: &str) -> Result<()> {
+        let origin_cursor = self
+            .search_origin
+            .as_ref()
+            .map_or(self.cursor, |origin| origin.cursor);
+        self.restore_search_origin();
+        self.search_origin = None;
+        self.highlighted_query = None;
+        self.enter_normal_mode();
+
+        let found = find_next(&self.buffer, query, origin_cursor)
+            .ok_or_else(|| anyhow!("Pattern not found: {query}"))?;
+        self.set_cursor(found);
+
+        Ok(())
+    }
+
+    pub fn cancel_search(&mut self) {
+        self.restore_search_origin();
+        self.search_origin = None;
+        self.highlighted_query = None;
+        self.enter_normal_mode();
+    }
+
+    fn restore_search_origin(&mut self) {
+        if let Some(origin) = &self.search_origin {
+            self.cursor = origin.cursor;
+            self.scroll_offset = origin.scroll_offset;
+        }
+    }
+
+    fn set_cursor(&mut self, cursor: Position<usize>) {
+        self.cursor = cursor;
+        self.cursor.y = self.cursor.y.min(self.buffer.line_count() - 1);
+        self.clamp_cursor_x();
+        self.scroll_to_cursor();
+    }
+
+    fn max_cursor_x(&self, line: usize) -> usize {
+        let len = self.line_len_at(line);
+
+        // virtual space is a cell per column. without wrapping the view
+        // scrolls sideways after the cursor, so it goes on past the edge of
+        // the view, but wrapped lines only have it up to the edge
+        if self.has_virtual_space() {
+            if self.options.wrap == WrapMode::Off {
+                return usize::MAX;
+            }
+            let past_end = self
+                .text_width()
+                .saturating_sub(self.line_display_width(line) + 1);
+            return len + past_end;
+        }
+
+        // in insert mode, the cursor can be placed after the last character,
+        // and so can an operator's motion, like dl on the last character
+        match self.mode {
+            Mode::Normal
+            | Mode::Command
+            | Mode::Search
+            | Mode::Visual
+            | Mode::VisualLine
+            | Mode::VisualBlock => len.saturating_sub(1),
+            Mode::Insert | Mode::Replace | Mode::OperatorPending(_) => len,
+        }
+    }
+
+    // a block keeps its columns on lines too short to reach them, like vim's
+    // virtualedit=block
+    fn has_virtual_space(&self) -> bool {
+        self.options.virtual_edit || self.mode == Mode::VisualBlock
+    }
+
+    fn clamp_cursor_x(&mut self) {
+        self.cursor.x = self.cursor.x.min(self.max_cursor_x(self.cursor.y));
+    }
+
+    // the display column of the cursor, where virtual space past the end of
+    // the line is a cell per column
+    fn cursor_display_col(&self) -> usize {
+        let len = self.line_len_at(self.cursor.y);
+        if self.cursor.x > len {
+            self.line_display_width(self.cursor.y) + self.cursor.x - len
+        } else {
+            self.display_col_at(self.cursor.y, self.cursor.x)
+        }
+    }
+
+    // the column (in chars) of `line` covering display column `display`,
+    // counting virtual space past the end of the line
+    fn col_at_display(&self, line: usize, display: usize) -> usize {
+        let text = self.buffer.line(line).unwrap_or_default();
+        let tab_width = self.buffer.options().tab_width;
+
+        let mut cells = 0;
+        for (col, ch) in text.chars().enumerate() {
+            let width = char_width_at(ch, cells, tab_width);
+            if display < cells + width {
+                return col;
+            }
+            cells += width;
+        }
+
+        text.chars().count() + (display - cells)
+    }
+
+    // cells kept between the cursor and the left and right edges of the view
+    fn side_scroll_margin(&self) -> usize {
+        self.options
+            .side_scroll_off
+            .min(self.text_width().saturating_sub(1) / 2)
+    }
+
+    // scrolls sideways until the cursor is in view, away from the edges by
+    // the side scroll margin
+    fn scroll_to_cursor_x(&mut self) {
+        if self.options.wrap != WrapMode::Off {
+            self.scroll_offset_x = 0;
+            return;
+        }
+
+        let width = self.text_width();
+        if width == 0 {
+            return;
+        }
+
+        let margin = self.side_scroll_margin();
+        let col = self.cursor_display_col();
+        if col < self.scroll_offset_x + margin {
+            self.scroll_offset_x = col.saturating_sub(margin);
+        } else if col + margin >= self.scroll_offset_x + width {
+            self.scroll_offset_x = col + margin + 1 - width;
+        }
+    }
+
+    /// Scrolls the view `cells` to the right, or to the left if negative, like
+    /// vim's zl and zh. The cursor is moved along if it would leave the view.
+    pub fn scroll_horizontally(&mut self, cells: isize) {
+        if self.options.wrap != WrapMode::Off {
+            return;
+        }
+
+        self.scroll_offset_x = self.scroll_offset_x.saturating_add_signed(cells);
+
+        let margin = self.side_scroll_margin();
+        let first = self.scroll_offset_x + margin;
+        let last = (self.scroll_offset_x + self.text_width()).saturating_sub(margin + 1);
+        let col = self.cursor_display_col();
+        if col < first || col > last {
+            let target = col.clamp(first, last.max(first));
+            self.cursor.x = self.col_at_display(self.cursor.y, target);
+            self.clamp_cursor_x();
+        }
+
+        // a cursor that can't follow, at the end of a short line, stops the scroll
+        self.scroll_to_cursor_x();
+    }
+
+    fn scroll_to_cursor(&mut self) {
+        self.scroll_to_cursor_x();
+
+        if self.cursor.y < self.scroll_offset {
+            self.scroll_offset = self.cursor.y;
+        }
+        if self.size.h == 0 {
+            return;
+        }
+
+        let margin = self.scroll_margin();
+        // near the end of the buffer, there is nothing to keep below the cursor
+        let margin_below = margin.min(self.buffer.line_count() - 1 - self.cursor.y);
+
+        while self.scroll_offset > 0 && self.visual_rows_to_cursor() < margin {
+            self.scroll_offset -= 1;
+        }
+
+        // the top line is always shown from its first visual row, so
+        // scroll by whole lines until the cursor's row fits
+        while self.scroll_offset < self.cursor.y
+            && self.visual_rows_to_cursor() + margin_below >= self.size.h
+        {
+            self.scroll_offset += 1;
+        }
+    }
+
+    // rows kept between the cursor and the edges of the view, which centers
+    // the cursor when there is no room for both margins
+    fn scroll_margin(&self) -> usize {
+        self.options
+            .scroll_off
+            .min(self.size.h.saturating_sub(1) / 2)
+    }
+
+    // number of visual rows from the top of the view to the cursor's row
+    fn visual_rows_to_cursor(&self) -> usize {
+        let line_map = self.line_map();
+
+        let rows_before: usize = (self.scroll_offset..self.cursor.y)
+            .map(|line| {
+                line_map
+                    .rows(self.buffer.line(line).unwrap_or_default())
+                    .len()
+            })
+            .sum();
+        let cursor_line = self.buffer.line(self.cursor.y).unwrap_or_default();
+
+        rows_before + line_map.row_of(cursor_line, self.cursor.x)
+    }
+
+    pub fn render(
+        &self,
+        renderer: &mut AppRenderer,
+        font: &AppFont,
+        font_size: f32,
+        theme: &Theme,
+        focused: bool,
+        cursor_visible: bool,
+    ) {
+        let bounds = font.variations(&[]).measure_text(font_size, " ");
+        let single_space_width = bounds.w as f64;
+        let font_height = bounds.h as f64;
+
+        let gutter_cells = self.gutter_cells();
+        let gutter_width = self.gutter_width(single_space_width);
+        let text_width = self.text_width() as f64 * single_space_width;
+        let tab_width = self.buffer.options().tab_width;
+
+        // a hidden cursor (while blinking) isn't on any row
+        let cursor_row = self
+            .visible_rows()
+            .filter(|row| row.buffer_line == self.cursor.y && row.start_col <= self.cursor.x)
+            .last()
+            .filter(|_| cursor_visible);
+
+        let mut rows_drawn = 0;
+
+        // part of the way to the next line while scrolling smoothly
+        let scroll_fraction = self.scroll_position - self.first_drawn_line() as f64;
+        renderer.push_transform(Affine::translate((0.0, -scroll_fraction * font_height)));
+
+        self.visible_rows().for_each(|row| {
+            let line = self.buffer.line(row.buffer_line).unwrap_or_default();
+            // columns are positioned by the cells before them, relative to the row
+            let widths = self.cell_widths(row.buffer_line);
+            let cells_before = |col: usize| widths[..col.min(widths.len())].iter().sum::<usize>();
+            let row_start = cells_before(row.start_col);
+            let cell_x = |col: usize| (cells_before(col) - row_start) as f64 * single_space_width;
+
+            // draw each row in its own coordinates
+            renderer.push_transform(Affine::translate((0.0, font_height * row.screen_y as f64)));
+
+            if self.options.number && !row.is_wrapped_continuation {
+                renderer.draw_text(DrawTextOptions::<&Brush, _, _> {
+                    font,
+                    size: font_size,
+                    transform: Affine::IDENTITY,
+                    glyph_transform: None,
+                    brush: &Brush::Solid(theme.line_number),
+                    style: Fill::NonZero,
+                    // right aligned, with a cell to separate it from the text
+                    text: format!("{:>1$}", row.buffer_line + 1, gutter_cells - 1),
+                    _marker: PhantomData,
+                });
+            }
+
+            // marks where a line continues from the row above
+            if row.is_wrapped_continuation && !self.options.show_break.is_empty() {
+                let marker_col = gutter_cells + row.indent.saturating_sub(self.show_break_width());
+                renderer.draw_text(DrawTextOptions::<&Brush, _, _> {
+                    font,
+                    size: font_size,
+                    transform: Affine::translate((marker_col as f64 * single_space_width, 0.0)),
+                    glyph_transform: None,
+                    brush: &Brush::Solid(theme.show_break_color),
+                    style: Fill::NonZero,
+                    text: &self.options.show_break,
+                    _marker: PhantomData,
+                });
+            }
+
+            // everything else is drawn after the gutter and the break indent
+            let indent = row.indent as f64 * single_space_width;
+            renderer.push_transform(Affine::translate((gutter_width + indent, 0.0)));
+
+            if row.buffer_line == self.cursor.y {
+                // only spans the text area, so that it does not bleed into the
+                // gutter or anything beside the view
+                renderer.draw_fill_rectangle(DrawFillRectangleOptions {
+                    pos: Position { x: -indent, y: 0.0 },
+                    size: Size {
+                        w: text_width,
+                        h: font_height,
+                    },
+                    fill_color: theme.current_line_bg,
+                });
+            }
+
+            // rulers stay at the same cells on every row, whatever the row is shifted by
+            self.options
+                .color_columns
+                .iter()
+                .filter_map(|column| column.checked_sub(self.scroll_offset_x))
+                .filter(|&column| column <= self.text_width())
+                .for_each(|column| {
+                    renderer.draw_fill_rectangle(DrawFillRectangleOptions {
+                        pos: Position {
+                            x: column as f64 * single_space_width - indent,
+                            y: 0.0,
+                        },
+                        size: Size {
+                            w: 1.0,
+                            h: font_height,
+                        },
+                        fill_color: theme.color_column_color,
+                    });
+                });
+
+            let row_line_len = self.line_len_at(row.buffer_line);
+
+            let selection = self.selection();
+            if let Some(cols) = selection
+                .filter(|selection| selection.kind == SelectionKind::Block)
+                .and_then(|selection| selection.cols(row.buffer_line, row_line_len))
+            {
+                // columns past the end of the line are a cell each, on its last row
+                let is_last_row = row.end_col == row_line_len;
+                let col_x = |col: usize| {
+                    let past_end = col.saturating_sub(row_line_len);
+                    cell_x(col - past_end) + past_end as f64 * single_space_width
+                };
+                let start = cols.start.max(row.start_col);
+                let end = if is_last_row {
+                    cols.end
+                } else {
+                    cols.end.min(row.end_col)
+                };
+
+                if start < end {
+                    renderer.draw_fill_rectangle(DrawFillRectangleOptions {
+                        pos: Position {
+                            x: col_x(start),
+                            y: 0.0,
+                        },
+                        size: Size {
+                            w: col_x(end) - col_x(start),
+                            h: font_height,
+                        },
+                        fill_color: theme.selection_bg,
+                    });
+                }
+            } else if let Some(cols) =
+                selection.and_then(|selection| selection.cols(row.buffer_line, row_line_len))
+            {
+                let start = cols.start.max(row.start_col);
+                let end = cols.end.min(row.end_col);
+                // selecting the end of the line shows as one more cell after it
+                let past_end = cols.end > row.end_col && row.end_col == row_line_len;
+                let extra = if past_end { single_space_width } else { 0.0 };
+
+                if start < end || (start == end && past_end) {
+                    renderer.draw_fill_rectangle(DrawFillRectangleOptions {
+                        pos: Position {
+                            x: cell_x(start),
+                            y: 0.0,
+                        },
+                        size: Size {
+                            w: cell_x(end) - cell_x(start) + extra,
+                            h: font_height,
+                        },
+                        fill_color: theme.selection_bg,
+                    });
+                }
+            }
+
+            if !row.is_wrapped_continuation && !line.trim().is_empty() {
+                let indent = leading_whitespace(line);
+                let indent_depth = display_col(indent, indent.len(), tab_width) / tab_width;
+
+                // guides scrolled out of view to the left aren't drawn
+                (0..indent_depth)
+                    .map(|level| level * tab_width)
+                    .filter(|&guide_col| guide_col + row.indent >= row_start)
+                    .for_each(|guide_col| {
+                        renderer.draw_fill_rectangle(DrawFillRectangleOptions {
+                            pos: Position {
+                                x: (guide_col as f64 - row_start as f64) * single_space_width,
+                                y: 0.0,
+                            },
+                            size: Size {
+                                w: 1.0,
+                                h: font_height,
+                            },
+                            fill_color: theme.indent_guide_color,
+                        });
+                    });
+            }
+
+            let trailing_start = line.trim_end_matches([' ', '\t']).chars().count();
+            let cursor_in_trailing =
+                row.buffer_line == self.cursor.y && self.cursor.x >= trailing_start;
+
+            if trailing_start < row_line_len && !cursor_in_trailing {
+                let start = trailing_start.max(row.start_col);
+                let end = row.end_col;
+
+                if start < end {
+                    renderer.draw_fill_rectangle(DrawFillRectangleOptions {
+                        pos: Position {
+                            x: cell_x(start),
+                            y: 0.0,
+                        },
+                        size: Size {
+                            w: cell_x(end) - cell_x(start),
+                            h: font_height,
+                        },
+                        fill_color: theme.trailing_ws_color,
+                    });
+                }
+            }
+
+            if let Some(query) = &self.highlighted_query {
+                let query_len = query.chars().count();
+
+                find_in_line(line, query)
+                    .into_iter()
+                    .filter(|col| (row.start_col..row.end_col).contains(col))
+                    .for_each(|col| {
+                        let end = (col + query_len).min(row.end_col);
+
+                        renderer.draw_fill_rectangle(DrawFillRectangleOptions {
+                            pos: Position {
+                                x: cell_x(col),
+                                y: 0.0,
+                            },
+                            size: Size {
+                                w: cell_x(end) - cell_x(col),
+                                h: font_height,
+                            },
+                            fill_color: theme.search_match_bg,
+                        });
+                    });
+            }
+
+            if let Some(style) = self.options.link_underline {
+                find_links(line)
+                    .into_iter()
+                    .filter(|link| link.start < row.end_col && link.end > row.start_col)
+                    .for_each(|link| {
+                        let start = link.start.max(row.start_col);
+                        let end = link.end.min(row.end_col);
+
+                        renderer.draw_underline(DrawUnderlineOptions {
+                            pos: Position {
+                                x: cell_x(start),
+                                y: font_height - 2.0,
+                            },
+                            width: cell_x(end) - cell_x(start),
+                            color: theme.link_underline,
+                            style,
+                        });
+                    });
+            }
+
+            if let Some(cursor_row) = &cursor_row {
+                if cursor_row.screen_y == row.screen_y {
+                    // columns past the end of the line are a cell each
+                    let line_len = self.line_len_at(row.buffer_line);
+                    let cursor_x = if self.cursor.x >= line_len {
+                        (self.line_display_width(row.buffer_line) + self.cursor.x
+                            - line_len
+                            - row_start) as f64
+                            * single_space_width
+                    } else {
+                        cell_x(self.cursor.x)
+                    };
+                    // as wide as the character under the cursor, but always visible
+                    let cursor_cells = line.chars().nth(self.cursor.x).map_or(1, |ch| {
+                        char_width_at(ch, display_col(line, self.cursor.x, tab_width), tab_width)
+                    });
+
+                    let cell_width = cursor_cells.max(1) as f64 * single_space_width;
+
+                    renderer.draw_cursor(DrawCursorOptions {
+                        shape: self.options.cursor_shapes.shape_for(self.mode),
+                        pos: Position {
+                            x: cursor_x,
+                            y: 0.0,
+                        },
+                        cell_size: Size {
+                            w: cell_width,
+                            h: font_height,
+                        },
+                        color: theme.cursor_color(self.mode),
+                    });
+                }
+            }
+
+            renderer.draw_colored_runs(DrawColoredRunsOptions {
+                font,
+                size: font_size,
+                origin: Position { x: 0.0, y: 0.0 },
+                runs: &self.row_runs(&row, cursor_row.as_ref(), theme),
+            });
+
+            renderer.pop_transform();
+            renderer.pop_transform();
+            rows_drawn = row.screen_y + 1;
+        });
+
+        if self.options.debug_grid {
+            self.render_debug_grid(renderer, single_space_width, font_height, theme);
+        }
+
+        // rows past the end of the buffer
+        let total_tildes = self.size.h.saturating_sub(rows_drawn);
+
+        renderer.draw_text(DrawTextOptions::<&Brush, _, _> {
+            font,
+            size: font_size,
+            transform: Affine::translate((0.0, font_height * rows_drawn as f64)),
+            glyph_transform: None,
+            brush: &Brush::Solid(theme.foreground),
+            style: Fill::NonZero,
+            text: "~\n".repeat(total_tildes),
+            _marker: PhantomData,
+        });
+
+        renderer.pop_transform();
+
+        if self.has_scrollbar() {
+            self.render_scrollbar(renderer, single_space_width, font_height, theme);
+        }
+
+        // makes the focused view stand out from the others
+        if !focused && self.options.dim_inactive {
+            renderer.draw_fill_rectangle(DrawFillRectangleOptions {
+                pos: Position { x: 0.0, y: 0.0 },
+                size: Size {
+                    w: self.size.w as f64 * single_space_width,
+                    h: self.size.h as f64 * font_height,
+                },
+                fill_color: theme.inactive_dim_color,
+            });
+        }
+    }
+
+    /// The cell the cursor is drawn at, from the top left of the view, if it
+    /// is in view.
+    pub fn cursor_screen_pos(&self) -> Option<Position<usize>> {
+        let row = self
+            .visible_rows()
+            .filter(|row| row.buffer_line == self.cursor.y && row.start_col <= self.cursor.x)
+            .last()?;
+
+        // columns past the end of the line are a cell each
+        let widths = self.cell_widths(row.buffer_line);
+        let cells_before = |col: usize| widths[..col.min(widths.len())].iter().sum::<usize>();
+        let cursor_cells = cells_before(self.cursor.x) + self.cursor.x.saturating_sub(widths.len());
+        // a row scrolled past its end is also scrolled past some virtual space
+        let scrolled_past_end = self
+            .scroll_offset_x
+            .saturating_sub(cells_before(row.start_col));
+
+        Some(Position {
+            x: self.gutter_cells() + row.indent + cursor_cells
+                - cells_before(row.start_col)
+                - scrolled_past_end,
+            y: row.screen_y,
+        })
+    }
+
+    // the text of `row` in runs of colors and styles, where the character under
+    // a block cursor is drawn in the cursor text color to stay readable
+    fn row_runs(
+        &self,
+        row: &VisibleRow,
+        cursor_row: Option<&VisibleRow>,
+        theme: &Theme,
+    ) -> Vec<TextRun> {
+        let line = self.buffer.line(row.buffer_line).unwrap_or_default();
+        let conceals = self.conceals(row.buffer_line);
+        let widths = self.cell_widths(row.buffer_line);
+        let run = |start: usize, end: usize, look: RunLook| TextRun {
+            text: display_text(
+                line,
+                start..end,
+                &widths,
+                &conceals,
+                self.options.conceal_level,
+            ),
+            color: look.color,
+            weight: look.weight,
+            style: look.style,
+        };
+
+        // how each column of the row is drawn
+        let plain = RunLook {
+            color: theme.foreground,
+            weight: FontWeight::Regular,
+            style: FontStyle::Normal,
+        };
+        let mut looks = vec![plain; row.end_col - row.start_col];
+        // where the columns from `start` to `end` of the line are in `looks`
+        let row_range = |start: usize, end: usize| {
+            let start = start.clamp(row.start_col, row.end_col);
+            let end = end.clamp(start, row.end_col);
+            start - row.start_col..end - row.start_col
+        };
+
+        for span in self.highlight_spans(row.buffer_line).iter() {
+            looks[row_range(span.start, span.end)].fill(RunLook {
+                color: theme.highlight_color(span.group),
+                weight: span.weight,
+                style: span.style,
+            });
+        }
+
+        let has_block_cursor = cursor_row
+            .is_some_and(|cursor_row| cursor_row.screen_y == row.screen_y)
+            && self.options.cursor_shapes.shape_for(self.mode) == CursorShape::Block;
+
+        if has_block_cursor {
+            // combining marks belong to the character under the cursor
+            let cursor_end = self.cursor.x
+                + self
+                    .buffer
+                    .grapheme_at(row.buffer_line, self.cursor.x)
+                    .map_or(1, |grapheme| grapheme.chars().count());
+            looks[row_range(self.cursor.x, cursor_end)]
+                .iter_mut()
+                .for_each(|look| look.color = theme.cursor_text);
+        }
+
+        let mut runs = vec![];
+        let mut start = row.start_col;
+        for (i, look) in looks.iter().enumerate() {
+            let col = row.start_col + i;
+            if looks.get(i + 1) != Some(look) {
+                runs.push(run(start, col + 1, *look));
+                start = col + 1;
+            }
+        }
+
+        if runs.is_empty() {
+            runs.push(run(row.start_col, row.end_col, plain));
+        }
+
+        runs
+    }
+
+    // the highlighted parts of `line`, for drawing it in color
+    fn highlight_spans(&self, line: usize) -> Cow<'_, [HighlightSpan]> {
+        if let Some(Some(spans)) = self.highlight_cache.get(line) {
+            return Cow::Borrowed(spans);
+        }
+
+        Cow::Owned(
+            self.highlighter
+                .as_ref()
+                .map_or_else(Vec::new, |highlighter| {
+                    highlighter.highlight(&self.buffer, line)
+                }),
+        )
+    }
+
+    /// Forgets the highlighting of the lines changed since this was last
+    /// called, and highlights the lines shown that aren't yet, so that drawing
+    /// only highlights what has changed.
+    pub fn update_highlights(&mut self) {
+        let splices = self.buffer.take_splices();
+
+        if self.highlight_filetype != self.buffer.filetype() {
+            self.highlight_filetype = self.buffer.filetype();
+            self.highlighter = highlighter(self.highlight_filetype);
+            self.highlight_cache.clear();
+        }
+
+        for splice in &splices {
+            // lines past the end of the cache haven't been highlighted anyway
+            if splice.start >= self.highlight_cache.len() {
+                continue;
+            }
+            let old_end = splice.old_end.min(self.highlight_cache.len());
+            self.highlight_cache.splice(
+                splice.start..old_end,
+                iter::repeat_n(None, splice.new_end - splice.start),
+            );
+        }
+        self.highlight_cache.resize(self.buffer.line_count(), None);
+
+        let mut lines: Vec<usize> = self.visible_rows().map(|row| row.buffer_line).collect();
+        lines.dedup();
+
+        let Some(highlighter) = self.highlighter.as_mut() else {
+            return;
+        };
+        for lines in highlighter.update(&self.buffer, &splices) {
+            let end = lines.end.min(self.highlight_cache.len());
+            let start = lines.start.min(end);
+            self.highlight_cache[start..end].fill(None);
+        }
+
+        for line in lines {
+            let spans = &mut self.highlight_cache[line];
+            if spans.is_none() {
+                *spans = Some(highlighter.highlight(&self.buffer, line));
+            }
+        }
+    }
+
+    // the scrollbar is only shown when there is somewhere to scroll to
+    fn has_scrollbar(&self) -> bool {
+        self.options.scrollbar && self.max_scroll_offset() > 0
+    }
+
+    // the furthest the view scrolls, which leaves the last line at the bottom
+    // of the view, or at the top when scrolling past the end
+    fn max_scroll_offset(&self) -> usize {
+        let line_count = self.buffer.line_count();
+        if self.options.scroll_past_end {
+            line_count - 1
+        } else {
+            line_count.saturating_sub(self.size.h)
+        }
+    }
+
+    // the lines the scrollbar track stands for, including those past the end
+    fn scroll_range(&self) -> usize {
+        self.max_scroll_offset() + self.size.h
+    }
+
+    // draws the scrollbar over the last column, with a thumb for the visible lines
+    fn render_scrollbar(
+        &self,
+        renderer: &mut AppRenderer,
+        cell_width: f64,
+        cell_height: f64,
+        theme: &Theme,
+    ) {
+        let x = (self.size.w as f64 - SCROLLBAR_WIDTH) * cell_width;
+        let width = SCROLLBAR_WIDTH * cell_width;
+        let viewport_height = self.size.h as f64 * cell_height;
+        let total_lines = self.scroll_range() as f64;
+
+        renderer.draw_fill_rectangle(DrawFillRectangleOptions {
+            pos: Position { x, y: 0.0 },
+            size: Size {
+                w: width,
+                h: viewport_height,
+            },
+            fill_color: theme.scrollbar_track_color,
+        });
+
+        let thumb_height = self.size.h as f64 / total_lines * viewport_height;
+        let thumb_top = self.scroll_position / total_lines * viewport_height;
+
+        renderer.draw_fill_rectangle(DrawFillRectangleOptions {
+            pos: Position { x, y: thumb_top },
+            size: Size {
+                w: width,
+                // the thumb stays big enough to see for long buffers
+                h: thumb_height.max(cell_height / 2.0),
+            },
+            fill_color: theme.scrollbar_thumb_color,
+        });
+    }
+
+    /// The character of the buffer at `position`, in cells from the top left
+    /// of the view, or `None` if there is no character there.
+    pub fn screen_pos_to_cursor(&self, position: Position<f64>) -> Option<Position<usize>> {
+        if position.x < 0.0 || position.y < 0.0 {
+            return None;
+        }
+
+        let screen_y = position.y as usize;
+        let row = self.visible_rows().find(|row| row.screen_y == screen_y)?;
+
+        let text_x = (position.x as usize).checked_sub(self.gutter_cells() + row.indent)?;
+        if text_x >= self.text_width() {
+            return None;
+        }
+
+        let widths = self.cell_widths(row.buffer_line);
+
+        let mut start = 0;
+        for (col, width) in widths
+            .iter()
+            .enumerate()
+            .take(row.end_col)
+            .skip(row.start_col)
+        {
+            if (start..start + width).contains(&text_x) {
+                return Some(Position {
+                    x: col,
+                    y: row.buffer_line,
+                });
+            }
+            start += width;
+        }
+
+        None
+    }
+
+    // the markup of `line` that is concealed, which is revealed on the cursor
+    // line so that it can be edited
+    fn conceals(&self, line: usize) -> Vec<Conceal> {
+        if self.options.conceal_level == 0 || line == self.cursor.y {
+            return vec![];
+        }
+
+        find_conceals(
+            self.buffer.filetype(),
+            self.buffer.line(line).unwrap_or_default(),
+        )
+    }
+
+    // the cells each character of `line` takes up as drawn
+    fn cell_widths(&self, line: usize) -> Vec<usize> {
+        cell_widths(
+            self.buffer.line(line).unwrap_or_default(),
+            self.buffer.options().tab_width,
+            &self.conceals(line),
+            self.options.conceal_level,
+        )
+    }
+
+    /// Handles a mouse click at `position`, in cells from the top left of the
+    /// view, returning whether the view used it.
+    pub fn handle_mouse_click(&mut self, position: Position<f64>) -> bool {
+        let on_scrollbar = position.x >= self.size.w as f64 - SCROLLBAR_WIDTH
+            && position.x < self.size.w as f64
+            && position.y >= 0.0
+            && position.y < self.size.h as f64;
+        if !self.has_scrollbar() || !on_scrollbar {
+            return false;
+        }
+
+        // jump to the same proportion of the buffer as the click is of the track
+        let line = (position.y / self.size.h as f64 * self.scroll_range() as f64) as usize;
+        self.scroll_offset = line.min(self.max_scroll_offset());
+        self.keep_cursor_in_view();
+
+        true
+    }
+
+    /// Scrolls the view `lines` down, or up if negative, like the mouse wheel.
+    /// The cursor is moved along if it would leave the view.
+    pub fn scroll_vertically(&mut self, lines: isize) {
+        self.scroll_offset = self
+            .scroll_offset
+            .saturating_add_signed(lines)
+            .min(self.max_scroll_offset());
+        self.keep_cursor_in_view();
+    }
+
+    /// Scrolls the view `pages` screens down, or up if negative, like vim's
+    /// CTRL-F and CTRL-B. The cursor goes to the first line in view.
+    pub fn scroll_pages(&mut self, pages: isize) {
+        let lines = pages.saturating_mul(self.size.h as isize);
+        self.scroll_offset = self
+            .scroll_offset
+            .saturating_add_signed(lines)
+            .min(self.max_scroll_offset());
+        self.cursor.y = self.scroll_offset;
+        self.keep_cursor_in_view();
+    }
+
+    /// Scrolls the view `halves` half screens down, or up if negative, like
+    /// vim's CTRL-D and CTRL-U. The cursor moves as many lines, but stops at
+    /// the ends of the buffer.
+    pub fn scroll_half_pages(&mut self, halves: isize) {
+        let lines = halves.saturating_mul((self.size.h / 2).max(1) as isize);
+        self.scroll_offset = self
+            .scroll_offset
+            .saturating_add_signed(lines)
+            .min(self.max_scroll_offset());
+        self.cursor.y = self
+            .cursor
+            .y
+            .saturating_add_signed(lines)
+            .min(self.buffer.line_count() - 1);
+        self.keep_cursor_in_view();
+    }
+
+    /// Scrolls the cursor's row to the top of the view, below the scroll
+    /// margin, like vim's zt.
+    pub fn scroll_cursor_to_top(&mut self) {
+        self.scroll_cursor_to_row(self.scroll_margin());
+    }
+
+    /// Scrolls the cursor's row to the middle of the view, like vim's zz.
+    pub fn scroll_cursor_to_center(&mut self) {
+        self.scroll_cursor_to_row(self.size.h.saturating_sub(1) / 2);
+    }
+
+    /// Scrolls the cursor's row to the bottom of the view, above the scroll
+    /// margin, like vim's zb.
+    pub fn scroll_cursor_to_bottom(&mut self) {
+        self.scroll_cursor_to_row(self.size.h.saturating_sub(1 + self.scroll_margin()));
+    }
+
+    // scrolls so that the cursor's row is `row` rows from the top of the
+    // view, or as close as the ends of the buffer allow
+    fn scroll_cursor_to_row(&mut self, row: usize) {
+        self.scroll_offset = self.cursor.y;
+        while self.scroll_offset > 0 && self.visual_rows_to_cursor() < row {
+            self.scroll_offset -= 1;
+            // a wrapped line that doesn't fit above the row stays below the top
+            if self.visual_rows_to_cursor() > row {
+                self.scroll_offset += 1;
+                break;
+            }
+        }
+        self.scroll_offset = self.scroll_offset.min(self.max_scroll_offset());
+    }
+
+    // moves the cursor on screen and out of the scroll margins, so that
+    // scrolling to it doesn't undo a scroll
+    fn keep_cursor_in_view(&mut self) {
+        let margin = self.scroll_margin();
+        let last_line = self.buffer.line_count() - 1;
+        // the top of the buffer needs no margin above it
+        let margin_above = if self.scroll_offset == 0 { 0 } else { margin };
+        let first = (self.scroll_offset + margin_above).min(last_line);
+        let last =
+            (self.scroll_offset + self.size.h.saturating_sub(1 + margin)).clamp(first, last_line);
+        self.cursor.y = self.cursor.y.clamp(first, last);
+        self.clamp_cursor_x();
+    }
+
+    // draws the cell grid over the text area, every column and row
+    fn render_debug_grid(
+        &self,
+        renderer: &mut AppRenderer,
+        cell_width: f64,
+        cell_height: f64,
+        theme: &Theme,
+    ) {
+        let left = self.gutter_width(cell_width);
+        let columns = self.text_width();
+        let rows = self.size.h;
+
+        let right = left + columns as f64 * cell_width;
+        let bottom = rows as f64 * cell_height;
+
+        (0..=columns).for_each(|col| {
+            let x = left + col as f64 * cell_width;
+
+            renderer.draw_line(DrawLineOptions {
+                start: Position { x, y: 0.0 },
+                end: Position { x, y: bottom },
+                width: 1.0,
+                color: theme.debug_grid_color,
+            });
+        });
+
+        (0..=rows).for_each(|row| {
+            let y = row as f64 * cell_height;
+
+            renderer.draw_line(DrawLineOptions {
+                start: Position { x: left, y },
+                end: Position { x: right, y },
+                width: 1.0,
+                color: theme.debug_grid_color,
+            });
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::super::{filetype::FileType, highlight::HighlightGroup};
+    use super::*;
+    use crate::base::{test_font, BaseAppRenderer};
+
+    // a view of `text` that is `w` cells wide and `h` rows high
+    fn view(text: &str, w: usize, h: usize) -> View {
+        let mut view = View::new(Buffer::from(text));
+        view.options_mut().smooth_scroll = false;
+        view.resize(Size { w, h });
+        view
+    }
+
+    // the buffer line of each visible row
+    fn rows(view: &View) -> Vec<usize> {
+        view.visible_rows().map(|row| row.buffer_line).collect()
+    }
+
+    #[test]
+    fn visible_rows_show_lines_whole_without_wrap() {
+        let view = view("short\na much longer line\nend", 8, 10);
+
+        assert_eq!(rows(&view), [0, 1, 2]);
+        assert!(view.visible_rows().all(|row| !row.is_wrapped_continuation));
+    }
+
+    #[test]
+    fn visible_rows_split_lines_with_wrap() {
+        let mut view = view("abcdefghij\nxy", 4, 10);
+        view.options_mut().wrap = WrapMode::SoftWrap;
+
+        let rows: Vec<_> = view
+            .visible_rows()
+            .map(|row| (row.buffer_line, row.start_col..row.end_col))
+            .collect();
+        assert_eq!(rows, [(0, 0..4), (0, 4..8), (0, 8..10), (1, 0..2)]);
+        let continuations: Vec<bool> = view
+            .visible_rows()
+            .map(|row| row.is_wrapped_continuation)
+            .collect();
+        assert_eq!(continuations, [false, true, true, false]);
+    }
+
+    #[test]
+    fn visible_rows_fill_the_height_of_the_view() {
+        let mut view = view("a\nb\nc\nd\ne", 10, 2);
+        assert_eq!(rows(&view), [0, 1]);
+
+        (0..3).for_each(|_| view.move_cursor_down());
+        view.animate_scroll(0.0);
+
+        assert_eq!(rows(&view), [2, 3]);
+        let screen_rows: Vec<usize> = view.visible_rows().map(|row| row.screen_y).collect();
+        assert_eq!(screen_rows, [0, 1]);
+    }
+
+    #[test]
+    fn scroll_to_cursor_scrolls_as_little_as_needed() {
+        let text = ["line"; 10].join("\n");
+        let mut view = view(&text, 20, 3);
+
+        (0..5).for_each(|_| view.move_cursor_down());
+        assert_eq!(view.scroll_offset, 3);
+        view.move_cursor_up();
+        assert_eq!(view.scroll_offset, 3);
+        (0..3).for_each(|_| view.move_cursor_up());
+        assert_eq!(view.scroll_offset, 1);
+    }
+
+    #[test]
+    fn scroll_to_cursor_keeps_the_scrolloff_margin() {
+        let text = ["line"; 10].join("\n");
+        let mut view = view(&text, 20, 4);
+        view.options_mut().scroll_off = 1;
+
+        (0..5).for_each(|_| view.move_cursor_down());
+        assert_eq!(view.scroll_offset, 3);
+        (0..2).for_each(|_| view.move_cursor_up());
+        assert_eq!(view.scroll_offset, 2);
+        // there is nothing to keep below the last line
+        (0..6).for_each(|_| view.move_cursor_down());
+        assert_eq!(view.scroll_offset, 6);
+    }
+
+    #[test]
+    fn scroll_to_cursor_scrolls_long_lines_sideways() {
+        let mut view = view("abcdefghij", 5, 3);
+
+        (0..7).for_each(|_| view.move_cursor_right());
+        view.scroll_to_cursor();
+        assert_eq!(view.scroll_offset_x, 3);
+
+        view.options_mut().wrap = WrapMode::SoftWrap;
+        view.scroll_to_cursor();
+        assert_eq!(view.scroll_offset_x, 0);
+    }
+
+    fn buffer_lines(view: &View) -> Vec<&str> {
+        (0..view.buffer().line_count())
+            .filter_map(|line| view.buffer().line(line))
+            .collect()
+    }
+
+    #[test]
+    fn yanked_lines_are_pasted_below_or_above_the_cursor_line() {
+        let mut view = view("a\n  b\nc", 20, 10);
+        let at_b = Position { x: 3, y: 1 };
+
+        let text = view
+            .apply_operator(Operator::Yank, at_b, at_b, MotionKind::Linewise)
+            .unwrap();
+        assert_eq!(text, RegisterText::linewise("  b\n"));
+
+        view.paste(&text, false);
+        assert_eq!(buffer_lines(&view), ["a", "  b", "  b", "c"]);
+        assert_eq!((view.cursor.x, view.cursor.y), (2, 2));
+
+        view.set_cursor(Position { x: 0, y: 0 });
+        view.paste(&text, true);
+        assert_eq!(buffer_lines(&view), ["  b", "a", "  b", "  b", "c"]);
+        assert_eq!((view.cursor.x, view.cursor.y), (2, 0));
+    }
+
+    #[test]
+    fn charwise_text_ending_with_a_newline_is_pasted_inside_the_line() {
+        let mut view = view("ab\ncd", 20, 10);
+
+        view.paste(&RegisterText::charwise("x\n"), false);
+
+        assert_eq!(buffer_lines(&view), ["ax", "b", "cd"]);
+    }
+
+    // the view of `text` in insert mode after typing `typed`
+    fn typed(text: &str, typed: &str) -> View {
+        let mut view = view(text, 20, 10);
+        view.enter_insert_mode();
+        typed.chars().for_each(|ch| view.insert_typed_char(ch));
+        view
+    }
+
+    #[test]
+    fn typing_an_opening_character_closes_it() {
+        let view = typed("", "f(");
+
+        assert_eq!(view.buffer().line(0), Some("f()"));
+        assert_eq!((view.cursor.x, view.cursor.y), (2, 0));
+    }
+
+    #[test]
+    fn typing_nested_openings_closes_both() {
+        let view = typed("", "({");
+
+        assert_eq!(view.buffer().line(0), Some("({})"));
+        assert_eq!((view.cursor.x, view.cursor.y), (2, 0));
+    }
+
+    #[test]
+    fn typing_a_closing_character_moves_over_it() {
+        let view = typed("", "f(x)");
+
+        assert_eq!(view.buffer().line(0), Some("f(x)"));
+        assert_eq!((view.cursor.x, view.cursor.y), (4, 0));
+        // only a closing character that is already there is typed over
+        assert_eq!(typed("", "a)").buffer().line(0), Some("a)"));
+    }
+
+    #[test]
+    fn typing_a_quote_after_a_word_does_not_close_it() {
+        assert_eq!(typed("", "don'").buffer().line(0), Some("don'"));
+        assert_eq!(typed("", "x = '").buffer().line(0), Some("x = ''"));
+    }
+
+    #[test]
+    fn save_trims_blank_lines_when_asked() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        fs::write(file.path(), "\na\n\n\nb\n\n").unwrap();
+        let mut view = View::new(Buffer::open(file.path()).unwrap());
+        view.buffer_mut().options_mut().trim_blank_lines_on_save = true;
+
+        view.save().unwrap();
+
+        assert_eq!(fs::read_to_string(file.path()).unwrap(), "a\n\nb\n");
+    }
+
+    #[test]
+    fn update_search_previews_from_where_the_search_began() {
+        let mut view = view("one\ntwo\none two", 20, 10);
+        view.begin_search();
+
+        view.update_search("t");
+        assert_eq!(view.cursor, Position { x: 0, y: 1 });
+        view.update_search("two");
+        assert_eq!(view.cursor, Position { x: 0, y: 1 });
+        view.update_search("one ");
+        assert_eq!(view.cursor, Position { x: 0, y: 2 });
+        view.update_search("nothing");
+        assert_eq!(view.cursor, Position { x: 0, y: 0 });
+    }
+
+    #[test]
+    fn cancel_search_goes_back_to_where_it_began() {
+        let mut view = view("one\ntwo", 20, 10);
+        view.begin_search();
+        view.update_search("two");
+
+        view.cancel_search();
+
+        assert_eq!(view.cursor, Position { x: 0, y: 0 });
+        assert_eq!(view.mode(), Mode::Normal);
+        assert_eq!(view.highlighted_query, None);
+    }
+
+    #[test]
+    fn update_search_does_nothing_without_incsearch() {
+        let mut view = view("one\ntwo", 20, 10);
+        view.options_mut().incsearch = false;
+        view.begin_search();
+
+        view.update_search("two");
+
+        assert_eq!(view.cursor, Position { x: 0, y: 0 });
+        assert_eq!(view.highlighted_query, None);
+    }
+
+    #[test]
+    fn scroll_to_cursor_counts_wrapped_rows() {
+        let text = ["abcdefgh"; 10].join("\n");
+        let mut view = view(&text, 4, 4);
+        view.options_mut().wrap = WrapMode::SoftWrap;
+
+        // down by rows, to the start of the fourth line
+        (0..6).for_each(|_| view.move_cursor_down());
+
+        // each line takes two rows, so only two fit
+        assert_eq!(view.scroll_offset, 2);
+    }
+
+    #[test]
+    fn debug_grid_draws_a_line_at_every_cell_boundary() {
+        let mut view = view("abc", 10, 4);
+        let theme = Theme::default();
+
+        let mut base = BaseAppRenderer::new();
+        view.render_debug_grid(&mut AppRenderer::from(&mut base), 8.0, 16.0, &theme);
+        // 11 column boundaries and 5 row boundaries
+        assert_eq!(base.path_count(), 11 + 5);
+
+        // the gutter of line numbers takes 3 cells
+        view.options_mut().number = true;
+        let mut base = BaseAppRenderer::new();
+        view.render_debug_grid(&mut AppRenderer::from(&mut base), 8.0, 16.0, &theme);
+        assert_eq!(base.path_count(), 8 + 5);
+    }
+
+    #[test]
+    fn gutter_width_follows_the_digits_of_the_line_count() {
+        let mut view = view(&["x"; 99].join("\n"), 20, 10);
+        assert_eq!(view.gutter_width(8.0), 0.0);
+
+        view.options_mut().number = true;
+        // two digits and a cell on either side
+        assert_eq!(view.gutter_width(8.0), 32.0);
+
+        view.buffer_mut().insert_lines(0, &["x".to_string()]);
+        assert_eq!(view.gutter_width(8.0), 40.0);
+        assert_eq!(view.text_width(), 15);
+    }
+
+    #[test]
+    fn views_without_focus_are_drawn_dimmed() {
+        let mut view = view("abc", 10, 4);
+        let font = test_font();
+        let theme = Theme::default();
+        let is_dimmed = |view: &View, focused| {
+            let mut base = BaseAppRenderer::new();
+            view.render(
+                &mut AppRenderer::from(&mut base),
+                &font,
+                16.0,
+                &theme,
+                focused,
+                true,
+            );
+            base.has_drawn_color(theme.inactive_dim_color)
+        };
+
+        assert!(is_dimmed(&view, false));
+        assert!(!is_dimmed(&view, true));
+
+        view.options_mut().dim_inactive = false;
+        assert!(!is_dimmed(&view, false));
+    }
+
+    // the text and color of each run of the first row
+    fn runs(view: &View, with_cursor: bool, theme: &Theme) -> Vec<(String, Color)> {
+        let row = view.visible_rows().next().unwrap();
+        let cursor_row = with_cursor.then_some(&row);
+
+        view.row_runs(&row, cursor_row, theme)
+            .into_iter()
+            .map(|run| (run.text, run.color))
+            .collect()
+    }
+
+    #[test]
+    fn row_runs_split_where_the_highlighting_changes() {
+        let mut view = view("int x; // hi", 20, 10);
+        view.buffer_mut().set_filetype(FileType::C);
+        view.update_highlights();
+        let theme = Theme::default();
+
+        assert_eq!(
+            runs(&view, false, &theme),
+            [
+                (
+                    "int".to_string(),
+                    theme.highlight_color(HighlightGroup::Keyword)
+                ),
+                (" x; ".to_string(), theme.foreground),
+                (
+                    "// hi".to_string(),
+                    theme.highlight_color(HighlightGroup::Comment)
+                ),
+            ]
+        );
+
+        let row = view.visible_rows().next().unwrap();
+        let looks: Vec<_> = view
+            .row_runs(&row, None, &theme)
+            .into_iter()
+            .map(|run| (run.weight, run.style))
+            .collect();
+        assert_eq!(
+            looks,
+            [
+                (FontWeight::Bold, FontStyle::Normal),
+                (FontWeight::Regular, FontStyle::Normal),
+                (FontWeight::Regular, FontStyle::Italic),
+            ]
+        );
+    }
+
+    #[test]
+    fn row_runs_draw_the_character_under_a_block_cursor_readably() {
+        let mut view = view("abc", 20, 10);
+        view.move_cursor_right();
+        let theme = Theme::default();
+
+        assert_eq!(
+            runs(&view, true, &theme),
+            [
+                ("a".to_string(), theme.foreground),
+                ("b".to_string(), theme.cursor_text),
+                ("c".to_string(), theme.foreground),
+            ]
+        );
+        assert_eq!(
+            runs(&view, false, &theme),
+            [("abc".to_string(), theme.foreground)]
+        );
+    }
+
+    #[test]
+    fn read_file_moves_to_the_first_line_read() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        fs::write(file.path(), "  x\ny\n").unwrap();
+        let mut view = view("a\nb", 20, 10);
+
+        view.read_file(0, file.path()).unwrap();
+
+        assert_eq!(view.buffer().line(1), Some("  x"));
+        assert_eq!(view.cursor(), Position { x: 2, y: 1 });
+    }
+
+    #[test]
+    fn cursor_moves_over_combining_marks_with_their_character() {
+        let mut view = view("ae\u{0301}b", 20, 10);
+
+        view.move_cursor_right();
+        assert_eq!(view.cursor(), Position { x: 1, y: 0 });
+        view.move_cursor_right();
+        assert_eq!(view.cursor(), Position { x: 3, y: 0 });
+        // already on the last grapheme
+        view.move_cursor_right();
+        assert_eq!(view.cursor(), Position { x: 3, y: 0 });
+
+        view.move_cursor_left();
+        assert_eq!(view.cursor(), Position { x: 1, y: 0 });
+    }
+
+    #[test]
+    fn wrapped_rows_are_shifted_by_the_showbreak_marker() {
+        let mut view = view("abcdefgh", 4, 10);
+        view.options_mut().wrap = WrapMode::SoftWrap;
+        view.options_mut().show_break = "> ".to_string();
+
+        let rows: Vec<_> = view
+            .visible_rows()
+            .map(|row| (row.start_col..row.end_col, row.indent))
+            .collect();
+
+        assert_eq!(rows, [(0..4, 0), (4..6, 2), (6..8, 2)]);
+    }
+
+    #[test]
+    fn the_cursor_line_is_not_concealed() {
+        let mut view = view("**a**\n**b**", 20, 10);
+        view.buffer_mut().set_filetype(FileType::Markdown);
+        view.options_mut().conceal_level = 2;
+
+        assert_eq!(view.conceals(0), []);
+        assert_eq!(view.conceals(1).len(), 2);
+
+        view.options_mut().conceal_level = 0;
+        assert_eq!(view.conceals(1), []);
+    }
+
+    #[test]
+    fn virtual_edit_moves_past_the_end_of_the_line() {
+        let mut view = view("ab\nc", 6, 10);
+        view.options_mut().virtual_edit = true;
+        view.options_mut().wrap = WrapMode::SoftWrap;
+
+        (0..10).for_each(|_| view.move_cursor_right());
+        // wrapped lines have virtual space up to the last cell of the view
+        assert_eq!(view.cursor(), Position { x: 5, y: 0 });
+
+        view.move_cursor_left();
+        assert_eq!(view.cursor(), Position { x: 4, y: 0 });
+    }
+
+    #[test]
+    fn virtual_edit_scrolls_past_the_edge_of_the_view() {
+        let mut view = view("ab\nc", 6, 10);
+        view.options_mut().virtual_edit = true;
+
+        (0..10).for_each(|_| view.move_cursor_right());
+
+        assert_eq!(view.cursor(), Position { x: 10, y: 0 });
+        assert!(view.scroll_offset_x > 0);
+        assert_eq!(
+            view.cursor_screen_pos(),
+            Some(Position {
+                x: 10 - view.scroll_offset_x,
+                y: 0
+            })
+        );
+
+        // the column is kept on shorter lines
+        view.move_cursor_down();
+        assert_eq!(view.cursor(), Position { x: 10, y: 1 });
+    }
+
+    #[test]
+    fn typing_in_virtual_space_pads_the_line() {
+        let mut view = view("ab", 20, 10);
+        view.options_mut().virtual_edit = true;
+        (0..4).for_each(|_| view.move_cursor_right());
+        view.enter_insert_mode();
+
+        view.insert_typed_char('x');
+
+        assert_eq!(view.buffer().line(0), Some("ab  x"));
+        assert_eq!(view.cursor(), Position { x: 5, y: 0 });
+    }
+
+    #[test]
+    fn backspace_in_virtual_space_only_moves_the_cursor() {
+        let mut view = view("ab", 20, 10);
+        view.options_mut().virtual_edit = true;
+        (0..4).for_each(|_| view.move_cursor_right());
+        view.enter_insert_mode();
+
+        view.remove_char_before_cursor();
+
+        assert_eq!(view.buffer().line(0), Some("ab"));
+        assert_eq!(view.cursor(), Position { x: 3, y: 0 });
+    }
+
+    #[test]
+    fn set_buffer_starts_at_the_last_change() {
+        let mut view = view("a", 20, 10);
+        let mut buffer = Buffer::from("one\ntwo\nthree");
+        buffer.insert_text(2, 3, "!");
+
+        view.set_buffer(buffer);
+        assert_eq!(view.cursor(), Position { x: 3, y: 2 });
+
+        view.set_buffer(Buffer::from("x\ny"));
+        assert_eq!(view.cursor(), Position { x: 0, y: 0 });
+    }
+
+    #[test]
+    fn long_substitutions_go_on_until_done_or_cancelled() {
+        let text = vec!["a"; SUBSTITUTE_CHUNK_LINES * 2].join("\n");
+        let substitute = || Substitute::parse("/a/b/").unwrap();
+        let scope = |view: &View| SubstituteScope {
+            lines: 0..=view.buffer().line_count() - 1,
+            cols: None,
+        };
+        let mut view = view(&text, 20, 10);
+
+        view.substitute(substitute(), scope(&view)).unwrap();
+        assert_eq!(view.substitute_progress(), Some(0.0));
+        assert!(view.continue_substitute().is_none());
+        assert_eq!(view.substitute_progress(), Some(0.5));
+        view.cancel_substitute();
+        assert_eq!(view.substitute_progress(), None);
+        assert_eq!(view.buffer().line(0), Some("a"));
+
+        view.substitute(substitute(), scope(&view)).unwrap();
+        while view.continue_substitute().is_none() {}
+        assert_eq!(
+            view.buffer().line(SUBSTITUTE_CHUNK_LINES * 2 - 1),
+            Some("b")
+        );
+    }
+
+    #[test]
+    fn save_keeps_no_cleanups_when_the_file_cannot_be_written() {
+        // without a file name there is nothing to write to
+        let mut view = view("a  \n\n\n\nb", 20, 10);
+        view.buffer_mut().options_mut().trim_blank_lines_on_save = true;
+        view.buffer_mut()
+            .options_mut()
+            .trim_trailing_whitespace_on_save = true;
+
+        assert!(view.save().is_err());
+
+        assert_eq!(view.buffer().line(0), Some("a  "));
+        assert_eq!(view.buffer().line_count(), 5);
+    }
+
+    fn pos(x: usize, y: usize) -> Position<usize> {
+        Position { x, y }
+    }
+
+    // the ends of what a motion moves over, in order, and whether it is linewise
+    fn moved_over(
+        view: &View,
+        from: Position<usize>,
+        to: Position<usize>,
+        kind: MotionKind,
+    ) -> Option<(Position<usize>, Position<usize>, SelectionKind)> {
+        let selection = view.motion_selection(from, to, kind)?;
+        let (start, end) = selection.ordered();
+        Some((start, end, selection.kind))
+    }
+
+    #[test]
+    fn exclusive_motions_leave_out_where_they_stop() {
+        let view = view("abc def\nghi", 20, 10);
+
+        assert_eq!(
+            moved_over(&view, pos(0, 0), pos(4, 0), MotionKind::Exclusive),
+            Some((pos(0, 0), pos(3, 0), SelectionKind::Char))
+        );
+        // stopping at the start of a line takes the line break before it
+        assert_eq!(
+            moved_over(&view, pos(4, 0), pos(0, 1), MotionKind::Exclusive),
+            Some((pos(4, 0), pos(7, 0), SelectionKind::Char))
+        );
+        assert_eq!(
+            moved_over(&view, pos(2, 0), pos(2, 0), MotionKind::Exclusive),
+            None
+        );
+    }
+
+    #[test]
+    fn inclusive_motions_stop_on_text() {
+        let view = view("abc\n\nxyz", 20, 10);
+
+        // backwards motions are the same as forwards ones
+        assert_eq!(
+            moved_over(&view, pos(2, 2), pos(0, 0), MotionKind::Inclusive),
+            Some((pos(0, 0), pos(2, 2), SelectionKind::Char))
+        );
+        assert_eq!(
+            moved_over(&view, pos(0, 0), pos(5, 0), MotionKind::Inclusive),
+            Some((pos(0, 0), pos(2, 0), SelectionKind::Char))
+        );
+        assert_eq!(
+            moved_over(&view, pos(0, 1), pos(0, 1), MotionKind::Inclusive),
+            None
+        );
+    }
+
+    #[test]
+    fn linewise_motions_take_whole_lines() {
+        let view = view("a\nb\nc", 20, 10);
+
+        assert_eq!(
+            moved_over(&view, pos(0, 2), pos(0, 1), MotionKind::Linewise),
+            Some((pos(0, 1), pos(0, 2), SelectionKind::Line))
+        );
+    }
+}
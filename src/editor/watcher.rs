@@ -0,0 +1,41 @@
+use std::{fs, path::Path};
+
+use anyhow::{anyhow, Result};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::base::{AppEvent, AppEventSender};
+
+/// Watches a file for changes made by other programs, sending
+/// `AppEvent::FileChangedOnDisk` for each of them. Watching stops when the
+/// watcher is dropped.
+pub struct FileWatcher {
+    _watcher: RecommendedWatcher,
+}
+
+impl FileWatcher {
+    pub fn new(path: &Path, sender: AppEventSender) -> Result<Self> {
+        let path = fs::canonicalize(path)?;
+        // the directory is watched, as the file itself may be replaced or deleted
+        let dir = path
+            .parent()
+            .ok_or_else(|| anyhow!("No directory to watch"))?
+            .to_path_buf();
+
+        let mut watcher = notify::recommended_watcher(move |result: notify::Result<Event>| {
+            let Ok(event) = result else {
+                return;
+            };
+
+            let is_change =
+                event.kind.is_create() || event.kind.is_modify() || event.kind.is_remove();
+            if is_change && event.paths.contains(&path) {
+                sender.send(AppEvent::FileChangedOnDisk {
+                    path: path.to_string_lossy().into_owned(),
+                });
+            }
+        })?;
+        watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+
+        Ok(Self { _watcher: watcher })
+    }
+}
@@ -0,0 +1,95 @@
+use crate::base::is_combining_mark;
+
+// ranges of characters that take up two cells, roughly following East Asian Width
+const WIDE_RANGES: &[(char, char)] = &[
+    ('\u{1100}', '\u{115F}'),
+    ('\u{2E80}', '\u{303E}'),
+    ('\u{3041}', '\u{33FF}'),
+    ('\u{3400}', '\u{4DBF}'),
+    ('\u{4E00}', '\u{9FFF}'),
+    ('\u{A000}', '\u{A4CF}'),
+    ('\u{AC00}', '\u{D7A3}'),
+    ('\u{F900}', '\u{FAFF}'),
+    ('\u{FE30}', '\u{FE4F}'),
+    ('\u{FF00}', '\u{FF60}'),
+    ('\u{FFE0}', '\u{FFE6}'),
+    ('\u{1F300}', '\u{1F64F}'),
+    ('\u{1F900}', '\u{1F9FF}'),
+    ('\u{20000}', '\u{3FFFD}'),
+];
+
+// ranges of invisible characters, other than combining marks
+const ZERO_WIDTH_RANGES: &[(char, char)] = &[('\u{200B}', '\u{200F}'), ('\u{FE00}', '\u{FE0F}')];
+
+fn in_ranges(ch: char, ranges: &[(char, char)]) -> bool {
+    ranges
+        .iter()
+        .any(|(start, end)| (*start..=*end).contains(&ch))
+}
+
+/// Number of cells `ch` takes up. Tabs are not handled here, as their width
+/// depends on where they are in the line.
+pub fn char_width(ch: char) -> usize {
+    if ch.is_control() || is_combining_mark(ch) || in_ranges(ch, ZERO_WIDTH_RANGES) {
+        0
+    } else if in_ranges(ch, WIDE_RANGES) {
+        2
+    } else {
+        1
+    }
+}
+
+/// Number of cells `ch` takes up when it starts at display column `display_col`.
+pub fn char_width_at(ch: char, display_col: usize, tab_width: usize) -> usize {
+    if ch == '\t' {
+        tab_width - display_col % tab_width
+    } else {
+        char_width(ch)
+    }
+}
+
+/// Converts a column (in chars) of `line` into a display column (in cells),
+/// expanding tabs to the next multiple of `tab_width`.
+pub fn display_col(line: &str, col: usize, tab_width: usize) -> usize {
+    line.chars().take(col).fold(0, |display_col, ch| {
+        display_col + char_width_at(ch, display_col, tab_width)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn char_width_counts_cells() {
+        assert_eq!(char_width('a'), 1);
+        assert_eq!(char_width('漢'), 2);
+        assert_eq!(char_width('한'), 2);
+        assert_eq!(char_width('\u{0301}'), 0);
+        assert_eq!(char_width('\u{200B}'), 0);
+    }
+
+    #[test]
+    fn tabs_reach_the_next_tab_stop() {
+        assert_eq!(char_width_at('\t', 0, 4), 4);
+        assert_eq!(char_width_at('\t', 3, 4), 1);
+        assert_eq!(char_width_at('\t', 4, 4), 4);
+    }
+
+    #[test]
+    fn display_col_expands_tabs_and_wide_chars() {
+        assert_eq!(display_col("ab\tc", 3, 8), 8);
+        assert_eq!(display_col("ab\tc", 4, 8), 9);
+        assert_eq!(display_col("漢字a", 2, 8), 4);
+        assert_eq!(display_col("ab", 5, 8), 2);
+    }
+
+    #[test]
+    fn combining_marks_share_the_cell_of_their_base() {
+        // e followed by a combining acute
+        let line = "e\u{0301}x";
+
+        assert_eq!(display_col(line, 2, 8), 1);
+        assert_eq!(display_col(line, 3, 8), 2);
+    }
+}
@@ -1,5 +1,6 @@
 mod app;
 mod base;
+mod editor;
 
 use app::App;
 